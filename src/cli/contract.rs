@@ -5,9 +5,10 @@ use argh::FromArgs;
 use nekoton_abi::FunctionExt;
 use ton_block::{Deserializable, Serializable};
 
+use super::offline::export_message;
 use super::CliContext;
-use crate::config::{AppConfigValidator, StoredKeys};
-use crate::contracts::{wallet, InternalMessage, ONE_EVER};
+use crate::config::AppConfigValidator;
+use crate::contracts::{wallet, InternalMessage, WalletSigner, ONE_EVER};
 use crate::network::{NodeTcpRpc, NodeUdpRpc, Subscription};
 use crate::util::*;
 
@@ -290,7 +291,7 @@ impl CmdSendx {
             hash: tx_hash,
             data: tx,
         } = subscription
-            .send_message(&message, expire_at)
+            .send_message(&message, expire_at, "contract send")
             .await?
             .context("message expired")?;
 
@@ -359,6 +360,14 @@ struct CmdSend {
     /// interpret amount as amount in nano tokens
     #[argh(switch)]
     nano: bool,
+
+    /// save an unsigned message to the file instead of sending it
+    #[argh(option)]
+    offline: Option<PathBuf>,
+
+    /// expiration timeout of the unsigned message in seconds
+    #[argh(option, default = "3600")]
+    offline_timeout: u32,
 }
 
 impl CmdSend {
@@ -402,21 +411,15 @@ impl CmdSend {
         let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
 
         // Prepare wallet
-        let keypair = StoredKeys::load(&ctx.dirs.validator_keys)
-            .context("failed to load validator wallet keys")?
-            .as_keypair();
+        let signer = WalletSigner::load(config.signer.as_ref(), &ctx.dirs)?;
 
-        let wallet_address = match validator {
-            AppConfigValidator::Single(single) => single.address,
-            AppConfigValidator::DePool(depool) => depool.owner,
+        let (wallet_address, wallet_type) = match validator {
+            AppConfigValidator::Single(single) => (single.address, single.wallet_type),
+            AppConfigValidator::DePool(depool) => (depool.owner, depool.wallet_type),
         };
 
         let wallet =
-            wallet::Wallet::new(wallet_address.workchain_id() as i8, keypair, subscription);
-        anyhow::ensure!(
-            wallet.address() == &wallet_address,
-            "validator wallet address mismatch"
-        );
+            wallet::Wallet::with_signer(wallet_type, wallet_address, signer, subscription)?;
 
         // Check wallet balance
         let wallet_balance = wallet.get_balance().await?.unwrap_or_default();
@@ -426,18 +429,24 @@ impl CmdSend {
             Tokens(wallet_balance)
         );
 
+        let message = InternalMessage {
+            dst: dest,
+            amount,
+            payload,
+            bounce: self.bounce,
+        };
+        if let Some(path) = &self.offline {
+            return export_message(&wallet, message, self.offline_timeout, path).await;
+        }
+
+        // Prevent concurrent usage of the wallet
+        let _wallet_lock = ctx.dirs.try_lock_wallet("contract send")?;
+
         // Send external message and wait until it is delivered
         let TransactionWithHash {
             hash: tx_hash,
             data: tx,
-        } = wallet
-            .transfer(InternalMessage {
-                dst: dest,
-                amount,
-                payload,
-                bounce: self.bounce,
-            })
-            .await?;
+        } = wallet.transfer(message).await?;
 
         // Parse transaction
         let msg_hash = tx.in_msg.context("inbound message not found")?.hash();
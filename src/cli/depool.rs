@@ -0,0 +1,962 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use dialoguer::console::style;
+
+use super::offline::export_message;
+use super::CliContext;
+use crate::config::{AppConfigSigner, AppConfigValidator, DePoolType, StoredKeys, WalletType};
+use crate::contracts::depool::{self, DePool};
+use crate::contracts::{wallet, WalletSigner, ONE_EVER};
+use crate::network::{NodeTcpRpc, NodeUdpRpc, Subscription};
+use crate::util::*;
+use crate::validator::rewards::{self, RewardHistory};
+
+#[derive(FromArgs)]
+/// DePool management stuff
+#[argh(subcommand, name = "depool")]
+pub struct Cmd {
+    #[argh(subcommand)]
+    subcommand: SubCmd,
+}
+
+impl Cmd {
+    pub async fn run(self, ctx: CliContext) -> Result<()> {
+        match self.subcommand {
+            SubCmd::Rounds(cmd) => cmd.run(ctx).await,
+            SubCmd::Info(cmd) => cmd.run(ctx).await,
+            SubCmd::Participants(cmd) => cmd.run(ctx).await,
+            SubCmd::Rewards(cmd) => cmd.run(ctx).await,
+            SubCmd::Allowed(cmd) => cmd.run(ctx).await,
+            SubCmd::Close(cmd) => invoke_as_cli(cmd.run(ctx)).await,
+        }
+    }
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum SubCmd {
+    Rounds(CmdRounds),
+    Info(CmdInfo),
+    Participants(CmdParticipants),
+    Rewards(CmdRewards),
+    Allowed(CmdAllowed),
+    Close(CmdClose),
+}
+
+#[derive(FromArgs)]
+/// Prints the DePool rounds
+#[argh(subcommand, name = "rounds")]
+struct CmdRounds {
+    /// output format: `json`, `table` or `csv`. `json` default
+    #[argh(option, default = "OutputFormat::Json")]
+    format: OutputFormat,
+}
+
+impl CmdRounds {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let ctx = DePoolStateContext::new(&ctx).await?;
+
+        let state = ctx.depool.get_state().await?;
+        let rounds = ctx
+            .depool
+            .get_rounds(&state)
+            .context("failed to get DePool rounds")?;
+
+        match self.format {
+            OutputFormat::Json => {
+                let rounds = rounds.values().map(describe_round).collect::<Vec<_>>();
+                print_output(serde_json::json!({
+                    "depool": ctx.depool.address().to_string(),
+                    "rounds": rounds,
+                }));
+            }
+            OutputFormat::Table | OutputFormat::Csv => {
+                let currency = ctx.currency;
+                let rows = rounds
+                    .values()
+                    .map(|round| {
+                        vec![
+                            round.id.to_string(),
+                            format!("{:?}", round.step),
+                            format!("{:?}", round.completion_reason),
+                            format!("{} {currency}", Tokens(round.stake)),
+                            format!("{} {currency}", Tokens(round.validator_stake)),
+                            format!("{} {currency}", Tokens(round.participant_reward)),
+                            round.participant_qty.to_string(),
+                            round.supposed_elected_at.to_string(),
+                            round.unfreeze.to_string(),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+
+                self.format.print_rows(
+                    &[
+                        "ID",
+                        "STEP",
+                        "COMPLETION",
+                        "STAKE",
+                        "VALIDATOR STAKE",
+                        "REWARD",
+                        "PARTICIPANTS",
+                        "ELECTED AT",
+                        "UNFREEZE",
+                    ],
+                    &rows,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Prints the DePool params, proxies and the validator participant info
+#[argh(subcommand, name = "info")]
+struct CmdInfo {
+    /// output format: `json` or `table`. `json` default
+    #[argh(option, default = "OutputFormat::Json")]
+    format: OutputFormat,
+}
+
+impl CmdInfo {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let ctx = DePoolStateContext::new(&ctx).await?;
+
+        let state = ctx.depool.get_state().await?;
+        let info = ctx
+            .depool
+            .get_info(&state)
+            .context("failed to get DePool info")?;
+        let participant = ctx
+            .depool
+            .get_participant_info(&state, &ctx.owner)
+            .context("failed to get participant info")?;
+
+        let mut proxies = Vec::with_capacity(info.proxies.len());
+        for proxy in &info.proxies {
+            let balance = ctx
+                .subscription
+                .get_account_state(proxy)
+                .await
+                .context("failed to get proxy state")?
+                .map(|account| account.storage.balance.grams.as_u128());
+            proxies.push((proxy.clone(), balance));
+        }
+
+        let balance = state.storage.balance.grams.as_u128();
+
+        match self.format {
+            OutputFormat::Json => print_output(serde_json::json!({
+                "depool": ctx.depool.address().to_string(),
+                "balance": balance.to_string(),
+                "pool_closed": info.pool_closed,
+                "min_stake": info.min_stake.to_string(),
+                "validator_assurance": info.validator_assurance.to_string(),
+                "participant_reward_fraction": info.participant_reward_fraction,
+                "validator_reward_fraction": info.validator_reward_fraction,
+                "balance_threshold": info.balance_threshold.to_string(),
+                "validator_wallet": info.validator_wallet.to_string(),
+                "stake_fee": info.stake_fee.to_string(),
+                "return_or_reinvest_fee": info.return_or_reinvest_fee.to_string(),
+                "proxy_fee": info.proxy_fee.to_string(),
+                "proxies": proxies
+                    .iter()
+                    .map(|(address, balance)| serde_json::json!({
+                        "address": address.to_string(),
+                        "balance": balance.map(|balance| balance.to_string()),
+                    }))
+                    .collect::<Vec<_>>(),
+                "participant": participant.as_ref().map(describe_participant),
+            })),
+            OutputFormat::Table => {
+                let currency = ctx.currency;
+                let tokens = |amount: u64| format!("{} {currency}", Tokens(amount));
+
+                let rows = [
+                    ("DePool", ctx.depool.address().to_string()),
+                    ("Balance", format!("{} {currency}", Tokens(balance))),
+                    ("Pool closed", info.pool_closed.to_string()),
+                    ("Min stake", tokens(info.min_stake)),
+                    ("Validator assurance", tokens(info.validator_assurance)),
+                    (
+                        "Participant reward fraction",
+                        format!("{}%", info.participant_reward_fraction),
+                    ),
+                    (
+                        "Validator reward fraction",
+                        format!("{}%", info.validator_reward_fraction),
+                    ),
+                    ("Balance threshold", tokens(info.balance_threshold)),
+                    ("Validator wallet", info.validator_wallet.to_string()),
+                    ("Stake fee", tokens(info.stake_fee)),
+                    (
+                        "Return or reinvest fee",
+                        tokens(info.return_or_reinvest_fee),
+                    ),
+                    ("Proxy fee", tokens(info.proxy_fee)),
+                ]
+                .into_iter()
+                .map(|(key, value)| vec![key.to_owned(), value])
+                .collect::<Vec<_>>();
+                print_table(&["PARAM", "VALUE"], &rows);
+
+                println!();
+                let rows = proxies
+                    .iter()
+                    .map(|(address, balance)| {
+                        vec![
+                            address.to_string(),
+                            match balance {
+                                Some(balance) => format!("{} {currency}", Tokens(*balance)),
+                                None => "not deployed".to_owned(),
+                            },
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                print_table(&["PROXY", "BALANCE"], &rows);
+
+                println!();
+                match &participant {
+                    Some(participant) => {
+                        let rows = [
+                            ("Total", tokens(participant.total)),
+                            ("Withdraw value", tokens(participant.withdraw_value)),
+                            ("Reinvest", participant.reinvest.to_string()),
+                            ("Reward", tokens(participant.reward)),
+                        ]
+                        .into_iter()
+                        .map(|(key, value)| vec![key.to_owned(), value])
+                        .collect::<Vec<_>>();
+                        print_table(&["PARTICIPANT", "VALUE"], &rows);
+
+                        println!();
+                        let rows = participant_rounds(participant)
+                            .map(|round| {
+                                let complex = |stake: Option<&depool::ComplexStake>| {
+                                    stake
+                                        .map(|stake| tokens(stake.remaining_amount))
+                                        .unwrap_or_default()
+                                };
+                                vec![
+                                    round.to_string(),
+                                    participant
+                                        .stakes
+                                        .get(&round)
+                                        .map(|&stake| tokens(stake))
+                                        .unwrap_or_default(),
+                                    complex(participant.vestings.get(&round)),
+                                    complex(participant.locks.get(&round)),
+                                ]
+                            })
+                            .collect::<Vec<_>>();
+                        print_table(&["ROUND", "ORDINARY", "VESTING", "LOCK"], &rows);
+                    }
+                    None => println!("Validator wallet is not a DePool participant"),
+                }
+            }
+            OutputFormat::Csv => anyhow::bail!("csv output is not supported for the DePool info"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Prints all DePool participants sorted by stake
+#[argh(subcommand, name = "participants")]
+struct CmdParticipants {
+    /// output format: `json`, `table` or `csv`. `json` default
+    #[argh(option, default = "OutputFormat::Json")]
+    format: OutputFormat,
+}
+
+impl CmdParticipants {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let ctx = DePoolStateContext::new(&ctx).await?;
+
+        let state = ctx.depool.get_state().await?;
+        let addresses = ctx
+            .depool
+            .get_participants(&state)
+            .context("failed to get DePool participants")?;
+
+        let mut participants = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            if let Some(info) = ctx
+                .depool
+                .get_participant_info(&state, &address)
+                .context("failed to get participant info")?
+            {
+                participants.push((address, info));
+            }
+        }
+        participants.sort_by(|(_, a), (_, b)| b.total.cmp(&a.total));
+
+        match self.format {
+            OutputFormat::Json => print_output(serde_json::json!({
+                "depool": ctx.depool.address().to_string(),
+                "participants": participants
+                    .iter()
+                    .map(|(address, info)| {
+                        let mut participant = describe_participant(info);
+                        participant["address"] = address.to_string().into();
+                        participant
+                    })
+                    .collect::<Vec<_>>(),
+            })),
+            OutputFormat::Table | OutputFormat::Csv => {
+                let complex = |stakes: &BTreeMap<u64, depool::ComplexStake>| {
+                    stakes
+                        .values()
+                        .map(|stake| stake.remaining_amount)
+                        .sum::<u64>()
+                };
+
+                let rows = participants
+                    .iter()
+                    .map(|(address, info)| {
+                        vec![
+                            address.to_string(),
+                            Tokens(info.total).to_string(),
+                            Tokens(info.stakes.values().sum::<u64>()).to_string(),
+                            Tokens(complex(&info.vestings)).to_string(),
+                            Tokens(complex(&info.locks)).to_string(),
+                            Tokens(info.reward).to_string(),
+                            Tokens(info.withdraw_value).to_string(),
+                            info.reinvest.to_string(),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+
+                self.format.print_rows(
+                    &[
+                        "ADDRESS", "TOTAL", "ORDINARY", "VESTING", "LOCK", "REWARD", "WITHDRAW",
+                        "REINVEST",
+                    ],
+                    &rows,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Prints rewards and yields of the completed DePool rounds
+#[argh(subcommand, name = "rewards")]
+struct CmdRewards {
+    /// output format: `json`, `table` or `csv`. `json` default
+    #[argh(option, default = "OutputFormat::Json")]
+    format: OutputFormat,
+}
+
+impl CmdRewards {
+    async fn run(self, cli_ctx: CliContext) -> Result<()> {
+        let ctx = DePoolStateContext::new(&cli_ctx).await?;
+
+        // Save the current rounds to the history
+        let state = ctx.depool.get_state().await?;
+        let rounds = ctx
+            .depool
+            .get_rounds(&state)
+            .context("failed to get DePool rounds")?;
+
+        let history = RewardHistory::new(&cli_ctx.dirs.depool_rewards);
+        history.update(ctx.depool.address(), &rounds)?;
+
+        let mut entries = history.load()?;
+        entries.retain(|entry| &entry.depool == ctx.depool.address());
+
+        match self.format {
+            OutputFormat::Json => print_output(serde_json::json!({
+                "depool": ctx.depool.address().to_string(),
+                "average_annualized_yield": rewards::average_annualized_yield(&entries),
+                "rounds": entries
+                    .iter()
+                    .map(|entry| serde_json::json!({
+                        "id": entry.id,
+                        "supposed_elected_at": entry.supposed_elected_at,
+                        "unfreeze": entry.unfreeze,
+                        "completion_reason": entry.completion_reason,
+                        "stake": entry.stake.to_string(),
+                        "validator_stake": entry.validator_stake.to_string(),
+                        "participant_qty": entry.participant_qty,
+                        "total_reward": entry.total_reward().to_string(),
+                        "participant_reward": entry.participant_reward.to_string(),
+                        "validator_reward": entry.validator_reward().to_string(),
+                        "validator_fee_share": entry.validator_fee_share(),
+                        "round_yield": entry.round_yield(),
+                        "annualized_yield": entry.annualized_yield(),
+                    }))
+                    .collect::<Vec<_>>(),
+            })),
+            OutputFormat::Table | OutputFormat::Csv => {
+                let percent = |value: f64| format!("{:.2}%", value * 100.0);
+                let signed_tokens = |value: i128| match value {
+                    value if value < 0 => format!("-{}", Tokens(value.unsigned_abs())),
+                    value => Tokens(value.unsigned_abs()).to_string(),
+                };
+
+                let rows = entries
+                    .iter()
+                    .map(|entry| {
+                        vec![
+                            entry.id.to_string(),
+                            entry.supposed_elected_at.to_string(),
+                            entry.completion_reason.clone(),
+                            Tokens(entry.stake).to_string(),
+                            signed_tokens(entry.total_reward()),
+                            Tokens(entry.participant_reward).to_string(),
+                            percent(entry.validator_fee_share()),
+                            percent(entry.round_yield()),
+                            percent(entry.annualized_yield()),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+
+                self.format.print_rows(
+                    &[
+                        "ID",
+                        "ELECTED AT",
+                        "COMPLETION",
+                        "STAKE",
+                        "REWARD",
+                        "PARTICIPANT REWARD",
+                        "VALIDATOR FEE",
+                        "YIELD",
+                        "ANNUALIZED",
+                    ],
+                    &rows,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Allowed participants of the stEVER DePool (they can't be removed, the contract has no such method)
+#[argh(subcommand, name = "allowed")]
+struct CmdAllowed {
+    #[argh(subcommand)]
+    subcommand: AllowedSubCmd,
+}
+
+impl CmdAllowed {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        match self.subcommand {
+            AllowedSubCmd::List(cmd) => cmd.run(ctx).await,
+            AllowedSubCmd::Add(cmd) => invoke_as_cli(cmd.run(ctx)).await,
+        }
+    }
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum AllowedSubCmd {
+    List(CmdAllowedList),
+    Add(CmdAllowedAdd),
+}
+
+#[derive(FromArgs)]
+/// Prints the allowed participants
+#[argh(subcommand, name = "list")]
+struct CmdAllowedList {
+    /// output format: `json`, `table` or `csv`. `json` default
+    #[argh(option, default = "OutputFormat::Json")]
+    format: OutputFormat,
+}
+
+impl CmdAllowedList {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let ctx = DePoolStateContext::new(&ctx).await?;
+        ensure_allowed_participants(ctx.depool_type)?;
+
+        let state = ctx.depool.get_state().await?;
+        let participants = ctx
+            .depool
+            .get_allowed_participants(&state)
+            .context("failed to get allowed participants")?;
+
+        match self.format {
+            OutputFormat::Json => print_output(serde_json::json!({
+                "depool": ctx.depool.address().to_string(),
+                "allowed_participants": participants
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            })),
+            OutputFormat::Table | OutputFormat::Csv => {
+                let rows = participants
+                    .iter()
+                    .map(|address| vec![address.to_string()])
+                    .collect::<Vec<_>>();
+                self.format.print_rows(&["ADDRESS"], &rows);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Allows the address to participate in the stEVER DePool
+#[argh(subcommand, name = "add")]
+struct CmdAllowedAdd {
+    /// participant address
+    #[argh(positional)]
+    address: String,
+
+    /// never prompt
+    #[argh(switch, short = 'f')]
+    force: bool,
+
+    /// save an unsigned message to the file instead of sending it
+    #[argh(option)]
+    offline: Option<PathBuf>,
+
+    /// expiration timeout of the unsigned message in seconds
+    #[argh(option, default = "3600")]
+    offline_timeout: u32,
+}
+
+impl CmdAllowedAdd {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let address = parse_address(&self.address).context("invalid participant address")?;
+
+        let state_ctx = DePoolStateContext::new(&ctx).await?;
+        ensure_allowed_participants(state_ctx.depool_type)?;
+        let DePoolStateContext {
+            currency, depool, ..
+        } = &state_ctx;
+
+        // Check allowed participants
+        let state = depool.get_state().await?;
+        let participants = depool
+            .get_allowed_participants(&state)
+            .context("failed to get allowed participants")?;
+        anyhow::ensure!(
+            !participants.contains(&address),
+            "participant is already allowed"
+        );
+
+        // Check wallet balance
+        let wallet = state_ctx.load_wallet(&ctx)?;
+        let wallet_balance = wallet.get_balance().await?.unwrap_or_default();
+        anyhow::ensure!(
+            ONE_EVER * 2 < wallet_balance,
+            "wallet balance is not enough ({} {currency})",
+            Tokens(wallet_balance)
+        );
+
+        if is_terminal() {
+            eprintln!(
+                "{}\n{}\n{}\n{}\n\n{}\n{}\n",
+                style("Wallet address:").green().bold(),
+                style(wallet.address()).bold(),
+                style("DePool address:").green().bold(),
+                style(depool.address()).bold(),
+                style("Participant to allow:").green().bold(),
+                style(&address).bold(),
+            );
+
+            if !self.force
+                && !confirm(
+                    &dialoguer::theme::ColorfulTheme::default(),
+                    false,
+                    "Do you really want to allow this participant?",
+                )?
+            {
+                return Ok(());
+            }
+        }
+
+        let message = depool.set_allowed_participant(&address)?;
+        if let Some(path) = &self.offline {
+            let output = export_message(&wallet, message, self.offline_timeout, path).await?;
+            print_output(output);
+            return Ok(());
+        }
+
+        // Prevent concurrent usage of the wallet
+        let _wallet_lock = ctx.dirs.try_lock_wallet("validator depool allowed add")?;
+
+        // Send external message and wait until it is delivered
+        let TransactionWithHash {
+            hash: tx_hash,
+            data: tx,
+        } = wallet.transfer(message).await?;
+
+        // Parse transaction
+        let msg_hash = tx
+            .in_msg
+            .context("external inbound message not found")?
+            .hash();
+
+        // Done
+        print_output(serde_json::json!({
+            "tx_hash": tx_hash.to_hex_string(),
+            "msg_hash": msg_hash.to_hex_string(),
+        }));
+        Ok(())
+    }
+}
+
+/// Only stEVER DePools have the list of allowed participants
+fn ensure_allowed_participants(depool_type: DePoolType) -> Result<()> {
+    anyhow::ensure!(
+        depool_type.is_stever(),
+        "allowed participants are only supported by stEVER DePools (configured type: {depool_type:?})"
+    );
+    Ok(())
+}
+
+#[derive(FromArgs)]
+/// Closes the DePool and waits until all stakes are returned
+#[argh(subcommand, name = "close")]
+struct CmdClose {
+    /// never prompt
+    #[argh(switch, short = 'f')]
+    force: bool,
+
+    /// only send the closing message without waiting for stakes return
+    #[argh(switch)]
+    no_wait: bool,
+
+    /// rounds polling interval in seconds
+    #[argh(option, default = "300")]
+    interval: u64,
+
+    /// remove the validator entry from the config after all stakes are returned
+    #[argh(switch)]
+    disable_validator: bool,
+}
+
+impl CmdClose {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let DePoolStateContext {
+            currency,
+            mut depool,
+            subscription,
+            ..
+        } = DePoolStateContext::new(&ctx).await?;
+
+        let state = depool.get_state().await?;
+        let info = depool
+            .get_info(&state)
+            .context("failed to get DePool info")?;
+
+        if info.pool_closed {
+            if is_terminal() {
+                eprintln!("{}\n", style("DePool is already closed").green().bold());
+            }
+        } else {
+            let pending = get_pending_stakes(&depool, &state)?;
+
+            if is_terminal() {
+                eprintln!(
+                    "{}\n{}\n{}\n{}\n{}\n{}\n",
+                    style("DePool address:").green().bold(),
+                    style(depool.address()).bold(),
+                    style("Participants:").green().bold(),
+                    style(pending.participants).bold(),
+                    style("Stakes to return:").green().bold(),
+                    style(format!("{} {currency}", Tokens(pending.amount))).bold(),
+                );
+
+                if !self.force
+                    && !confirm(
+                        &dialoguer::theme::ColorfulTheme::default(),
+                        false,
+                        "Do you really want to close the DePool? This can't be undone",
+                    )?
+                {
+                    return Ok(());
+                }
+            }
+
+            let keypair = StoredKeys::load(&ctx.dirs.depool_keys)
+                .context("failed to load DePool keys")?
+                .as_keypair();
+            depool.set_keypair(keypair)?;
+
+            // Send external message and wait until it is delivered
+            depool.close().await?;
+        }
+
+        if self.no_wait {
+            print_output(serde_json::json!({
+                "depool": depool.address().to_string(),
+                "closed": true,
+            }));
+            return Ok(());
+        }
+
+        // Wait until all participant stakes are returned
+        let interval = Duration::from_secs(std::cmp::max(self.interval, 10));
+        loop {
+            // DePool is destroyed after all rounds are completed
+            let Some(state) = subscription.get_account_state(depool.address()).await? else {
+                break;
+            };
+
+            let pending = get_pending_stakes(&depool, &state)?;
+            if pending.participants == 0 {
+                break;
+            }
+
+            if is_terminal() {
+                let rounds = depool
+                    .get_rounds(&state)
+                    .context("failed to get DePool rounds")?
+                    .values()
+                    .map(|round| format!("{}:{:?}", round.id, round.step))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                eprintln!(
+                    "{} {} {}",
+                    style("Waiting for stakes return:").green().bold(),
+                    style(format!(
+                        "{} participants, {} {currency}",
+                        pending.participants,
+                        Tokens(pending.amount)
+                    ))
+                    .bold(),
+                    note(format!("rounds {rounds}")),
+                );
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+
+        // Report leftover balances
+        let depool_balance = depool.get_balance().await?;
+        let mut proxies = Vec::with_capacity(info.proxies.len());
+        for proxy in &info.proxies {
+            let balance = subscription
+                .get_account_state(proxy)
+                .await
+                .context("failed to get proxy state")?
+                .map(|account| account.storage.balance.grams.as_u128());
+            proxies.push(serde_json::json!({
+                "address": proxy.to_string(),
+                "balance": balance.map(|balance| balance.to_string()),
+            }));
+        }
+
+        // Stop the validator manager from participating in elections
+        if self.disable_validator {
+            let mut config = ctx.load_config()?;
+            config.validator = None;
+            config
+                .store(&ctx.dirs.app_config)
+                .context("failed to update the app config")?;
+        }
+
+        print_output(serde_json::json!({
+            "depool": depool.address().to_string(),
+            "closed": true,
+            "depool_balance": depool_balance.map(|balance| balance.to_string()),
+            "proxies": proxies,
+            "validator_disabled": self.disable_validator,
+        }));
+        Ok(())
+    }
+}
+
+struct PendingStakes {
+    participants: usize,
+    amount: u64,
+}
+
+/// Computes participant stakes which are not yet returned
+fn get_pending_stakes(depool: &DePool, state: &ton_block::AccountStuff) -> Result<PendingStakes> {
+    let mut pending = PendingStakes {
+        participants: 0,
+        amount: 0,
+    };
+
+    let participants = depool
+        .get_participants(state)
+        .context("failed to get DePool participants")?;
+    for address in participants {
+        let Some(info) = depool
+            .get_participant_info(state, &address)
+            .context("failed to get participant info")?
+        else {
+            continue;
+        };
+
+        if info.total > 0 {
+            pending.participants += 1;
+            pending.amount = pending.amount.saturating_add(info.total);
+        }
+    }
+
+    Ok(pending)
+}
+
+struct DePoolStateContext {
+    currency: &'static str,
+    depool_type: DePoolType,
+    owner: ton_block::MsgAddressInt,
+    wallet_type: WalletType,
+    signer: Option<AppConfigSigner>,
+    depool: DePool,
+    subscription: Arc<Subscription>,
+}
+
+impl DePoolStateContext {
+    async fn new(ctx: &CliContext) -> Result<Self> {
+        // Load config
+        let mut config = ctx.load_config()?;
+        let validator = match config.validator.take() {
+            Some(AppConfigValidator::DePool(depool)) => depool,
+            Some(AppConfigValidator::Single(_)) => {
+                anyhow::bail!("validator is not configured as a depool");
+            }
+            None => {
+                anyhow::bail!("validator entry not found in the app config");
+            }
+        };
+
+        // Prepare RPC clients
+        let node_tcp_rpc = NodeTcpRpc::new(config.control()?)
+            .await
+            .context("failed to build node TCP client")?;
+        let node_udp_rpc = NodeUdpRpc::new(config.adnl()?)
+            .await
+            .context("failed to build node UDP client")?;
+
+        let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
+        subscription.ensure_ready().await?;
+
+        // Prepare depool
+        let depool = DePool::new(
+            validator.depool_type,
+            validator.depool,
+            subscription.clone(),
+        );
+
+        Ok(Self {
+            currency: config.currency(),
+            depool_type: validator.depool_type,
+            owner: validator.owner,
+            wallet_type: validator.wallet_type,
+            signer: config.signer,
+            depool,
+            subscription,
+        })
+    }
+
+    /// Prepares the DePool owner wallet (loads keys only when needed)
+    fn load_wallet(&self, ctx: &CliContext) -> Result<wallet::Wallet> {
+        let signer = WalletSigner::load(self.signer.as_ref(), &ctx.dirs)?;
+        wallet::Wallet::with_signer(
+            self.wallet_type,
+            self.owner.clone(),
+            signer,
+            self.subscription.clone(),
+        )
+    }
+}
+
+/// Returns all rounds with the participant stakes
+fn participant_rounds(participant: &depool::ParticipantInfo) -> impl Iterator<Item = u64> {
+    let mut rounds = participant
+        .stakes
+        .keys()
+        .chain(participant.vestings.keys())
+        .chain(participant.locks.keys())
+        .copied()
+        .collect::<Vec<_>>();
+    rounds.sort_unstable();
+    rounds.dedup();
+    rounds.into_iter()
+}
+
+fn describe_round(round: &depool::Round) -> serde_json::Value {
+    serde_json::json!({
+        "id": round.id,
+        "step": format!("{:?}", round.step),
+        "completion_reason": format!("{:?}", round.completion_reason),
+        "supposed_elected_at": round.supposed_elected_at,
+        "unfreeze": round.unfreeze,
+        "stake_held_for": round.stake_held_for,
+        "vset_hash_in_election_phase": round.vset_hash_in_election_phase.to_hex_string(),
+        "stake": round.stake.to_string(),
+        "recovered_stake": round.recovered_stake.to_string(),
+        "unused": round.unused.to_string(),
+        "is_validator_stake_completed": round.is_validator_stake_completed,
+        "participant_reward": round.participant_reward.to_string(),
+        "participant_qty": round.participant_qty,
+        "validator_stake": round.validator_stake.to_string(),
+        "validator_remaining_stake": round.validator_remaining_stake.to_string(),
+        "handled_stakes_and_rewards": round.handled_stakes_and_rewards.to_string(),
+    })
+}
+
+fn describe_participant(participant: &depool::ParticipantInfo) -> serde_json::Value {
+    let describe_complex = |stake: &depool::ComplexStake| {
+        serde_json::json!({
+            "remaining_amount": stake.remaining_amount.to_string(),
+            "last_withdrawal_time": stake.last_withdrawal_time,
+            "withdrawal_period": stake.withdrawal_period,
+            "withdrawal_value": stake.withdrawal_value.to_string(),
+            "owner": stake.owner.to_string(),
+        })
+    };
+
+    serde_json::json!({
+        "total": participant.total.to_string(),
+        "withdraw_value": participant.withdraw_value.to_string(),
+        "reinvest": participant.reinvest,
+        "reward": participant.reward.to_string(),
+        "stakes": participant
+            .stakes
+            .iter()
+            .map(|(round, stake)| (round.to_string(), serde_json::Value::from(stake.to_string())))
+            .collect::<serde_json::Map<_, _>>(),
+        "vestings": participant
+            .vestings
+            .iter()
+            .map(|(round, stake)| (round.to_string(), describe_complex(stake)))
+            .collect::<serde_json::Map<_, _>>(),
+        "locks": participant
+            .locks
+            .iter()
+            .map(|(round, stake)| (round.to_string(), describe_complex(stake)))
+            .collect::<serde_json::Map<_, _>>(),
+        "vesting_donor": participant.vesting_donor.to_string(),
+        "lock_donor": participant.lock_donor.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_participants_only_for_stever() {
+        assert!(ensure_allowed_participants(DePoolType::StEverV1).is_ok());
+        assert!(ensure_allowed_participants(DePoolType::StEverV2).is_ok());
+        assert!(ensure_allowed_participants(DePoolType::DefaultV3).is_err());
+    }
+
+    #[test]
+    fn allowed_subcommands() {
+        const ADDRESS: &str = "0:0000000000000000000000000000000000000000000000000000000000000001";
+
+        let cmd = CmdAllowed::from_args(&["allowed"], &["add", ADDRESS, "-f"]).unwrap();
+        assert!(matches!(cmd.subcommand, AllowedSubCmd::Add(add) if add.force));
+
+        let cmd = CmdAllowed::from_args(&["allowed"], &["list", "--format", "table"]).unwrap();
+        assert!(matches!(cmd.subcommand, AllowedSubCmd::List(_)));
+
+        // Participants can't be revoked by the contract
+        assert!(CmdAllowed::from_args(&["allowed"], &["remove", ADDRESS]).is_err());
+    }
+}
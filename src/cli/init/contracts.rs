@@ -16,6 +16,7 @@ use crate::config::*;
 use crate::contracts::*;
 use crate::crypto;
 use crate::util::*;
+use crate::validator::{compute_target_balance_for_depool, compute_target_balance_for_single};
 
 const DEFAULT_STAKE_FACTOR: f64 = 3.0;
 
@@ -216,7 +217,11 @@ fn prepare_single_validator(
     app_config.validator = Some(AppConfigValidator::Single(AppConfigValidatorSingle {
         address: wallet_address.clone(),
         stake_per_round,
+        stake: None,
         stake_factor: Some(stake_factor),
+        stake_top_up: true,
+        wallet_type: WalletType::EverWallet,
+        wallet_deploy: None,
     }));
     dirs.store_app_config(app_config)?;
 
@@ -249,10 +254,6 @@ fn prepare_single_validator(
     })
 }
 
-fn compute_target_balance_for_single(stake_per_round: u64) -> u128 {
-    Wallet::INITIAL_BALANCE + stake_per_round as u128 * 2
-}
-
 #[derive(Debug, Serialize)]
 pub struct OutputDePool {
     #[serde(with = "serde_address")]
@@ -343,15 +344,6 @@ fn prepare_depool_validator(
     })
 }
 
-fn compute_target_balance_for_depool(validator_assurance: Option<u64>) -> u128 {
-    match validator_assurance {
-        Some(assurance) => {
-            Wallet::INITIAL_BALANCE + DePool::INITIAL_BALANCE + assurance as u128 * 2
-        }
-        None => Wallet::INITIAL_BALANCE,
-    }
-}
-
 fn prepare_new_depool(
     theme: &dyn Theme,
     dirs: &ProjectDirs,
@@ -489,6 +481,8 @@ fn prepare_new_depool(
             validator_assurance,
             participant_reward_fraction,
         }),
+        wallet_type: WalletType::EverWallet,
+        wallet_deploy: None,
     };
 
     // Configure stEVER strategies stuff
@@ -567,6 +561,8 @@ fn prepare_existing_depool(
         stake_factor: Some(stake_factor),
         cluster: None,
         deploy: None,
+        wallet_type: WalletType::EverWallet,
+        wallet_deploy: None,
     };
 
     // Configure stEVER strategies stuff
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+
+use super::CliContext;
+use crate::config::{keys_password, StoredKeys};
+use crate::util::*;
+
+#[derive(FromArgs)]
+/// Keys management stuff
+#[argh(subcommand, name = "keys")]
+pub struct Cmd {
+    #[argh(subcommand)]
+    subcommand: SubCmd,
+}
+
+impl Cmd {
+    pub fn run(self, ctx: CliContext) -> Result<()> {
+        match self.subcommand {
+            SubCmd::Encrypt(cmd) => cmd.run(ctx),
+            SubCmd::Decrypt(cmd) => cmd.run(ctx),
+            SubCmd::ChangePassword(cmd) => cmd.run(ctx),
+        }
+    }
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum SubCmd {
+    Encrypt(CmdEncrypt),
+    Decrypt(CmdDecrypt),
+    ChangePassword(CmdChangePassword),
+}
+
+#[derive(FromArgs)]
+/// Encrypts keys with a password
+#[argh(subcommand, name = "encrypt")]
+struct CmdEncrypt {
+    /// use DePool keys instead of the validator wallet keys
+    #[argh(switch)]
+    depool: bool,
+
+    /// explicit path to the keys file
+    #[argh(option)]
+    path: Option<PathBuf>,
+}
+
+impl CmdEncrypt {
+    fn run(self, ctx: CliContext) -> Result<()> {
+        let path = keys_path(&ctx, self.depool, self.path);
+        anyhow::ensure!(
+            !StoredKeys::is_encrypted(&path)?,
+            "keys are already encrypted"
+        );
+
+        let keys = StoredKeys::load(&path).context("failed to load keys")?;
+
+        // NOTE: non-interactive mode uses the same sources as for decryption
+        let password = if is_terminal() {
+            new_password()?
+        } else {
+            let password = keys_password()?;
+            anyhow::ensure!(!password.is_empty(), "password must not be empty");
+            password
+        };
+
+        keys.store_encrypted(&path, &password)?;
+        eprintln!("Keys encrypted: {}", path.display());
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Stores keys as a plain JSON
+#[argh(subcommand, name = "decrypt")]
+struct CmdDecrypt {
+    /// use DePool keys instead of the validator wallet keys
+    #[argh(switch)]
+    depool: bool,
+
+    /// explicit path to the keys file
+    #[argh(option)]
+    path: Option<PathBuf>,
+}
+
+impl CmdDecrypt {
+    fn run(self, ctx: CliContext) -> Result<()> {
+        let path = keys_path(&ctx, self.depool, self.path);
+        anyhow::ensure!(StoredKeys::is_encrypted(&path)?, "keys are not encrypted");
+
+        let keys = StoredKeys::load(&path)?;
+        keys.store(&path)?;
+        eprintln!("Keys decrypted: {}", path.display());
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Changes the password of the encrypted keys
+#[argh(subcommand, name = "change-password")]
+struct CmdChangePassword {
+    /// use DePool keys instead of the validator wallet keys
+    #[argh(switch)]
+    depool: bool,
+
+    /// explicit path to the keys file
+    #[argh(option)]
+    path: Option<PathBuf>,
+}
+
+impl CmdChangePassword {
+    fn run(self, ctx: CliContext) -> Result<()> {
+        let path = keys_path(&ctx, self.depool, self.path);
+        anyhow::ensure!(StoredKeys::is_encrypted(&path)?, "keys are not encrypted");
+        anyhow::ensure!(
+            is_terminal(),
+            "new password can only be entered interactively"
+        );
+
+        let keys = StoredKeys::load(&path)?;
+        let password = new_password()?;
+
+        keys.store_encrypted(&path, &password)?;
+        eprintln!("Keys password changed: {}", path.display());
+        Ok(())
+    }
+}
+
+fn keys_path(ctx: &CliContext, depool: bool, path: Option<PathBuf>) -> PathBuf {
+    match path {
+        Some(path) => path,
+        None if depool => ctx.dirs.depool_keys.clone(),
+        None => ctx.dirs.validator_keys.clone(),
+    }
+}
+
+fn new_password() -> Result<String> {
+    let password = dialoguer::Password::with_theme(&dialoguer::theme::ColorfulTheme::default())
+        .with_prompt("New keys password")
+        .with_confirmation("Repeat password", "Passwords mismatch")
+        .interact()
+        .context("failed to read password")?;
+    anyhow::ensure!(!password.is_empty(), "password must not be empty");
+    Ok(password)
+}
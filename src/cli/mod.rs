@@ -8,11 +8,16 @@ use crate::dirs::*;
 use crate::util::*;
 
 pub mod contract;
+pub mod depool;
 pub mod exporter;
 pub mod init;
+pub mod keys;
 pub mod node;
+pub mod offline;
 pub mod seed;
+pub mod signer;
 pub mod validator;
+pub mod wallet;
 
 /// All-in-one node management tool
 #[derive(FromArgs)]
@@ -36,10 +41,15 @@ impl App {
         match self.command {
             Command::Init(cmd) => invoke_as_cli(cmd.run(ctx)).await,
             Command::Validator(cmd) => cmd.run(ctx).await,
+            Command::Wallet(cmd) => cmd.run(ctx).await,
             Command::Contract(cmd) => invoke_as_cli(cmd.run(ctx)).await,
             Command::Exporter(cmd) => cmd.run(ctx).await,
             Command::Node(cmd) => cmd.run(ctx).await,
             Command::Seed(cmd) => cmd.run(),
+            Command::Keys(cmd) => cmd.run(ctx),
+            Command::Signer(cmd) => cmd.run(ctx).await,
+            Command::SignMessage(cmd) => cmd.run(ctx),
+            Command::BroadcastMessage(cmd) => invoke_as_cli(cmd.run(ctx)).await,
         }
     }
 }
@@ -49,10 +59,15 @@ impl App {
 enum Command {
     Init(init::Cmd),
     Validator(validator::Cmd),
+    Wallet(wallet::Cmd),
     Contract(contract::Cmd),
     Exporter(exporter::Cmd),
     Node(node::Cmd),
     Seed(seed::Cmd),
+    Keys(keys::Cmd),
+    Signer(signer::Cmd),
+    SignMessage(offline::CmdSignMessage),
+    BroadcastMessage(offline::CmdBroadcastMessage),
 }
 
 pub struct CliContext {
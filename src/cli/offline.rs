@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use dialoguer::console::style;
+
+use super::CliContext;
+use crate::config::StoredKeys;
+use crate::contracts::wallet::{OfflineMessage, Wallet};
+use crate::contracts::{decode_internal_payload, InternalMessage};
+use crate::network::{NodeTcpRpc, NodeUdpRpc, Subscription};
+use crate::util::*;
+
+#[derive(FromArgs)]
+/// Signs the exported wallet message on the offline host
+#[argh(subcommand, name = "sign-message")]
+pub struct CmdSignMessage {
+    /// path to the exported message
+    #[argh(positional)]
+    path: PathBuf,
+
+    /// path to the keys instead of the validator keys
+    #[argh(option)]
+    keys: Option<PathBuf>,
+
+    /// path to the signed message instead of overwriting the exported one
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+
+    /// never prompt
+    #[argh(switch, short = 'f')]
+    force: bool,
+}
+
+impl CmdSignMessage {
+    pub fn run(self, ctx: CliContext) -> Result<()> {
+        let mut message = OfflineMessage::load(&self.path)?;
+        anyhow::ensure!(message.signature.is_none(), "message is already signed");
+        anyhow::ensure!(
+            message.expire_at > broxus_util::now(),
+            "message expired at {}",
+            message.expire_at
+        );
+
+        let keys = self.keys.as_ref().unwrap_or(&ctx.dirs.validator_keys);
+        let keypair = StoredKeys::load(keys)
+            .context("failed to load keys")?
+            .as_keypair();
+
+        // NOTE: purpose is provided by the online host, so the transfer is decoded here
+        let transfer = message
+            .decode_transfer()
+            .context("failed to decode wallet message")?;
+
+        if is_terminal() {
+            eprintln!(
+                "{}\n{}\n{}\n{}\n{}\n{}\n",
+                style("Wallet address:").green().bold(),
+                style(&message.dst).bold(),
+                style("Purpose:").green().bold(),
+                style(&message.purpose).bold(),
+                style("Expire at:").green().bold(),
+                style(message.expire_at).bold(),
+            );
+
+            match &transfer {
+                Some(transfer) => {
+                    let method = match decode_internal_payload(&transfer.payload) {
+                        Ok(Some(payload)) => payload.method.to_owned(),
+                        _ => "-".to_owned(),
+                    };
+                    let amount = if transfer.all_balance {
+                        "all balance".to_owned()
+                    } else {
+                        Tokens(transfer.value).to_string()
+                    };
+
+                    eprintln!(
+                        "{}\n{}\n{}\n{}\n{}\n{}\n",
+                        style("Target address:").green().bold(),
+                        style(&transfer.dst).bold(),
+                        style("Amount to send:").green().bold(),
+                        style(amount).bold(),
+                        style("Method:").green().bold(),
+                        style(method).bold(),
+                    );
+                }
+                None => eprintln!(
+                    "{}\n",
+                    style("Message is not a wallet transfer").yellow().bold()
+                ),
+            }
+
+            if !self.force
+                && !confirm(
+                    &dialoguer::theme::ColorfulTheme::default(),
+                    false,
+                    "Do you really want to sign this message?",
+                )?
+            {
+                return Ok(());
+            }
+        }
+
+        message.sign(&keypair)?;
+
+        let output = self.output.as_ref().unwrap_or(&self.path);
+        message.save(output)?;
+
+        print_output(serde_json::json!({
+            "path": output,
+        }));
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Broadcasts the signed wallet message and waits for the transaction
+#[argh(subcommand, name = "broadcast-message")]
+pub struct CmdBroadcastMessage {
+    /// path to the signed message
+    #[argh(positional)]
+    path: PathBuf,
+}
+
+impl CmdBroadcastMessage {
+    pub async fn run(self, ctx: CliContext) -> Result<()> {
+        let message = OfflineMessage::load(&self.path)?;
+        anyhow::ensure!(message.signature.is_some(), "message is not signed");
+
+        let config = ctx.load_config()?;
+
+        // Prepare RPC clients
+        let node_tcp_rpc = NodeTcpRpc::new(config.control()?)
+            .await
+            .context("failed to build node TCP client")?;
+        let node_udp_rpc = NodeUdpRpc::new(config.adnl()?)
+            .await
+            .context("failed to build node UDP client")?;
+
+        let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
+        subscription.ensure_ready().await?;
+
+        // Send external message and wait until it is delivered
+        let TransactionWithHash {
+            hash: tx_hash,
+            data: tx,
+        } = message.broadcast(&subscription).await?;
+
+        // Parse transaction
+        let msg_hash = tx
+            .in_msg
+            .context("external inbound message not found")?
+            .hash();
+
+        // Done
+        print_output(serde_json::json!({
+            "tx_hash": tx_hash.to_hex_string(),
+            "msg_hash": msg_hash.to_hex_string(),
+        }));
+        Ok(())
+    }
+}
+
+/// Saves an unsigned wallet message to the file instead of sending it
+pub async fn export_message(
+    wallet: &Wallet,
+    internal_message: InternalMessage,
+    timeout: u32,
+    path: &Path,
+) -> Result<serde_json::Value> {
+    let message = wallet.export_transfer(internal_message, timeout).await?;
+    message.save(path)?;
+
+    Ok(serde_json::json!({
+        "path": path,
+        "purpose": message.purpose,
+        "expire_at": message.expire_at,
+    }))
+}
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+
+use super::CliContext;
+use crate::config::StoredKeys;
+use crate::contracts::wallet;
+
+#[derive(FromArgs)]
+/// Remote signer stuff
+#[argh(subcommand, name = "signer")]
+pub struct Cmd {
+    #[argh(subcommand)]
+    subcommand: SubCmd,
+}
+
+impl Cmd {
+    pub async fn run(self, ctx: CliContext) -> Result<()> {
+        match self.subcommand {
+            SubCmd::Serve(cmd) => cmd.run(ctx).await,
+        }
+    }
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum SubCmd {
+    Serve(CmdServe),
+}
+
+#[derive(FromArgs)]
+/// Signs wallet messages received over a Unix socket with the keys file
+#[argh(subcommand, name = "serve")]
+struct CmdServe {
+    /// path to the signer socket instead of `signer.sock` in the root directory
+    #[argh(option)]
+    socket: Option<PathBuf>,
+
+    /// path to the keys instead of the validator keys
+    #[argh(option)]
+    keys: Option<PathBuf>,
+}
+
+impl CmdServe {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let keys = self.keys.as_ref().unwrap_or(&ctx.dirs.validator_keys);
+        let keypair = StoredKeys::load(keys)
+            .context("failed to load keys")?
+            .as_keypair();
+
+        let socket = self.socket.as_ref().unwrap_or(&ctx.dirs.signer_socket);
+        wallet::serve_remote_signer(socket, keypair).await
+    }
+}
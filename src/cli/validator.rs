@@ -1,16 +1,20 @@
-use std::time::Duration;
+use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 use argh::FromArgs;
 use dialoguer::console::style;
 use tokio_util::sync::CancellationToken;
 
+use super::offline::export_message;
 use super::CliContext;
-use crate::config::{AppConfigValidator, StoredKeys};
-use crate::contracts::{depool, wallet, InternalMessage, ONE_EVER};
+use crate::config::{AppConfigValidator, StakePolicy};
+use crate::contracts::{depool, wallet, Elector, InternalMessage, WalletSigner, ONE_EVER};
 use crate::network::{ConfigWithId, NodeTcpRpc, NodeUdpRpc, Subscription};
 use crate::util::*;
-use crate::validator::{ValidationManager, ValidationParams};
+use crate::validator::control::{self, ControlCommand};
+use crate::validator::journal::ElectionJournal;
+use crate::validator::preflight::{self, StakeLimits};
+use crate::validator::{ValidationManager, ValidationParams, DEFAULT_STAKE_FACTOR};
 
 #[derive(FromArgs)]
 /// Validator management stuff
@@ -28,6 +32,10 @@ impl Cmd {
             SubCmd::Withdraw(cmd) => invoke_as_cli(cmd.run(ctx)).await,
             SubCmd::Unstake(cmd) => invoke_as_cli(cmd.run(ctx)).await,
             SubCmd::Run(cmd) => cmd.run(ctx).await,
+            SubCmd::History(cmd) => cmd.run(ctx),
+            SubCmd::CheckStake(cmd) => cmd.run(ctx).await,
+            SubCmd::Ctl(cmd) => cmd.run(ctx).await,
+            SubCmd::DePool(cmd) => cmd.run(ctx).await,
         }
     }
 }
@@ -40,6 +48,10 @@ enum SubCmd {
     Withdraw(CmdWithdraw),
     Unstake(CmdUnstake),
     Run(CmdRun),
+    History(CmdHistory),
+    CheckStake(CmdCheckStake),
+    Ctl(CmdCtl),
+    DePool(super::depool::Cmd),
 }
 
 #[derive(FromArgs)]
@@ -177,6 +189,9 @@ impl CmdTick {
             Tokens(wallet_balance)
         );
 
+        // Prevent concurrent usage of the wallet
+        let _wallet_lock = ctx.dirs.try_lock_wallet("validator tick")?;
+
         // Send external message and wait until it is delivered
         let TransactionWithHash {
             hash: tx_hash,
@@ -217,6 +232,14 @@ struct CmdUnstake {
     /// unstake from a pooling round
     #[argh(switch)]
     from_pooling: bool,
+
+    /// save an unsigned message to the file instead of sending it
+    #[argh(option)]
+    offline: Option<PathBuf>,
+
+    /// expiration timeout of the unsigned message in seconds
+    #[argh(option, default = "3600")]
+    offline_timeout: u32,
 }
 
 impl CmdUnstake {
@@ -280,13 +303,21 @@ impl CmdUnstake {
             }
         }
 
+        let message = depool.withdraw_part(amount as u64, self.from_pooling)?;
+        if let Some(path) = &self.offline {
+            let output = export_message(&wallet, message, self.offline_timeout, path).await?;
+            print_output(output);
+            return Ok(());
+        }
+
+        // Prevent concurrent usage of the wallet
+        let _wallet_lock = ctx.dirs.try_lock_wallet("validator unstake")?;
+
         // Send external message and wait until it is delivered
         let TransactionWithHash {
             hash: tx_hash,
             data: tx,
-        } = wallet
-            .transfer(depool.withdraw_part(amount as u64, self.from_pooling)?)
-            .await?;
+        } = wallet.transfer(message).await?;
 
         // Parse transaction
         let msg_hash = tx
@@ -322,6 +353,14 @@ struct CmdWithdraw {
     /// interpret amount as amount in nano tokens
     #[argh(switch)]
     nano: bool,
+
+    /// save an unsigned message to the file instead of sending it
+    #[argh(option)]
+    offline: Option<PathBuf>,
+
+    /// expiration timeout of the unsigned message in seconds
+    #[argh(option, default = "3600")]
+    offline_timeout: u32,
 }
 
 impl CmdWithdraw {
@@ -353,21 +392,15 @@ impl CmdWithdraw {
         }
 
         // Prepare wallet
-        let keypair = StoredKeys::load(&ctx.dirs.validator_keys)
-            .context("failed to load validator wallet keys")?
-            .as_keypair();
+        let signer = WalletSigner::load(config.signer.as_ref(), &ctx.dirs)?;
 
-        let wallet_address = match validator {
-            AppConfigValidator::Single(single) => single.address,
-            AppConfigValidator::DePool(depool) => depool.owner,
+        let (wallet_address, wallet_type) = match validator {
+            AppConfigValidator::Single(single) => (single.address, single.wallet_type),
+            AppConfigValidator::DePool(depool) => (depool.owner, depool.wallet_type),
         };
 
         let wallet =
-            wallet::Wallet::new(wallet_address.workchain_id() as i8, keypair, subscription);
-        anyhow::ensure!(
-            wallet.address() == &wallet_address,
-            "validator wallet address mismatch"
-        );
+            wallet::Wallet::with_signer(wallet_type, wallet_address, signer, subscription)?;
 
         // Check wallet balance
         let wallet_balance = wallet.get_balance().await?.unwrap_or_default();
@@ -401,18 +434,21 @@ impl CmdWithdraw {
             }
         }
 
+        let message = InternalMessage::empty(dest, amount, false);
+        if let Some(path) = &self.offline {
+            let output = export_message(&wallet, message, self.offline_timeout, path).await?;
+            print_output(output);
+            return Ok(());
+        }
+
+        // Prevent concurrent usage of the wallet
+        let _wallet_lock = ctx.dirs.try_lock_wallet("validator withdraw")?;
+
         // Send external message and wait until it is delivered
         let TransactionWithHash {
             hash: tx_hash,
             data: tx,
-        } = wallet
-            .transfer(InternalMessage {
-                dst: dest,
-                amount,
-                payload: Default::default(),
-                bounce: false,
-            })
-            .await?;
+        } = wallet.transfer(message).await?;
 
         // Parse transaction
         let msg_hash = tx
@@ -472,6 +508,10 @@ struct CmdRun {
     /// force elect
     #[argh(switch)]
     force: bool,
+
+    /// print actions for the current elections without sending any messages
+    #[argh(switch)]
+    dry_run: bool,
 }
 
 impl CmdRun {
@@ -479,6 +519,8 @@ impl CmdRun {
         // Start listening termination signals
         let signal_rx = broxus_util::any_signal(broxus_util::TERMINATION_SIGNALS);
 
+        let control_socket = ctx.dirs.control_socket.clone();
+
         // Create validation manager
         let mut manager = ValidationManager::new(
             ctx.dirs,
@@ -492,6 +534,12 @@ impl CmdRun {
             },
         );
 
+        if self.dry_run {
+            let plan = manager.dry_run(self.force).await?;
+            print_output(plan.to_json());
+            return Ok(());
+        }
+
         if self.force {
             return manager.force_elect().await;
         }
@@ -513,6 +561,20 @@ impl CmdRun {
             }
         });
 
+        // Spawn control socket listener
+        let control = manager.control().clone();
+        tokio::spawn({
+            let control = control.clone();
+            async move {
+                if let Err(e) = control::serve(control_socket, control).await {
+                    tracing::error!("control socket failed: {e:?}");
+                }
+            }
+        });
+
+        // Spawn DePool ticktocks between the elections (if enabled in the config)
+        tokio::spawn(manager.ticker().run());
+
         // Prepare validation future
         let validation_fut = async {
             self.min_retry_interval = std::cmp::max(self.min_retry_interval, 1);
@@ -524,10 +586,11 @@ impl CmdRun {
             loop {
                 if let Err(e) = manager.try_validate().await {
                     tracing::error!("error occurred: {e:?}");
+                    manager.report_error(&e).await;
                 }
 
                 tracing::info!("retrying in {interval} seconds");
-                tokio::time::sleep(Duration::from_secs(interval)).await;
+                control.sleep(interval).await;
 
                 interval = std::cmp::min(
                     self.max_retry_interval,
@@ -546,6 +609,223 @@ impl CmdRun {
     }
 }
 
+#[derive(FromArgs)]
+/// Sends a command to the running validator manager
+#[argh(subcommand, name = "ctl")]
+struct CmdCtl {
+    /// command: `status`, `pause`, `resume`, `skip-next`, `force-elect`, `reload`
+    /// or `approve-spending:<hash>` (the hash of the pending transfer from `status`)
+    #[argh(positional, default = "ControlCommand::Status")]
+    command: ControlCommand,
+}
+
+impl CmdCtl {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let status = control::send_command(&ctx.dirs.control_socket, self.command).await?;
+        print_output(serde_json::to_value(status)?);
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Prints the elections participation journal
+#[argh(subcommand, name = "history")]
+struct CmdHistory {
+    /// output format: `json`, `table` or `csv`. `json` default
+    #[argh(option, default = "OutputFormat::Json")]
+    format: OutputFormat,
+
+    /// show only entries for the specified election id
+    #[argh(option)]
+    election_id: Option<u32>,
+}
+
+impl CmdHistory {
+    fn run(self, ctx: CliContext) -> Result<()> {
+        let mut entries = ElectionJournal::new(&ctx.dirs.election_journal).load()?;
+        if let Some(election_id) = self.election_id {
+            entries.retain(|entry| entry.election_id == election_id);
+        }
+
+        match self.format {
+            OutputFormat::Json => print_output(serde_json::to_value(entries)?),
+            OutputFormat::Table | OutputFormat::Csv => {
+                let mut rows = Vec::with_capacity(entries.len());
+                for entry in entries {
+                    let serde_json::Value::Object(mut fields) = serde_json::to_value(&entry)?
+                    else {
+                        continue;
+                    };
+
+                    let event = fields
+                        .remove("event")
+                        .and_then(|event| event.as_str().map(str::to_owned))
+                        .unwrap_or_default();
+                    fields.remove("timestamp");
+                    fields.remove("election_id");
+
+                    let details = fields
+                        .into_iter()
+                        .map(|(key, value)| match value {
+                            serde_json::Value::String(value) => format!("{key}={value}"),
+                            value => format!("{key}={value}"),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" ");
+
+                    rows.push(vec![
+                        entry.timestamp.to_string(),
+                        entry.election_id.to_string(),
+                        event,
+                        details,
+                    ]);
+                }
+
+                self.format
+                    .print_rows(&["TIME", "ELECTION", "EVENT", "DETAILS"], &rows);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Checks the stake against the elector limits
+#[argh(subcommand, name = "check-stake")]
+struct CmdCheckStake {
+    /// stake amount in tokens. Computed from the config by default
+    #[argh(option)]
+    stake: Option<u128>,
+
+    /// interpret stake as amount in nano tokens
+    #[argh(switch)]
+    nano: bool,
+
+    /// stake factor. Taken from the config by default
+    #[argh(option)]
+    stake_factor: Option<u32>,
+}
+
+impl CmdCheckStake {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        // Load config
+        let mut config = ctx.load_config()?;
+        let validator = config
+            .validator
+            .take()
+            .context("validator entry not found in the app config")?;
+
+        // Prepare RPC clients
+        let node_tcp_rpc = NodeTcpRpc::new(config.control()?)
+            .await
+            .context("failed to build node TCP client")?;
+        let node_udp_rpc = NodeUdpRpc::new(config.adnl()?)
+            .await
+            .context("failed to build node UDP client")?;
+
+        let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
+        subscription.ensure_ready().await?;
+
+        // Get current network config params
+        let ConfigWithId {
+            config: blockchain_config,
+            ..
+        } = subscription.tcp_rpc().get_config_all().await?;
+        let limits = StakeLimits::new(&blockchain_config)?;
+
+        // Get elector state
+        let elector_address = blockchain_config
+            .elector_address()
+            .context("invalid elector address")?;
+        let elector = Elector::new(elector_address, subscription.clone());
+        let elector_data = elector
+            .get_data()
+            .await
+            .context("failed to get elector data")?;
+
+        // Parse arguments
+        let requested_stake = self.stake.map(|stake| {
+            if self.nano {
+                stake
+            } else {
+                stake.saturating_mul(ONE_EVER)
+            }
+        });
+
+        // Find participant and its stake
+        let (participant, stake, stake_factor) = match validator {
+            AppConfigValidator::Single(single) => {
+                let stake = match (requested_stake, single.stake_policy()) {
+                    (Some(stake), _) => stake,
+                    (None, StakePolicy::Fixed { amount }) => amount as u128,
+                    (None, policy) => {
+                        let balance = subscription
+                            .get_account_state(&single.address)
+                            .await?
+                            .map(|account| account.storage.balance.grams.as_u128())
+                            .unwrap_or_default();
+                        let frozen = elector_data.total_frozen_stake(&single.address);
+                        preflight::compute_single_stake(&policy, balance, frozen)
+                    }
+                };
+                (single.address, stake, single.stake_factor)
+            }
+            AppConfigValidator::DePool(config) => {
+                let depool =
+                    depool::DePool::new(config.depool_type, config.depool, subscription.clone());
+                let depool_state = depool.get_state().await?;
+                let depool_info = depool.get_info(&depool_state)?;
+                anyhow::ensure!(depool_info.proxies.len() == 2, "invalid DePool proxies");
+
+                let rounds = depool
+                    .get_rounds(&depool_state)?
+                    .into_values()
+                    .collect::<Vec<_>>();
+                anyhow::ensure!(rounds.len() == 4, "DePool rounds number mismatch");
+                let target_round = &rounds[1];
+
+                let stake = match requested_stake {
+                    Some(stake) => stake,
+                    None => {
+                        anyhow::ensure!(
+                            elector_data.election_id() == Some(target_round.supposed_elected_at),
+                            "DePool target round is not ready for the current elections, \
+                            specify the stake explicitly"
+                        );
+                        target_round.stake as u128
+                    }
+                };
+
+                let proxy = depool_info.proxies[target_round.id as usize % 2].clone();
+                (proxy, stake, config.stake_factor)
+            }
+        };
+        let stake_factor = self
+            .stake_factor
+            .or(stake_factor)
+            .unwrap_or(DEFAULT_STAKE_FACTOR);
+
+        // Check stake
+        let election = elector_data.current_election_stakes(&participant);
+        let check = preflight::check_stake(&limits, election.as_ref(), stake, stake_factor);
+
+        print_output(serde_json::json!({
+            "election_id": elector_data.election_id(),
+            "participant": participant.to_string(),
+            "limits": limits,
+            "election": election.map(|election| serde_json::json!({
+                "min_stake": election.min_stake.to_string(),
+                "total_stake": election.total_stake.to_string(),
+                "participants": election.stakes.len(),
+            })),
+            "stake": stake.to_string(),
+            "stake_factor": stake_factor,
+            "check": check,
+        }));
+        Ok(())
+    }
+}
+
 struct DePoolCmdContext {
     currency: &'static str,
     wallet: wallet::Wallet,
@@ -578,19 +858,14 @@ impl DePoolCmdContext {
         subscription.ensure_ready().await?;
 
         // Prepare wallet
-        let wallet_keys = StoredKeys::load(&ctx.dirs.validator_keys)
-            .context("failed to load validator wallet keys")?
-            .as_keypair();
+        let signer = WalletSigner::load(config.signer.as_ref(), &ctx.dirs)?;
 
-        let wallet = wallet::Wallet::new(
-            validator.owner.workchain_id() as i8,
-            wallet_keys,
+        let wallet = wallet::Wallet::with_signer(
+            validator.wallet_type,
+            validator.owner.clone(),
+            signer,
             subscription.clone(),
-        );
-        anyhow::ensure!(
-            wallet.address() == &validator.owner,
-            "validator wallet address mismatch"
-        );
+        )?;
 
         // Prepare depool
         let depool = depool::DePool::new(validator.depool_type, validator.depool, subscription);
@@ -0,0 +1,290 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use dialoguer::console::style;
+
+use super::CliContext;
+use crate::config::{AppConfigValidator, StoredKeys, WalletType};
+use crate::contracts::{decode_internal_payload, wallet};
+use crate::network::{NodeTcpRpc, NodeUdpRpc, Subscription};
+use crate::util::*;
+
+#[derive(FromArgs)]
+/// Multisig wallet management stuff
+#[argh(subcommand, name = "wallet")]
+pub struct Cmd {
+    #[argh(subcommand)]
+    subcommand: SubCmd,
+}
+
+impl Cmd {
+    pub async fn run(self, ctx: CliContext) -> Result<()> {
+        match self.subcommand {
+            SubCmd::Pending(cmd) => cmd.run(ctx).await,
+            SubCmd::Confirm(cmd) => invoke_as_cli(cmd.run(ctx)).await,
+        }
+    }
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum SubCmd {
+    Pending(CmdPending),
+    Confirm(CmdConfirm),
+}
+
+#[derive(FromArgs)]
+/// Lists multisig transactions which are waiting for confirmations
+#[argh(subcommand, name = "pending")]
+struct CmdPending {
+    /// multisig address instead of the configured validator wallet
+    #[argh(option)]
+    address: Option<String>,
+
+    /// path to the custodian keys instead of the validator keys
+    #[argh(option)]
+    keys: Option<PathBuf>,
+
+    /// output format: `json`, `table` or `csv`. `json` default
+    #[argh(option, default = "OutputFormat::Json")]
+    format: OutputFormat,
+}
+
+impl CmdPending {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let ctx = MultisigCmdContext::new(&ctx, self.address, self.keys).await?;
+
+        let custodian_index = ctx.custodian_index().await?;
+        let transactions = ctx.wallet.get_pending_transactions().await?;
+
+        match self.format {
+            OutputFormat::Json => {
+                let transactions = transactions
+                    .iter()
+                    .map(|tx| describe_transaction(tx, custodian_index))
+                    .collect::<Vec<_>>();
+                print_output(serde_json::json!({
+                    "wallet": ctx.wallet.address().to_string(),
+                    "transactions": transactions,
+                }));
+            }
+            OutputFormat::Table | OutputFormat::Csv => {
+                let rows = transactions
+                    .iter()
+                    .map(|tx| {
+                        let method = match decode_internal_payload(&tx.payload) {
+                            Ok(Some(payload)) => payload.method.to_owned(),
+                            _ => String::new(),
+                        };
+                        let confirmed = match custodian_index {
+                            Some(index) if tx.is_confirmed_by(index) => "yes",
+                            Some(_) => "no",
+                            None => "",
+                        };
+                        vec![
+                            tx.id.to_string(),
+                            tx.dest.to_string(),
+                            format!("{} {}", Tokens(tx.value), ctx.currency),
+                            method,
+                            format!("{}/{}", tx.signs_received, tx.signs_required),
+                            confirmed.to_owned(),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+
+                self.format.print_rows(
+                    &["ID", "DEST", "AMOUNT", "METHOD", "SIGNS", "CONFIRMED"],
+                    &rows,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(FromArgs)]
+/// Confirms the pending multisig transaction
+#[argh(subcommand, name = "confirm")]
+struct CmdConfirm {
+    /// transaction id
+    #[argh(positional)]
+    id: u64,
+
+    /// multisig address instead of the configured validator wallet
+    #[argh(option)]
+    address: Option<String>,
+
+    /// path to the custodian keys instead of the validator keys
+    #[argh(option)]
+    keys: Option<PathBuf>,
+
+    /// never prompt
+    #[argh(switch, short = 'f')]
+    force: bool,
+}
+
+impl CmdConfirm {
+    async fn run(self, ctx: CliContext) -> Result<()> {
+        let dirs = &ctx.dirs;
+        let ctx = MultisigCmdContext::new(&ctx, self.address, self.keys).await?;
+
+        let custodian_index = ctx
+            .custodian_index()
+            .await?
+            .context("the key is not a custodian of this wallet")?;
+
+        let transaction = ctx
+            .wallet
+            .get_pending_transactions()
+            .await?
+            .into_iter()
+            .find(|tx| tx.id == self.id)
+            .with_context(|| format!("pending transaction {} not found", self.id))?;
+        anyhow::ensure!(
+            !transaction.is_confirmed_by(custodian_index),
+            "transaction was already confirmed by this custodian"
+        );
+
+        if is_terminal() {
+            let method = match decode_internal_payload(&transaction.payload) {
+                Ok(Some(payload)) => payload.method.to_owned(),
+                _ => "-".to_owned(),
+            };
+
+            eprintln!(
+                "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n",
+                style("Target address:").green().bold(),
+                style(&transaction.dest).bold(),
+                style("Amount to send:").green().bold(),
+                style(format!("{} {}", Tokens(transaction.value), ctx.currency)).bold(),
+                style("Method:").green().bold(),
+                style(method).bold(),
+                style("Confirmations:").green().bold(),
+                style(format!(
+                    "{}/{}",
+                    transaction.signs_received, transaction.signs_required
+                ))
+                .bold(),
+            );
+
+            if !self.force
+                && !confirm(
+                    &dialoguer::theme::ColorfulTheme::default(),
+                    false,
+                    "Do you really want to confirm this transaction?",
+                )?
+            {
+                return Ok(());
+            }
+        }
+
+        let _wallet_lock = dirs.try_lock_wallet("wallet confirm")?;
+        let TransactionWithHash {
+            hash: tx_hash,
+            data: tx,
+        } = ctx.wallet.confirm_transaction(self.id).await?;
+
+        // Parse transaction
+        let msg_hash = tx
+            .in_msg
+            .context("external inbound message not found")?
+            .hash();
+
+        // Done
+        print_output(serde_json::json!({
+            "tx_hash": tx_hash.to_hex_string(),
+            "msg_hash": msg_hash.to_hex_string(),
+        }));
+        Ok(())
+    }
+}
+
+struct MultisigCmdContext {
+    currency: &'static str,
+    wallet: wallet::Wallet,
+}
+
+impl MultisigCmdContext {
+    async fn new(ctx: &CliContext, address: Option<String>, keys: Option<PathBuf>) -> Result<Self> {
+        let config = ctx.load_config()?;
+
+        let (wallet_address, wallet_type) = match (address, &config.validator) {
+            // NOTE: both multisig types share the same ABI for these methods
+            (Some(address), _) => (parse_address(&address)?, WalletType::SafeMultisig),
+            (None, Some(AppConfigValidator::Single(single))) => {
+                (single.address.clone(), single.wallet_type)
+            }
+            (None, Some(AppConfigValidator::DePool(depool))) => {
+                (depool.owner.clone(), depool.wallet_type)
+            }
+            (None, None) => anyhow::bail!("validator entry not found in the app config"),
+        };
+        anyhow::ensure!(
+            wallet_type.is_multisig(),
+            "validator wallet is not a multisig"
+        );
+
+        // Prepare RPC clients
+        let node_tcp_rpc = NodeTcpRpc::new(config.control()?)
+            .await
+            .context("failed to build node TCP client")?;
+        let node_udp_rpc = NodeUdpRpc::new(config.adnl()?)
+            .await
+            .context("failed to build node UDP client")?;
+
+        let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
+        subscription.ensure_ready().await?;
+
+        // Prepare wallet
+        let keys = keys.as_ref().unwrap_or(&ctx.dirs.validator_keys);
+        let keypair = StoredKeys::load(keys)
+            .context("failed to load custodian keys")?
+            .as_keypair();
+
+        let wallet = wallet::Wallet::with_type(wallet_type, wallet_address, keypair, subscription)?;
+
+        Ok(Self {
+            currency: config.currency(),
+            wallet,
+        })
+    }
+
+    /// Returns the index of the loaded key in the custodians list
+    async fn custodian_index(&self) -> Result<Option<u8>> {
+        let pubkey = self.wallet.public_key().as_bytes();
+        Ok(self
+            .wallet
+            .get_custodians()
+            .await?
+            .into_iter()
+            .find(|custodian| custodian.pubkey.as_slice() == pubkey)
+            .map(|custodian| custodian.index))
+    }
+}
+
+fn describe_transaction(
+    tx: &wallet::MultisigTransaction,
+    custodian_index: Option<u8>,
+) -> serde_json::Value {
+    let payload = match decode_internal_payload(&tx.payload) {
+        Ok(Some(payload)) => serde_json::json!({
+            "method": payload.method,
+            "input": payload.input,
+        }),
+        _ => serde_json::Value::Null,
+    };
+
+    serde_json::json!({
+        "id": tx.id.to_string(),
+        "creator": tx.creator.to_hex_string(),
+        "dest": tx.dest.to_string(),
+        "value": tx.value.to_string(),
+        "bounce": tx.bounce,
+        "send_flags": tx.send_flags,
+        "signs_required": tx.signs_required,
+        "signs_received": tx.signs_received,
+        "confirmed": custodian_index.map(|index| tx.is_confirmed_by(index)),
+        "payload": payload,
+    })
+}
@@ -1,5 +1,5 @@
 use std::net::SocketAddrV4;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use anyhow::{Context, Result};
@@ -23,6 +23,27 @@ pub struct AppConfig {
     pub adnl: Option<AppConfigAdnl>,
     /// Validation config
     pub validator: Option<AppConfigValidator>,
+    /// Planned maintenance windows
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub maintenance: Vec<MaintenanceWindow>,
+    /// Notification sinks
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notifications: Vec<AppConfigNotificationSink>,
+    /// Scripts to run during the validation
+    #[serde(skip_serializing_if = "AppConfigHooks::is_empty")]
+    pub hooks: AppConfigHooks,
+    /// Validator wallet signer, local keys are used by default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<AppConfigSigner>,
+    /// Limits for the validator wallet transfers made by the manager
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spending_policy: Option<AppConfigSpendingPolicy>,
+    /// Transfers of the validator wallet surplus to the cold wallet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sweep: Option<AppConfigSweep>,
+    /// DePool ticktocks sent by the manager outside of the elections
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depool_ticktock: Option<AppConfigDePoolTicktock>,
 }
 
 impl AppConfig {
@@ -71,6 +92,247 @@ impl AppConfig {
     pub fn adnl(&self) -> Result<&AppConfigAdnl> {
         self.adnl.as_ref().context("adnl config is empty")
     }
+
+    /// Returns the first maintenance window which overlaps the specified time range
+    pub fn find_maintenance(&self, since: u32, until: u32) -> Option<&MaintenanceWindow> {
+        self.maintenance
+            .iter()
+            .find(|window| window.overlaps(since, until))
+    }
+}
+
+/// Time range during which the node must not be in the validator set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceWindow {
+    /// Window start (unix timestamp)
+    pub since: u32,
+    /// Window end (unix timestamp)
+    pub until: u32,
+    /// Optional description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Executables which receive a JSON event on stdin
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfigHooks {
+    /// Before sending a stake (non-zero exit code aborts sending)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pre_stake: Vec<PathBuf>,
+    /// After the stake was sent
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub post_stake: Vec<PathBuf>,
+    /// After the DePool ticktock was sent
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub post_ticktock: Vec<PathBuf>,
+    /// After the elections outcome is known
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub on_outcome: Vec<PathBuf>,
+    /// After the validation error
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub on_error: Vec<PathBuf>,
+    /// Max execution time of each hook (in seconds)
+    pub timeout_sec: u32,
+}
+
+impl Default for AppConfigHooks {
+    fn default() -> Self {
+        Self {
+            pre_stake: Vec::new(),
+            post_stake: Vec::new(),
+            post_ticktock: Vec::new(),
+            on_outcome: Vec::new(),
+            on_error: Vec::new(),
+            timeout_sec: 60,
+        }
+    }
+}
+
+impl AppConfigHooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre_stake.is_empty()
+            && self.post_stake.is_empty()
+            && self.post_ticktock.is_empty()
+            && self.on_outcome.is_empty()
+            && self.on_error.is_empty()
+    }
+}
+
+/// How validator wallet messages are signed
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case", tag = "type")]
+pub enum AppConfigSigner {
+    /// Messages are exported to files and signed on an offline host
+    Offline {
+        /// Validator wallet public key
+        #[serde(with = "serde_hex_array")]
+        public_key: [u8; 32],
+        /// Message expiration timeout
+        #[serde(default = "default_offline_timeout_sec")]
+        timeout_sec: u32,
+    },
+    /// Messages are signed by an external process over a Unix socket
+    Remote {
+        /// Validator wallet public key
+        #[serde(with = "serde_hex_array")]
+        public_key: [u8; 32],
+        /// Path to the signer socket (`signer.sock` in the root directory by default)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        socket: Option<PathBuf>,
+    },
+}
+
+fn default_offline_timeout_sec() -> u32 {
+    3600
+}
+
+/// Limits for the validator wallet transfers made by the manager
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfigSpendingPolicy {
+    /// Max amount of a single transfer (in nano tokens)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_per_tx: Option<u64>,
+    /// Max total amount of transfers during the last 24 hours (in nano tokens)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_per_day: Option<u64>,
+    /// Transfers above this amount must be approved with `validator ctl approve-spending:<hash>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation_threshold: Option<u64>,
+    /// Allowed destinations in addition to the elector, DePool and its proxies
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_destinations: Vec<String>,
+}
+
+impl AppConfigSpendingPolicy {
+    pub fn parse_allowed_destinations(&self) -> Result<Vec<ton_block::MsgAddressInt>> {
+        self.allowed_destinations
+            .iter()
+            .map(|address| {
+                crate::util::parse_address(address)
+                    .with_context(|| format!("invalid allowed destination: {address}"))
+            })
+            .collect()
+    }
+}
+
+/// Validator wallet surplus sweeping params
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfigSweep {
+    /// Cold wallet address
+    #[serde(with = "serde_string")]
+    pub address: ton_block::MsgAddressInt,
+    /// Amount which is kept in addition to the next stakes (in nano tokens)
+    #[serde(default, with = "serde_string_or_number")]
+    pub reserve: u64,
+    /// Min amount to sweep (in nano tokens)
+    #[serde(default = "default_sweep_min_amount", with = "serde_string_or_number")]
+    pub min_amount: u64,
+}
+
+fn default_sweep_min_amount() -> u64 {
+    10_000_000_000
+}
+
+/// Background DePool ticktocks params
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfigDePoolTicktock {
+    /// DePool rounds check interval (in seconds)
+    #[serde(default = "default_ticktock_interval")]
+    pub interval_sec: u32,
+    /// Max number of ticktocks within 24 hours
+    #[serde(default = "default_ticktock_max_per_day")]
+    pub max_per_day: u32,
+    /// Max wallet transaction fees of ticktocks within 24 hours (in nano tokens)
+    #[serde(
+        default = "default_ticktock_max_fees_per_day",
+        with = "serde_string_or_number"
+    )]
+    pub max_fees_per_day: u64,
+}
+
+fn default_ticktock_interval() -> u32 {
+    600
+}
+
+fn default_ticktock_max_per_day() -> u32 {
+    12
+}
+
+fn default_ticktock_max_fees_per_day() -> u64 {
+    1_000_000_000
+}
+
+/// Destination for the validator events
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfigNotificationSink {
+    /// Events to send (all events if empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<NotificationKind>,
+    /// Min interval between events of the same kind (in seconds)
+    #[serde(default = "default_notification_interval")]
+    pub min_interval_sec: u32,
+    /// Sink type and params
+    pub target: NotificationTarget,
+}
+
+impl AppConfigNotificationSink {
+    pub fn accepts(&self, kind: NotificationKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+fn default_notification_interval() -> u32 {
+    300
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum NotificationTarget {
+    /// Generic JSON webhook
+    Webhook { url: String },
+    /// Telegram bot
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+        #[serde(default = "default_telegram_api_url")]
+        api_url: String,
+    },
+    /// Slack incoming webhook
+    Slack { url: String },
+}
+
+fn default_telegram_api_url() -> String {
+    "https://api.telegram.org".to_owned()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    StakeSent,
+    StakeRecovered,
+    Elected,
+    NotElected,
+    DepoolRoundFailed,
+    BalanceWaiting,
+    NodeOutOfSync,
+    SpendingBlocked,
+    Error,
+}
+
+impl MaintenanceWindow {
+    pub fn overlaps(&self, since: u32, until: u32) -> bool {
+        self.since < until && since < self.until
+    }
+
+    pub fn contains(&self, time: u32) -> bool {
+        self.since <= time && time < self.until
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -151,7 +413,73 @@ pub struct AppConfigValidatorSingle {
     #[serde(with = "serde_string_or_number")]
     pub stake_per_round: u64,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stake: Option<StakePolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub stake_factor: Option<u32>,
+    /// Send an additional stake during the elections if the desired stake grows
+    #[serde(default = "default_stake_top_up", skip_serializing_if = "is_true")]
+    pub stake_top_up: bool,
+    #[serde(default, skip_serializing_if = "WalletType::is_default")]
+    pub wallet_type: WalletType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallet_deploy: Option<AppConfigMultisigDeploymentParams>,
+}
+
+fn default_stake_top_up() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+impl AppConfigValidatorSingle {
+    /// Explicit stake policy or a fixed `stake_per_round`
+    pub fn stake_policy(&self) -> StakePolicy {
+        self.stake.unwrap_or(StakePolicy::Fixed {
+            amount: self.stake_per_round,
+        })
+    }
+}
+
+/// Single validator stake sizing policy
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case", tag = "type")]
+pub enum StakePolicy {
+    /// Fixed amount of nano tokens
+    Fixed {
+        #[serde(with = "serde_string_or_number")]
+        amount: u64,
+    },
+    /// All wallet balance except the reserve
+    Balance {
+        #[serde(with = "serde_string_or_number")]
+        reserve: u64,
+    },
+    /// Percentage of the total funds split evenly across two rounds
+    Percent {
+        percent: u8,
+        #[serde(default, with = "serde_string_or_number")]
+        reserve: u64,
+    },
+}
+
+impl StakePolicy {
+    /// Computes the stake for the next round
+    ///
+    /// `balance` is the current wallet balance (without fees reserve),
+    /// `frozen` is the stake which is still frozen in the elector.
+    pub fn compute(&self, balance: u128, frozen: u128) -> u128 {
+        match *self {
+            Self::Fixed { amount } => amount as u128,
+            Self::Balance { reserve } => balance.saturating_sub(reserve as u128),
+            Self::Percent { percent, reserve } => {
+                let available = balance.saturating_sub(reserve as u128);
+                let total = (available + frozen) * std::cmp::min(percent, 100) as u128 / 100;
+                std::cmp::min(total / 2, available)
+            }
+        }
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -172,6 +500,10 @@ pub struct AppConfigValidatorDePool {
     pub cluster: Option<ton_block::MsgAddressInt>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub deploy: Option<AppConfigDePoolDeploymentParams>,
+    #[serde(default, skip_serializing_if = "WalletType::is_default")]
+    pub wallet_type: WalletType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallet_deploy: Option<AppConfigMultisigDeploymentParams>,
 }
 
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -183,6 +515,59 @@ pub struct AppConfigDePoolDeploymentParams {
     pub participant_reward_fraction: u8,
 }
 
+/// Validator wallet contract
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletType {
+    #[default]
+    EverWallet,
+    SafeMultisig,
+    SetcodeMultisig,
+}
+
+impl WalletType {
+    pub fn is_default(&self) -> bool {
+        matches!(self, Self::EverWallet)
+    }
+
+    pub fn is_multisig(&self) -> bool {
+        matches!(self, Self::SafeMultisig | Self::SetcodeMultisig)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfigMultisigDeploymentParams {
+    /// Path to the multisig contract TVC
+    pub tvc: PathBuf,
+    /// Hex encoded custodian public keys
+    pub custodians: Vec<String>,
+    /// Number of confirmations required to send a transaction
+    pub req_confirms: u8,
+}
+
+impl AppConfigMultisigDeploymentParams {
+    pub fn load_tvc(&self) -> Result<ton_block::StateInit> {
+        use ton_block::Deserializable;
+
+        let data = std::fs::read(&self.tvc).context("failed to read multisig TVC")?;
+        ton_block::StateInit::construct_from_bytes(&data).context("invalid multisig TVC")
+    }
+
+    pub fn parse_custodians(&self) -> Result<Vec<ton_types::UInt256>> {
+        self.custodians
+            .iter()
+            .map(|pubkey| {
+                let pubkey = hex::decode(pubkey.trim_start_matches("0x"))
+                    .ok()
+                    .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                    .with_context(|| format!("invalid custodian public key: {pubkey}"))?;
+                Ok(ton_types::UInt256::from(pubkey))
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum DePoolType {
     #[serde(rename = "default_v3")]
@@ -204,3 +589,117 @@ impl DePoolType {
         !self.is_default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE: u128 = 1_000_000_000;
+
+    #[test]
+    fn depool_rejects_top_up() {
+        const DEPOOL: &str = r#"
+            [validator]
+            type = "depool"
+            owner = "0:0000000000000000000000000000000000000000000000000000000000000001"
+            depool = "0:0000000000000000000000000000000000000000000000000000000000000002"
+            depool_type = "stever_v2"
+        "#;
+
+        let config: AppConfig = toml::from_str(DEPOOL).unwrap();
+        assert!(matches!(
+            config.validator,
+            Some(AppConfigValidator::DePool(_))
+        ));
+
+        let with_top_up = format!("{DEPOOL}stake_top_up = false\n");
+        assert!(toml::from_str::<AppConfig>(&with_top_up).is_err());
+    }
+
+    #[test]
+    fn fixed_stake() {
+        let policy = StakePolicy::Fixed {
+            amount: 10_000_000_000,
+        };
+        assert_eq!(policy.compute(0, 0), 10 * ONE);
+        assert_eq!(policy.compute(100 * ONE, 50 * ONE), 10 * ONE);
+    }
+
+    #[test]
+    fn balance_stake() {
+        let policy = StakePolicy::Balance {
+            reserve: 10_000_000_000,
+        };
+        assert_eq!(policy.compute(100 * ONE, 50 * ONE), 90 * ONE);
+        assert_eq!(policy.compute(5 * ONE, 0), 0);
+    }
+
+    #[test]
+    fn percent_stake() {
+        let policy = StakePolicy::Percent {
+            percent: 50,
+            reserve: 0,
+        };
+        // Half of the total funds split across two rounds
+        assert_eq!(policy.compute(100 * ONE, 100 * ONE), 50 * ONE);
+        // Limited by the available balance
+        assert_eq!(policy.compute(10 * ONE, 190 * ONE), 10 * ONE);
+
+        let policy = StakePolicy::Percent {
+            percent: 100,
+            reserve: 20_000_000_000,
+        };
+        assert_eq!(policy.compute(120 * ONE, 100 * ONE), 100 * ONE);
+        assert_eq!(policy.compute(10 * ONE, 100 * ONE), 0);
+
+        // Percent is clamped to 100
+        let policy = StakePolicy::Percent {
+            percent: 200,
+            reserve: 0,
+        };
+        assert_eq!(policy.compute(100 * ONE, 100 * ONE), 100 * ONE);
+    }
+
+    #[test]
+    fn maintenance_window_overlaps() {
+        let window = MaintenanceWindow {
+            since: 100,
+            until: 200,
+            reason: None,
+        };
+
+        assert!(window.overlaps(50, 150));
+        assert!(window.overlaps(150, 250));
+        assert!(window.overlaps(120, 180));
+        assert!(window.overlaps(0, 1000));
+
+        // Ranges are half-open
+        assert!(!window.overlaps(0, 100));
+        assert!(!window.overlaps(200, 300));
+        assert!(!window.overlaps(0, 50));
+        assert!(!window.overlaps(250, 300));
+
+        assert!(window.contains(100));
+        assert!(window.contains(199));
+        assert!(!window.contains(200));
+    }
+
+    #[test]
+    fn parse_stake_policy() {
+        let policy: StakePolicy = toml::from_str(
+            r#"
+            type = "percent"
+            percent = 50
+            reserve = "10000000000"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            policy,
+            StakePolicy::Percent {
+                percent: 50,
+                reserve: 10_000_000_000
+            }
+        );
+    }
+}
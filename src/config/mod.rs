@@ -1,10 +1,13 @@
 pub use self::app_config::{
     AppConfig, AppConfigAdnl, AppConfigControl, AppConfigDePoolDeploymentParams,
+    AppConfigDePoolTicktock, AppConfigHooks, AppConfigMultisigDeploymentParams,
+    AppConfigNotificationSink, AppConfigSigner, AppConfigSpendingPolicy, AppConfigSweep,
     AppConfigValidator, AppConfigValidatorDePool, AppConfigValidatorSingle, DePoolType,
+    MaintenanceWindow, NotificationKind, NotificationTarget, StakePolicy, WalletType,
 };
 pub use self::global_config::GlobalConfig;
 pub use self::node_config::{NodeConfig, NodeConfigAdnl, NodeConfigControlServer, NodeLogConfig};
-pub use self::stored_keys::StoredKeys;
+pub use self::stored_keys::{keys_password, StoredKeys};
 
 mod app_config;
 mod global_config;
@@ -2,9 +2,18 @@ use std::path::Path;
 
 use anyhow::{Context, Result};
 use broxus_util::{serde_hex_array, serde_optional_hex_array};
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 
 use crate::crypto::*;
+use crate::util::{is_terminal, write_file_atomic};
+
+/// Environment variable with the keys password
+const KEYS_PASSWORD_ENV: &str = "NODEKEEPER_KEYS_PASSWORD";
+/// Environment variable with the path to the keys password file
+const KEYS_PASSWORD_FILE_ENV: &str = "NODEKEEPER_KEYS_PASSWORD_FILE";
+/// Name of the systemd credential with the keys password
+const KEYS_PASSWORD_CREDENTIAL: &str = "nodekeeper-keys-password";
 
 #[derive(Serialize)]
 pub struct StoredKeys {
@@ -59,6 +68,13 @@ impl StoredKeys {
         Ok(Self::load(path)?.as_keypair())
     }
 
+    /// Returns whether the keys file is password protected
+    pub fn is_encrypted<P: AsRef<Path>>(path: P) -> Result<bool> {
+        let data = std::fs::read(path).context("failed to read keys file")?;
+        Ok(is_encrypted_data(&data))
+    }
+
+    /// Loads keys, encrypted keys are decrypted with the password from [`keys_password`]
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         fn inner(path: &Path) -> Result<StoredKeys> {
             #[derive(Deserialize)]
@@ -72,9 +88,17 @@ impl StoredKeys {
                 pub seed: Option<String>,
             }
 
-            let file = std::fs::File::open(path).context("failed to open keys file")?;
-            let mut deserializer =
-                serde_json::Deserializer::from_reader(std::io::BufReader::new(file));
+            let mut data = std::fs::read(path).context("failed to read keys file")?;
+            if is_encrypted_data(&data) {
+                let encrypted: EncryptedData =
+                    serde_json::from_slice(&data).context("failed to parse encrypted keys")?;
+                let password = keys_password()?;
+                data = encrypted
+                    .decrypt(&password)
+                    .context("failed to decrypt keys")?;
+            }
+
+            let mut deserializer = serde_json::Deserializer::from_slice(&data);
             let data: StoredKeysHelper = serde_path_to_error::deserialize(&mut deserializer)
                 .context("failed to parse keys")?;
 
@@ -96,7 +120,15 @@ impl StoredKeys {
 
     pub fn store<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let data = serde_json::to_string_pretty(self).context("failed to serialize keys")?;
-        std::fs::write(path, data).context("failed to save keys")
+        save_keys_file(path.as_ref(), data)
+    }
+
+    /// Stores keys encrypted with the password
+    pub fn store_encrypted<P: AsRef<Path>>(&self, path: P, password: &str) -> Result<()> {
+        let data = serde_json::to_vec(self).context("failed to serialize keys")?;
+        let encrypted = EncryptedData::encrypt(&data, password);
+        let data = serde_json::to_string_pretty(&encrypted).context("failed to serialize keys")?;
+        save_keys_file(path.as_ref(), data)
     }
 
     pub fn as_secret(&self) -> ed25519_dalek::SecretKey {
@@ -109,3 +141,63 @@ impl StoredKeys {
         ed25519_dalek::Keypair { secret, public }
     }
 }
+
+/// Resolves the password for the encrypted keys once per process.
+///
+/// Sources are checked in the following order: `NODEKEEPER_KEYS_PASSWORD` env,
+/// file from `NODEKEEPER_KEYS_PASSWORD_FILE` env, systemd credential and
+/// an interactive prompt.
+pub fn keys_password() -> Result<String> {
+    static PASSWORD: OnceCell<String> = OnceCell::new();
+    PASSWORD.get_or_try_init(resolve_keys_password).cloned()
+}
+
+fn resolve_keys_password() -> Result<String> {
+    if let Ok(password) = std::env::var(KEYS_PASSWORD_ENV) {
+        return Ok(password);
+    }
+
+    if let Some(path) = std::env::var_os(KEYS_PASSWORD_FILE_ENV) {
+        return read_password_file(Path::new(&path));
+    }
+
+    if let Some(dir) = std::env::var_os("CREDENTIALS_DIRECTORY") {
+        let path = Path::new(&dir).join(KEYS_PASSWORD_CREDENTIAL);
+        if path.exists() {
+            return read_password_file(&path);
+        }
+    }
+
+    anyhow::ensure!(
+        is_terminal(),
+        "keys are encrypted but the password was not provided"
+    );
+    dialoguer::Password::new()
+        .with_prompt("Keys password")
+        .interact()
+        .context("failed to read password")
+}
+
+fn read_password_file(path: &Path) -> Result<String> {
+    let password = std::fs::read_to_string(path).context("failed to read password file")?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+fn is_encrypted_data(data: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Probe {
+        #[serde(default)]
+        ciphertext: Option<serde::de::IgnoredAny>,
+    }
+
+    matches!(
+        serde_json::from_slice::<Probe>(data),
+        Ok(Probe {
+            ciphertext: Some(_)
+        })
+    )
+}
+
+fn save_keys_file(path: &Path, data: String) -> Result<()> {
+    write_file_atomic(path, data).context("failed to save keys")
+}
@@ -98,7 +98,7 @@ impl DePool {
         .pack();
 
         self.subscription
-            .send_message_with_retires(move |timeout, signature_id| {
+            .send_message_with_retires("DePool deploy", move |timeout, signature_id| {
                 let (expire_at, header) = make_default_headers(None, timeout);
 
                 let mut message = self.external_message_to_self(
@@ -123,6 +123,34 @@ impl DePool {
         Ok(())
     }
 
+    /// Closes the DePool. All stakes are returned to participants after rounds completion
+    pub async fn close(&self) -> Result<()> {
+        let keypair = self.keypair.as_ref().context("DePool keypair not set")?;
+
+        self.subscription
+            .send_message_with_retires("DePool terminator", move |timeout, signature_id| {
+                let (expire_at, header) = make_default_headers(None, timeout);
+
+                let message = self.external_message_to_self(
+                    common::terminator()
+                        .encode_input(
+                            &header,
+                            &[],
+                            false,
+                            Some((keypair, signature_id)),
+                            Some(self.address.clone()),
+                        )
+                        .context("failed to encode terminator")?,
+                )?;
+
+                Ok((message, expire_at))
+            })
+            .await
+            .context("failed to send terminator message")?;
+
+        Ok(())
+    }
+
     pub fn ticktock(&self) -> Result<InternalMessage> {
         self.internal_message_to_self(ONE_EVER, common::ticktock().encode_internal_input(&[])?)
     }
@@ -238,6 +266,18 @@ impl DePool {
         )
     }
 
+    /// Methods which are used in internal messages to the DePool
+    pub fn internal_methods() -> [&'static ton_abi::Function; 6] {
+        [
+            common::add_ordinary_stake(),
+            common::withdraw_part(),
+            common::withdraw_from_pooling_round(),
+            common::ticktock(),
+            common::receive_funds(),
+            stever::set_allowed_participant(),
+        ]
+    }
+
     pub fn get_participant_info(
         &self,
         state: &ton_block::AccountStuff,
@@ -261,6 +301,13 @@ impl DePool {
         DePoolState { state, ty: self.ty }.get_allowed_participants()
     }
 
+    pub fn get_participants(
+        &self,
+        state: &ton_block::AccountStuff,
+    ) -> Result<Vec<ton_block::MsgAddressInt>> {
+        DePoolState { state, ty: self.ty }.get_participants()
+    }
+
     pub async fn get_state(&self) -> Result<ton_block::AccountStuff> {
         self.subscription
             .get_account_state(&self.address)
@@ -377,6 +424,15 @@ impl DePoolState<'_> {
         Ok(rounds)
     }
 
+    pub fn get_participants(&self) -> Result<Vec<ton_block::MsgAddressInt>> {
+        let function = match self.ty {
+            DePoolType::DefaultV3 => common::get_participants(),
+            DePoolType::StEverV1 | DePoolType::StEverV2 => stever::get_participants(),
+        };
+        let participants = self.run_local(function, &[])?.unpack_first()?;
+        Ok(participants)
+    }
+
     pub fn get_allowed_participants(&self) -> Result<Vec<ton_block::MsgAddressInt>> {
         self.ensure_stever()?;
         let addresses: stever::ParticipantsMap = self
@@ -617,6 +673,15 @@ mod common {
         })
     }
 
+    pub fn terminator() -> &'static ton_abi::Function {
+        once!(ton_abi::Function, || {
+            FunctionBuilder::new("terminator")
+                .time_header()
+                .expire_header()
+                .build()
+        })
+    }
+
     pub fn add_ordinary_stake() -> &'static ton_abi::Function {
         once!(ton_abi::Function, || {
             FunctionBuilder::new("addOrdinaryStake")
@@ -676,6 +741,20 @@ mod common {
         })
     }
 
+    pub fn get_participants() -> &'static ton_abi::Function {
+        once!(ton_abi::Function, || {
+            FunctionBuilder::new("getParticipants")
+                .time_header()
+                .expire_header()
+                .abi_version(ton_abi::contract::ABI_VERSION_2_0)
+                .output(
+                    "participants",
+                    Vec::<ton_block::MsgAddressInt>::param_type(),
+                )
+                .build()
+        })
+    }
+
     pub fn get_depool_info() -> &'static ton_abi::Function {
         once!(ton_abi::Function, || {
             FunctionBuilder::new("getDePoolInfo")
@@ -712,6 +791,20 @@ mod stever {
         })
     }
 
+    pub fn get_participants() -> &'static ton_abi::Function {
+        once!(ton_abi::Function, || {
+            FunctionBuilder::new("getParticipants")
+                .abi_version(ton_abi::contract::ABI_VERSION_2_2)
+                .time_header()
+                .expire_header()
+                .output(
+                    "participants",
+                    Vec::<ton_block::MsgAddressInt>::param_type(),
+                )
+                .build()
+        })
+    }
+
     pub fn set_allowed_participant() -> &'static ton_abi::Function {
         once!(ton_abi::Function, || {
             FunctionBuilder::new("setAllowedParticipant")
@@ -2,11 +2,12 @@ use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
-use broxus_util::now;
+use broxus_util::{now, serde_optional_hex_array};
 use nekoton_abi::{
     BuildTokenValue, FunctionBuilder, KnownParamType, KnownParamTypePlain, MaybeRef, PackAbiPlain,
     TokenValueExt, UnpackAbi, UnpackAbiPlain,
 };
+use serde::{Deserialize, Serialize};
 
 use super::{InternalMessage, ONE_EVER};
 use crate::network::Subscription;
@@ -48,14 +49,20 @@ impl Elector {
     }
 
     /// Prepares validator node and generates elector payload
-    pub async fn participate_in_elections(
+    ///
+    /// Keys from `generated` are reused, new keys are passed to `save` right after generation.
+    pub async fn participate_in_elections<F>(
         &self,
         election_id: u32,
         address: &ton_block::MsgAddressInt,
         stake_factor: u32,
         timings: &ton_block::ConfigParam15,
-        signature_id: Option<i32>,
-    ) -> Result<ton_types::Cell> {
+        generated: &mut GeneratedKeys,
+        mut save: F,
+    ) -> Result<ElectionPayload>
+    where
+        F: FnMut(&GeneratedKeys) -> Result<()>,
+    {
         const TTL_OFFSET: u32 = 1000;
 
         anyhow::ensure!(
@@ -68,39 +75,67 @@ impl Elector {
         let rpc = self.subscription.tcp_rpc();
 
         // Generate new key
-        let permanent_key_hash = rpc
-            .generate_key_pair()
-            .await
-            .context("failed to generate validator keys")?;
+        let permanent_key_hash = match generated.permanent_key_hash {
+            Some(key_hash) => key_hash,
+            None => {
+                let key_hash = rpc
+                    .generate_key_pair()
+                    .await
+                    .context("failed to generate validator keys")?;
+                generated.permanent_key_hash = Some(key_hash);
+                save(generated)?;
+                key_hash
+            }
+        };
 
         // Export its public key
         let perm_pubkey = rpc
             .export_public_key(&permanent_key_hash)
             .await
             .context("failed to export validator public key")?;
+        if generated.public_key.is_none() {
+            generated.public_key = Some(perm_pubkey.to_bytes());
+            save(generated)?;
+        }
 
         // Add this key as a validator key
-        let ttl = election_id
-            + timings.validators_elected_for
-            + timings.elections_start_before
-            + timings.elections_end_before
-            + timings.stake_held_for
-            + TTL_OFFSET;
-        rpc.add_validator_permanent_key(&permanent_key_hash, election_id, ttl)
-            .await
-            .context("failed to add validator permanent key")?;
+        if !generated.permanent_key_added {
+            let ttl = election_id
+                + timings.validators_elected_for
+                + timings.elections_start_before
+                + timings.elections_end_before
+                + timings.stake_held_for
+                + TTL_OFFSET;
+            rpc.add_validator_permanent_key(&permanent_key_hash, election_id, ttl)
+                .await
+                .context("failed to add validator permanent key")?;
+            generated.permanent_key_added = true;
+            save(generated)?;
+        }
 
         // Generate adnl key (key hash is equal to adnl addr)
-        let adnl_addr = rpc
-            .generate_key_pair()
-            .await
-            .context("failed to generate validator adnl keys")?;
+        let adnl_addr = match generated.adnl_addr {
+            Some(adnl_addr) => adnl_addr,
+            None => {
+                let adnl_addr = rpc
+                    .generate_key_pair()
+                    .await
+                    .context("failed to generate validator adnl keys")?;
+                generated.adnl_addr = Some(adnl_addr);
+                save(generated)?;
+                adnl_addr
+            }
+        };
 
         // Assign adnl address to the validator key
         // NOTE: ttl is 0 here because it is unused in the node
-        rpc.add_validator_adnl_address(&permanent_key_hash, &adnl_addr, 0)
-            .await
-            .context("failed to add validator adnl address")?;
+        if !generated.adnl_addr_added {
+            rpc.add_validator_adnl_address(&permanent_key_hash, &adnl_addr, 0)
+                .await
+                .context("failed to add validator adnl address")?;
+            generated.adnl_addr_added = true;
+            save(generated)?;
+        }
 
         // Sign data
         let unsigned = UnsignedParticipantData {
@@ -111,6 +146,7 @@ impl Elector {
             adnl_addr: ton_types::UInt256::from(adnl_addr),
         };
 
+        let signature_id = self.subscription.get_signature_id().await?;
         let data_to_sign = unsigned.build_data_to_sign();
         let data_to_sign = ton_abi::extend_signature_with_id(&data_to_sign, signature_id);
 
@@ -120,9 +156,46 @@ impl Elector {
             .context("failed to sign election data")?;
 
         // Generate internal message payload
-        unsigned
+        let payload = unsigned
             .sign(signature)
-            .context("failed to insert signature")
+            .context("failed to insert signature")?;
+
+        Ok(ElectionPayload {
+            keys: ValidatorKeys {
+                permanent_key_hash,
+                public_key: perm_pubkey.to_bytes(),
+                adnl_addr,
+            },
+            payload,
+        })
+    }
+
+    /// Generates elector payload with empty keys and signature
+    pub fn participation_preview(
+        &self,
+        election_id: u32,
+        address: &ton_block::MsgAddressInt,
+        stake_factor: u32,
+    ) -> Result<ton_types::Cell> {
+        let (_, address) = split_address(address)?;
+
+        UnsignedParticipantData {
+            election_id,
+            address,
+            max_factor: stake_factor,
+            public_key: Default::default(),
+            adnl_addr: Default::default(),
+        }
+        .sign([0; 64])
+        .context("failed to build election payload")
+    }
+
+    /// Methods which are used in internal messages to the elector
+    pub fn internal_methods() -> [&'static ton_abi::Function; 2] {
+        [
+            methods::recover_stake(),
+            methods::participate_in_elections(),
+        ]
     }
 
     pub async fn get_data(&self) -> Result<ElectorData> {
@@ -154,10 +227,46 @@ impl Elector {
     }
 }
 
+/// Validator keys which were added to the node for the elections
+#[derive(Debug, Clone, Copy)]
+pub struct ValidatorKeys {
+    pub permanent_key_hash: [u8; 32],
+    pub public_key: [u8; 32],
+    pub adnl_addr: [u8; 32],
+}
+
+/// Keys which were generated for the elections so far
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct GeneratedKeys {
+    #[serde(default, with = "serde_optional_hex_array")]
+    pub permanent_key_hash: Option<[u8; 32]>,
+    #[serde(default, with = "serde_optional_hex_array")]
+    pub public_key: Option<[u8; 32]>,
+    #[serde(default)]
+    pub permanent_key_added: bool,
+    #[serde(default, with = "serde_optional_hex_array")]
+    pub adnl_addr: Option<[u8; 32]>,
+    #[serde(default)]
+    pub adnl_addr_added: bool,
+}
+
+pub struct ElectionPayload {
+    pub keys: ValidatorKeys,
+    pub payload: ton_types::Cell,
+}
+
 pub struct ElectorData {
     inner: data::PartialElectorData,
 }
 
+/// Stakes of the ongoing elections
+pub struct CurrentElectionStakes {
+    pub min_stake: u128,
+    pub total_stake: u128,
+    /// Stakes of other participants in descending order
+    pub stakes: Vec<u128>,
+}
+
 impl ElectorData {
     pub fn election_id(&self) -> Option<u32> {
         let election_id = self.inner.current_election.0.as_ref()?.elect_at;
@@ -185,6 +294,94 @@ impl ElectorData {
         self.inner.credits.get(&address).copied()
     }
 
+    pub fn is_past_election(&self, election_id: u32) -> bool {
+        self.inner.past_elections.contains_key(&election_id)
+    }
+
+    /// Returns the true stake of the participant if it was elected in the finished elections
+    pub fn frozen_stake(
+        &self,
+        election_id: u32,
+        address: &ton_block::MsgAddressInt,
+    ) -> Option<u128> {
+        if !address.is_masterchain() {
+            return None;
+        }
+
+        let (_, address) = split_address(address).ok()?;
+        let election = self.inner.past_elections.get(&election_id)?;
+        election
+            .frozen_dict
+            .values()
+            .find(|frozen| frozen.addr == address)
+            .map(|frozen| frozen.true_stake)
+    }
+
+    /// Returns the total stake of the participant which is still frozen in the elector
+    pub fn total_frozen_stake(&self, address: &ton_block::MsgAddressInt) -> u128 {
+        if !address.is_masterchain() {
+            return 0;
+        }
+
+        let Ok((_, address)) = split_address(address) else {
+            return 0;
+        };
+
+        self.inner
+            .past_elections
+            .values()
+            .flat_map(|election| election.frozen_dict.values())
+            .filter(|frozen| frozen.addr == address)
+            .map(|frozen| frozen.true_stake)
+            .sum()
+    }
+
+    /// Returns stakes of the ongoing elections without the specified participant
+    pub fn current_election_stakes(
+        &self,
+        participant: &ton_block::MsgAddressInt,
+    ) -> Option<CurrentElectionStakes> {
+        let current_election = self.inner.current_election.0.as_ref()?;
+        let participant = split_address(participant).ok().map(|(_, addr)| addr);
+
+        let mut stakes = current_election
+            .members
+            .values()
+            .filter(|member| Some(&member.src_addr) != participant.as_ref())
+            .map(|member| member.msg_value as u128)
+            .collect::<Vec<_>>();
+        stakes.sort_unstable_by(|a, b| b.cmp(a));
+
+        Some(CurrentElectionStakes {
+            min_stake: current_election.min_stake,
+            total_stake: current_election.total_stake,
+            stakes,
+        })
+    }
+
+    /// Returns the stake of the participant in the current elections
+    pub fn member_stake(&self, address: &ton_block::MsgAddressInt) -> Option<u128> {
+        let current_election = self.inner.current_election.0.as_ref()?;
+        let (_, address) = split_address(address).ok()?;
+
+        current_election
+            .members
+            .values()
+            .filter(|member| member.src_addr == address)
+            .map(|member| member.msg_value as u128)
+            .reduce(|a, b| a + b)
+    }
+
+    /// Returns whether the public key is in the current elections members
+    pub fn is_member(&self, public_key: &[u8; 32]) -> bool {
+        match &self.inner.current_election.0 {
+            Some(current_election) => current_election
+                .members
+                .contains_key(&ton_types::UInt256::from(*public_key)),
+            None => false,
+        }
+    }
+
     pub fn elected(&self, address: &ton_block::MsgAddressInt) -> bool {
         if !address.is_masterchain() {
             return false;
@@ -204,12 +401,12 @@ impl ElectorData {
     }
 }
 
-struct UnsignedParticipantData {
-    election_id: u32,
-    address: ton_types::UInt256,
-    max_factor: u32,
-    public_key: ton_types::UInt256,
-    adnl_addr: ton_types::UInt256,
+pub(crate) struct UnsignedParticipantData {
+    pub election_id: u32,
+    pub address: ton_types::UInt256,
+    pub max_factor: u32,
+    pub public_key: ton_types::UInt256,
+    pub adnl_addr: ton_types::UInt256,
 }
 
 impl UnsignedParticipantData {
@@ -225,7 +422,7 @@ impl UnsignedParticipantData {
         data
     }
 
-    fn sign(self, signature: [u8; 64]) -> Result<ton_types::Cell> {
+    pub fn sign(self, signature: [u8; 64]) -> Result<ton_types::Cell> {
         methods::participate_in_elections()
             .encode_internal_input(
                 &methods::ParticipateInElectionsInputs {
@@ -295,6 +492,28 @@ mod data {
     pub struct PastElectionData {
         #[abi(uint32)]
         pub unfreeze_at: u32,
+        #[abi(uint32)]
+        pub stake_held: u32,
+        #[abi(uint256)]
+        pub vset_hash: ton_types::UInt256,
+        #[abi]
+        pub frozen_dict: BTreeMap<ton_types::UInt256, FrozenStake>,
+        #[abi(gram)]
+        pub total_stake: u128,
+        #[abi(gram)]
+        pub bonuses: u128,
+    }
+
+    #[derive(Debug, UnpackAbi, KnownParamType)]
+    pub struct FrozenStake {
+        #[abi(uint256)]
+        pub addr: ton_types::UInt256,
+        #[abi(uint64)]
+        pub weight: u64,
+        #[abi(gram)]
+        pub true_stake: u128,
+        #[abi(bool)]
+        pub banned: bool,
     }
 }
 
@@ -1,8 +1,10 @@
+use anyhow::{Context, Result};
+
 pub use cluster::Cluster;
 pub use depool::DePool;
 pub use elector::Elector;
 pub use strategy::Strategy;
-pub use wallet::Wallet;
+pub use wallet::{Wallet, WalletCall, WalletSigner};
 
 pub mod cluster;
 pub mod depool;
@@ -29,4 +31,36 @@ impl InternalMessage {
     }
 }
 
+/// Internal message payload decoded with one of the known methods
+pub struct DecodedPayload {
+    pub method: &'static str,
+    pub input: serde_json::Value,
+}
+
+/// Tries to decode internal message payload using known elector and DePool methods
+pub fn decode_internal_payload(payload: &ton_types::Cell) -> Result<Option<DecodedPayload>> {
+    let mut body = ton_types::SliceData::load_cell(payload.clone())?;
+    if body.remaining_bits() < 32 {
+        return Ok(None);
+    }
+    let function_id = body.get_next_u32()?;
+
+    let Some(function) = Elector::internal_methods()
+        .into_iter()
+        .chain(DePool::internal_methods())
+        .find(|function| function.input_id == function_id)
+    else {
+        return Ok(None);
+    };
+
+    let tokens =
+        ton_abi::TokenValue::decode_params(&function.inputs, body, &function.abi_version, false)
+            .with_context(|| format!("failed to decode `{}` input", function.name))?;
+
+    Ok(Some(DecodedPayload {
+        method: &function.name,
+        input: nekoton_abi::make_abi_tokens(&tokens)?,
+    }))
+}
+
 pub const ONE_EVER: u128 = 1_000_000_000;
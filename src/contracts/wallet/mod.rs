@@ -1,16 +1,29 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
-use nekoton_abi::{FunctionBuilder, KnownParamTypePlain, PackAbiPlain};
+use nekoton_abi::{
+    FunctionBuilder, KnownParamTypePlain, PackAbiPlain, UnpackAbiPlain, UnpackFirst,
+};
 use ton_abi::contract::ABI_VERSION_2_3;
-use ton_block::{Deserializable, GetRepresentationHash};
+use ton_block::{Deserializable, GetRepresentationHash, Serializable};
 
-use super::{InternalMessage, ONE_EVER};
-use crate::network::Subscription;
+pub use self::multisig::{MultisigCustodian, MultisigTransaction};
+pub use self::remote::serve as serve_remote_signer;
+use self::remote::{RemoteSigner, SignRequest};
+pub use self::signer::{OfflineMessage, WalletSigner, WalletTransfer};
+use super::{decode_internal_payload, InternalMessage, ONE_EVER};
+use crate::config::WalletType;
+use crate::network::{Subscription, TransactionsRx};
 use crate::util::{make_default_headers, TransactionWithHash};
 
+mod multisig;
+mod remote;
+mod signer;
+
 pub struct Wallet {
-    keypair: ed25519_dalek::Keypair,
+    ty: WalletType,
+    signer: WalletSigner,
     address: ton_block::MsgAddressInt,
     subscription: Arc<Subscription>,
 }
@@ -24,68 +37,145 @@ impl Wallet {
         subscription: Arc<Subscription>,
     ) -> Self {
         Self {
+            ty: WalletType::EverWallet,
             address: compute_wallet_address(workchain_id, &keypair.public),
-            keypair,
+            signer: WalletSigner::Local(keypair),
             subscription,
         }
     }
 
+    /// Creates a wallet of the specified type for the configured address
+    pub fn with_type(
+        ty: WalletType,
+        address: ton_block::MsgAddressInt,
+        keypair: ed25519_dalek::Keypair,
+        subscription: Arc<Subscription>,
+    ) -> Result<Self> {
+        Self::with_signer(ty, address, WalletSigner::Local(keypair), subscription)
+    }
+
+    /// Same as [`with_type`], but messages are signed with the specified signer
+    ///
+    /// [`with_type`]: Self::with_type
+    pub fn with_signer(
+        ty: WalletType,
+        address: ton_block::MsgAddressInt,
+        signer: WalletSigner,
+        subscription: Arc<Subscription>,
+    ) -> Result<Self> {
+        let wallet = Self {
+            ty,
+            address: match ty {
+                WalletType::EverWallet => {
+                    compute_wallet_address(address.workchain_id() as i8, signer.public_key())
+                }
+                // NOTE: multisig address depends on the contract code, so it is taken as is
+                WalletType::SafeMultisig | WalletType::SetcodeMultisig => address.clone(),
+            },
+            signer,
+            subscription,
+        };
+        anyhow::ensure!(
+            wallet.address == address,
+            "validator wallet address mismatch"
+        );
+        Ok(wallet)
+    }
+
+    pub fn ty(&self) -> WalletType {
+        self.ty
+    }
+
     pub fn address(&self) -> &ton_block::MsgAddressInt {
         &self.address
     }
 
+    pub fn public_key(&self) -> &ed25519_dalek::PublicKey {
+        self.signer.public_key()
+    }
+
+    pub async fn is_deployed(&self) -> Result<bool> {
+        let Some(account) = self.get_account_state().await? else {
+            return Ok(false);
+        };
+
+        match account.storage.state {
+            ton_block::AccountState::AccountActive { .. } => Ok(true),
+            ton_block::AccountState::AccountFrozen { .. } => anyhow::bail!("account frozen"),
+            ton_block::AccountState::AccountUninit => Ok(false),
+        }
+    }
+
     pub async fn get_balance(&self) -> Result<Option<u128>> {
         let account = self.get_account_state().await?;
         Ok(account.map(|state| state.storage.balance.grams.as_u128()))
     }
 
-    /// Sends the internal message to the recipient, returns the destination transaction
-    pub async fn call(&self, internal_message: InternalMessage) -> Result<TransactionWithHash> {
+    /// Sends the internal message to the recipient
+    ///
+    /// Returns the destination transaction or the submitted multisig
+    /// transaction which requires confirmations of other custodians.
+    pub async fn call(&self, internal_message: InternalMessage) -> Result<WalletCall> {
         let dst = internal_message.dst.clone();
         let mut dst_transactions = self.subscription.subscribe(&dst);
+        let src_transactions = self
+            .ty
+            .is_multisig()
+            .then(|| self.subscription.subscribe(&self.address));
 
         let src_tx = self.transfer(internal_message).await?;
         tracing::debug!(source_tx_hash = ?src_tx.hash, "message sent from wallet");
 
-        let mut out_msg_hash = None;
-        src_tx
-            .data
-            .out_msgs
-            .iterate_slices(|msg| {
-                let Some(msg) = msg.reference_opt(0) else {
-                    return Ok(true);
-                };
-
-                let msg_hash = msg.repr_hash();
-                let msg = ton_block::Message::construct_from_cell(msg)?;
-                let Some(header) = msg.int_header() else {
-                    return Ok(true);
-                };
-
-                if header.dst == dst {
-                    out_msg_hash = Some(msg_hash);
-                    Ok(false)
-                } else {
-                    Ok(true)
-                }
-            })
-            .context("failed to find outgoing message")?;
-        let out_msg_hash = out_msg_hash.context("outgoing message not found")?;
-
-        while let Some(tx) = dst_transactions.recv().await {
-            tracing::debug!(source_tx_hash = ?src_tx.hash, tx_hash = ?tx.hash, "new transaction found");
-            let Some(msg) = tx.data.in_msg_cell() else {
-                continue;
-            };
-            if msg.repr_hash() == out_msg_hash {
-                return Ok(tx);
+        let out_msg_hash = match (find_out_msg(&src_tx, &dst)?, src_transactions) {
+            (Some(hash), _) => hash,
+            (None, Some(src_transactions)) => {
+                tracing::info!(
+                    source_tx_hash = ?src_tx.hash,
+                    "multisig transaction is waiting for confirmations"
+                );
+                return Ok(WalletCall::Submitted(PendingConfirmation {
+                    deadline: src_tx
+                        .data
+                        .now()
+                        .saturating_add(MULTISIG_TRANSACTION_LIFETIME),
+                    src_tx,
+                    dst,
+                    src_transactions,
+                    dst_transactions,
+                    _subscription: self.subscription.clone(),
+                }));
             }
-        }
-        anyhow::bail!("destination transaction was not found")
+            (None, None) => anyhow::bail!("outgoing message not found"),
+        };
+
+        let tx = find_dst_transaction(&mut dst_transactions, &out_msg_hash).await?;
+        Ok(WalletCall::Delivered(tx))
     }
 
     /// Sends the internal message to the recipient, returns the source transaction
     pub async fn transfer(&self, internal_message: InternalMessage) -> Result<TransactionWithHash> {
+        let call = self.prepare_transfer(internal_message).await?;
+        self.send_external(call).await
+    }
+
+    /// Builds an unsigned transfer message to be signed on another host
+    pub async fn export_transfer(
+        &self,
+        internal_message: InternalMessage,
+        timeout: u32,
+    ) -> Result<OfflineMessage> {
+        let call = self.prepare_transfer(internal_message).await?;
+        self.make_offline_message(
+            &call.purpose,
+            call.function,
+            &call.inputs,
+            call.state_init.as_ref(),
+            timeout,
+        )
+        .await
+    }
+
+    async fn prepare_transfer(&self, internal_message: InternalMessage) -> Result<PreparedCall> {
         let account = self.get_account_state().await?;
 
         let state_init = match account {
@@ -94,26 +184,183 @@ impl Wallet {
                 ton_block::AccountState::AccountFrozen { .. } => {
                     anyhow::bail!("account frozen");
                 }
-                ton_block::AccountState::AccountUninit => Some(
-                    make_state_init(&self.keypair.public).context("failed to make state init")?,
-                ),
+                ton_block::AccountState::AccountUninit if self.ty.is_multisig() => {
+                    anyhow::bail!("multisig wallet is not deployed");
+                }
+                ton_block::AccountState::AccountUninit => {
+                    Some(make_state_init(self.public_key()).context("failed to make state init")?)
+                }
             },
             None => anyhow::bail!("account not deployed"),
         };
 
-        let inputs = ever_wallet::SendTransactionInputs {
-            dest: internal_message.dst,
-            value: internal_message.amount,
-            bounce: internal_message.bounce,
-            flags: 3,
-            payload: internal_message.payload,
+        // Describe the message for the outbound queue
+        let purpose = match decode_internal_payload(&internal_message.payload) {
+            Ok(Some(payload)) => format!("wallet transfer ({})", payload.method),
+            _ => "wallet transfer".to_owned(),
+        };
+
+        let destination = Some(internal_message.dst.clone());
+        let amount = Some(internal_message.amount);
+
+        let (function, inputs) = match self.ty {
+            WalletType::EverWallet => (
+                ever_wallet::send_transaction(),
+                ever_wallet::SendTransactionInputs {
+                    dest: internal_message.dst,
+                    value: internal_message.amount,
+                    bounce: internal_message.bounce,
+                    flags: 3,
+                    payload: internal_message.payload,
+                }
+                .pack(),
+            ),
+            WalletType::SafeMultisig | WalletType::SetcodeMultisig => (
+                multisig::submit_transaction(),
+                multisig::SubmitTransactionInputs {
+                    dest: internal_message.dst,
+                    value: internal_message.amount,
+                    bounce: internal_message.bounce,
+                    all_balance: false,
+                    payload: internal_message.payload,
+                }
+                .pack(),
+            ),
+        };
+
+        Ok(PreparedCall {
+            purpose,
+            function,
+            inputs,
+            state_init,
+            destination,
+            amount,
+        })
+    }
+
+    /// Deploys the multisig wallet using the code from the specified TVC
+    pub async fn deploy_multisig(
+        &self,
+        tvc: &ton_block::StateInit,
+        owners: Vec<ton_types::UInt256>,
+        req_confirms: u8,
+    ) -> Result<TransactionWithHash> {
+        anyhow::ensure!(self.ty.is_multisig(), "wallet is not a multisig");
+        anyhow::ensure!(
+            !owners.is_empty() && req_confirms > 0 && req_confirms as usize <= owners.len(),
+            "invalid multisig custodians or required confirmations"
+        );
+
+        let mut state_init = tvc.clone();
+        if let Some(data) = state_init.data.take() {
+            let data = ton_types::SliceData::load_cell(data)?;
+            let data = ton_abi::Contract::insert_pubkey(data, self.public_key().as_bytes())
+                .context("failed to insert pubkey")?;
+            state_init.data = Some(data.into_cell());
+        }
+
+        let hash = state_init
+            .serialize()
+            .context("failed to serialize multisig state")?
+            .repr_hash();
+        let computed_address = ton_block::MsgAddressInt::with_standart(
+            None,
+            self.address.workchain_id() as i8,
+            hash.into(),
+        )?;
+        anyhow::ensure!(
+            computed_address == self.address,
+            "multisig TVC doesn't match the wallet address"
+        );
+
+        let inputs = multisig::ConstructorInputs {
+            owners,
+            req_confirms,
         }
         .pack();
 
+        self.send_external(PreparedCall {
+            purpose: "multisig deploy".to_owned(),
+            function: multisig::constructor(),
+            inputs,
+            state_init: Some(state_init),
+            destination: None,
+            amount: None,
+        })
+        .await
+    }
+
+    /// Confirms the pending multisig transaction, returns the source transaction
+    pub async fn confirm_transaction(&self, transaction_id: u64) -> Result<TransactionWithHash> {
+        anyhow::ensure!(self.ty.is_multisig(), "wallet is not a multisig");
+
+        let inputs = multisig::ConfirmTransactionInputs { transaction_id }.pack();
+        self.send_external(PreparedCall {
+            purpose: "multisig confirm".to_owned(),
+            function: multisig::confirm_transaction(),
+            inputs,
+            state_init: None,
+            destination: None,
+            amount: None,
+        })
+        .await
+    }
+
+    /// Returns multisig transactions which are waiting for confirmations
+    pub async fn get_pending_transactions(&self) -> Result<Vec<MultisigTransaction>> {
+        anyhow::ensure!(self.ty.is_multisig(), "wallet is not a multisig");
+
+        self.subscription
+            .run_local(&self.address, multisig::get_transactions(), &[])
+            .await
+            .context("failed to get pending transactions")?
+            .unpack_first()
+            .context("invalid pending transactions")
+    }
+
+    /// Returns multisig custodians
+    pub async fn get_custodians(&self) -> Result<Vec<MultisigCustodian>> {
+        anyhow::ensure!(self.ty.is_multisig(), "wallet is not a multisig");
+
+        self.subscription
+            .run_local(&self.address, multisig::get_custodians(), &[])
+            .await
+            .context("failed to get custodians")?
+            .unpack_first()
+            .context("invalid custodians")
+    }
+
+    async fn send_external(&self, call: PreparedCall) -> Result<TransactionWithHash> {
+        let keypair = match &self.signer {
+            WalletSigner::Local(keypair) => keypair,
+            WalletSigner::Offline(signer) => {
+                let message = self
+                    .make_offline_message(
+                        &call.purpose,
+                        call.function,
+                        &call.inputs,
+                        call.state_init.as_ref(),
+                        signer.timeout,
+                    )
+                    .await?;
+                let message = signer.sign(&message).await?;
+                return message.broadcast(&self.subscription).await;
+            }
+            WalletSigner::Remote(signer) => return self.send_remote(signer, call).await,
+        };
+
+        let PreparedCall {
+            purpose,
+            function,
+            inputs,
+            state_init,
+            ..
+        } = call;
+
         let tx = self
             .subscription
-            .send_message_with_retires(|timeout, signature_id| {
-                let (expire_at, headers) = make_default_headers(Some(self.keypair.public), timeout);
+            .send_message_with_retires(&purpose, |timeout, signature_id| {
+                let (expire_at, headers) = make_default_headers(Some(keypair.public), timeout);
 
                 let mut message = ton_block::Message::with_ext_in_header(
                     ton_block::ExternalInboundMessageHeader {
@@ -123,12 +370,12 @@ impl Wallet {
                 );
 
                 message.set_body(
-                    ever_wallet::send_transaction()
+                    function
                         .encode_input(
                             &headers,
                             &inputs,
                             false,
-                            Some((&self.keypair, signature_id)),
+                            Some((keypair, signature_id)),
                             Some(self.address.clone()),
                         )
                         .and_then(ton_types::SliceData::load_builder)?,
@@ -145,6 +392,84 @@ impl Wallet {
         Ok(tx)
     }
 
+    /// Signs messages with the remote signer, retrying expired ones
+    async fn send_remote(
+        &self,
+        signer: &RemoteSigner,
+        call: PreparedCall,
+    ) -> Result<TransactionWithHash> {
+        const MAX_ATTEMPTS: usize = 5;
+        const TIMEOUT: u32 = 60;
+
+        let mut attempt = 0;
+        loop {
+            let mut message = self
+                .make_offline_message(
+                    &call.purpose,
+                    call.function,
+                    &call.inputs,
+                    call.state_init.as_ref(),
+                    TIMEOUT,
+                )
+                .await?;
+
+            let signature = signer
+                .sign(&SignRequest {
+                    public_key: message.public_key,
+                    data: message.data_to_sign.clone(),
+                    body: message.body.clone(),
+                    abi_version: message.abi_version,
+                    signature_id: message.signature_id,
+                    purpose: call.purpose.clone(),
+                    wallet: self.address.clone(),
+                    destination: call.destination.clone(),
+                    amount: call.amount,
+                    expire_at: message.expire_at,
+                })
+                .await?;
+            message.signature = Some(base64::encode(signature));
+
+            let tx = self
+                .subscription
+                .send_message(&message.build()?, message.expire_at, &call.purpose)
+                .await?;
+            if let Some(tx) = tx {
+                break Ok(tx);
+            }
+
+            attempt += 1;
+            if attempt >= MAX_ATTEMPTS {
+                anyhow::bail!("{}: message expired after {attempt} attempts", call.purpose);
+            }
+            tracing::warn!(
+                purpose = call.purpose,
+                attempt,
+                "retrying expired external message"
+            );
+        }
+    }
+
+    async fn make_offline_message(
+        &self,
+        purpose: &str,
+        function: &ton_abi::Function,
+        inputs: &[ton_abi::Token],
+        state_init: Option<&ton_block::StateInit>,
+        timeout: u32,
+    ) -> Result<OfflineMessage> {
+        let signature_id = self.subscription.get_signature_id().await?;
+        OfflineMessage::new(
+            purpose,
+            &self.address,
+            self.public_key(),
+            function,
+            inputs,
+            state_init,
+            timeout,
+            signature_id,
+        )
+    }
+
     async fn get_account_state(&self) -> Result<Option<ton_block::AccountStuff>> {
         self.subscription
             .get_account_state(&self.address)
@@ -153,6 +478,121 @@ impl Wallet {
     }
 }
 
+/// Result of the wallet call
+pub enum WalletCall {
+    /// Message was delivered to the recipient
+    Delivered(TransactionWithHash),
+    /// Multisig transaction requires confirmations of other custodians
+    Submitted(PendingConfirmation),
+}
+
+impl WalletCall {
+    /// Destination transaction or the submitted multisig transaction
+    pub fn tx(&self) -> &TransactionWithHash {
+        match self {
+            Self::Delivered(tx) => tx,
+            Self::Submitted(pending) => &pending.src_tx,
+        }
+    }
+}
+
+/// Multisig transaction which is waiting for confirmations of other custodians
+pub struct PendingConfirmation {
+    src_tx: TransactionWithHash,
+    dst: ton_block::MsgAddressInt,
+    deadline: u32,
+    src_transactions: TransactionsRx,
+    dst_transactions: TransactionsRx,
+    /// Keeps the subscription loop running while waiting
+    _subscription: Arc<Subscription>,
+}
+
+impl PendingConfirmation {
+    /// Waits until the transaction is confirmed or expired, returns the destination transaction
+    pub async fn wait(mut self) -> Result<TransactionWithHash> {
+        let timeout = self.deadline.saturating_sub(broxus_util::now());
+        let wait = async {
+            let out_msg_hash = loop {
+                let tx = self
+                    .src_transactions
+                    .recv()
+                    .await
+                    .context("multisig transaction was not confirmed")?;
+                if let Some(hash) = find_out_msg(&tx, &self.dst)? {
+                    break hash;
+                }
+            };
+            find_dst_transaction(&mut self.dst_transactions, &out_msg_hash).await
+        };
+
+        match tokio::time::timeout(Duration::from_secs(timeout as u64), wait).await {
+            Ok(res) => res,
+            Err(_) => anyhow::bail!("multisig transaction expired"),
+        }
+    }
+}
+
+struct PreparedCall {
+    purpose: String,
+    function: &'static ton_abi::Function,
+    inputs: Vec<ton_abi::Token>,
+    state_init: Option<ton_block::StateInit>,
+    /// Transfer destination for the remote signer
+    destination: Option<ton_block::MsgAddressInt>,
+    /// Transfer amount for the remote signer
+    amount: Option<u128>,
+}
+
+/// Waits for the transaction with the specified inbound message
+async fn find_dst_transaction(
+    dst_transactions: &mut TransactionsRx,
+    msg_hash: &ton_types::UInt256,
+) -> Result<TransactionWithHash> {
+    while let Some(tx) = dst_transactions.recv().await {
+        tracing::debug!(tx_hash = ?tx.hash, "new transaction found");
+        let Some(msg) = tx.data.in_msg_cell() else {
+            continue;
+        };
+        if &msg.repr_hash() == msg_hash {
+            return Ok(tx);
+        }
+    }
+    anyhow::bail!("destination transaction was not found")
+}
+
+/// Finds the hash of the outgoing message to the specified address
+fn find_out_msg(
+    tx: &TransactionWithHash,
+    dst: &ton_block::MsgAddressInt,
+) -> Result<Option<ton_types::UInt256>> {
+    let mut out_msg_hash = None;
+    tx.data
+        .out_msgs
+        .iterate_slices(|msg| {
+            let Some(msg) = msg.reference_opt(0) else {
+                return Ok(true);
+            };
+
+            let msg_hash = msg.repr_hash();
+            let msg = ton_block::Message::construct_from_cell(msg)?;
+            let Some(header) = msg.int_header() else {
+                return Ok(true);
+            };
+
+            if &header.dst == dst {
+                out_msg_hash = Some(msg_hash);
+                Ok(false)
+            } else {
+                Ok(true)
+            }
+        })
+        .context("failed to find outgoing message")?;
+    Ok(out_msg_hash)
+}
+
+/// Unconfirmed multisig transactions are removed after this period
+const MULTISIG_TRANSACTION_LIFETIME: u32 = 3600;
+
 pub fn compute_wallet_address(
     workchain_id: i8,
     pubkey: &ed25519_dalek::PublicKey,
@@ -191,7 +631,7 @@ fn ever_wallet_code() -> &'static ton_types::Cell {
 mod ever_wallet {
     use super::*;
 
-    #[derive(Clone, PackAbiPlain, KnownParamTypePlain)]
+    #[derive(Clone, PackAbiPlain, UnpackAbiPlain, KnownParamTypePlain)]
     pub struct SendTransactionInputs {
         #[abi(address)]
         pub dest: ton_block::MsgAddressInt,
@@ -0,0 +1,141 @@
+use nekoton_abi::{
+    FunctionBuilder, KnownParamType, KnownParamTypePlain, PackAbiPlain, UnpackAbi, UnpackAbiPlain,
+};
+use ton_abi::contract::ABI_VERSION_2_0;
+
+#[derive(Clone, PackAbiPlain, KnownParamTypePlain)]
+pub struct ConstructorInputs {
+    #[abi(array)]
+    pub owners: Vec<ton_types::UInt256>,
+    #[abi(uint8)]
+    pub req_confirms: u8,
+}
+
+#[derive(Clone, PackAbiPlain, UnpackAbiPlain, KnownParamTypePlain)]
+pub struct SubmitTransactionInputs {
+    #[abi(address)]
+    pub dest: ton_block::MsgAddressInt,
+    #[abi(uint128)]
+    pub value: u128,
+    #[abi(bool)]
+    pub bounce: bool,
+    #[abi(bool)]
+    pub all_balance: bool,
+    #[abi(cell)]
+    pub payload: ton_types::Cell,
+}
+
+#[derive(Clone, PackAbiPlain, KnownParamTypePlain)]
+pub struct ConfirmTransactionInputs {
+    #[abi(uint64)]
+    pub transaction_id: u64,
+}
+
+/// Multisig transaction which is waiting for confirmations
+#[derive(Debug, Clone, UnpackAbi, KnownParamType)]
+pub struct MultisigTransaction {
+    #[abi(uint64)]
+    pub id: u64,
+    #[abi(uint32)]
+    pub confirmations_mask: u32,
+    #[abi(uint8)]
+    pub signs_required: u8,
+    #[abi(uint8)]
+    pub signs_received: u8,
+    #[abi(uint256)]
+    pub creator: ton_types::UInt256,
+    #[abi(uint8)]
+    pub index: u8,
+    #[abi(address)]
+    pub dest: ton_block::MsgAddressInt,
+    #[abi(uint128)]
+    pub value: u128,
+    #[abi(uint16)]
+    pub send_flags: u16,
+    #[abi(cell)]
+    pub payload: ton_types::Cell,
+    #[abi(bool)]
+    pub bounce: bool,
+}
+
+impl MultisigTransaction {
+    /// Whether the custodian with the specified index has confirmed the transaction
+    pub fn is_confirmed_by(&self, custodian_index: u8) -> bool {
+        custodian_index < 32 && self.confirmations_mask & (1 << custodian_index) != 0
+    }
+}
+
+#[derive(Debug, Clone, UnpackAbi, KnownParamType)]
+pub struct MultisigCustodian {
+    #[abi(uint8)]
+    pub index: u8,
+    #[abi(uint256)]
+    pub pubkey: ton_types::UInt256,
+}
+
+pub fn constructor() -> &'static ton_abi::Function {
+    once!(ton_abi::Function, || {
+        FunctionBuilder::new("constructor")
+            .abi_version(ABI_VERSION_2_0)
+            .pubkey_header()
+            .time_header()
+            .expire_header()
+            .inputs(ConstructorInputs::param_type())
+            .build()
+    })
+}
+
+pub fn submit_transaction() -> &'static ton_abi::Function {
+    once!(ton_abi::Function, || {
+        FunctionBuilder::new("submitTransaction")
+            .abi_version(ABI_VERSION_2_0)
+            .pubkey_header()
+            .time_header()
+            .expire_header()
+            .inputs(SubmitTransactionInputs::param_type())
+            .output("transId", u64::param_type())
+            .build()
+    })
+}
+
+pub fn confirm_transaction() -> &'static ton_abi::Function {
+    once!(ton_abi::Function, || {
+        FunctionBuilder::new("confirmTransaction")
+            .abi_version(ABI_VERSION_2_0)
+            .pubkey_header()
+            .time_header()
+            .expire_header()
+            .inputs(ConfirmTransactionInputs::param_type())
+            .build()
+    })
+}
+
+pub fn get_transactions() -> &'static ton_abi::Function {
+    once!(ton_abi::Function, || {
+        FunctionBuilder::new("getTransactions")
+            .abi_version(ABI_VERSION_2_0)
+            .pubkey_header()
+            .time_header()
+            .expire_header()
+            .output(
+                "transactions",
+                ton_abi::ParamType::Array(Box::new(MultisigTransaction::param_type())),
+            )
+            .build()
+    })
+}
+
+pub fn get_custodians() -> &'static ton_abi::Function {
+    once!(ton_abi::Function, || {
+        FunctionBuilder::new("getCustodians")
+            .abi_version(ABI_VERSION_2_0)
+            .pubkey_header()
+            .time_header()
+            .expire_header()
+            .output(
+                "custodians",
+                ton_abi::ParamType::Array(Box::new(MultisigCustodian::param_type())),
+            )
+            .build()
+    })
+}
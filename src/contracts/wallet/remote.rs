@@ -0,0 +1,352 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use broxus_util::{serde_base64_array, serde_hex_array, serde_optional_string, serde_string};
+use ed25519_dalek::Signer;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use super::signer::{compute_data_to_sign, decode_transfer};
+use crate::util::system;
+
+/// Delegates signing to an external process over a Unix socket
+pub struct RemoteSigner {
+    pub public: ed25519_dalek::PublicKey,
+    pub socket: PathBuf,
+}
+
+impl RemoteSigner {
+    /// Sends the request to the signer and verifies the returned signature
+    pub async fn sign(&self, request: &SignRequest) -> Result<[u8; 64]> {
+        const TIMEOUT: Duration = Duration::from_secs(30);
+
+        anyhow::ensure!(
+            request.public_key == self.public.to_bytes(),
+            "sign request public key mismatch"
+        );
+
+        let response = tokio::time::timeout(TIMEOUT, self.send_request(request))
+            .await
+            .context("remote signer timeout")??;
+
+        let signature = match response {
+            SignResponse::Ok { signature } => signature,
+            SignResponse::Error { error } => {
+                anyhow::bail!("remote signer rejected the request: {error}")
+            }
+        };
+
+        let data = base64::decode(&request.data).context("invalid data to sign")?;
+        self.public
+            .verify_strict(&data, &ed25519_dalek::Signature::from_bytes(&signature)?)
+            .context("remote signer returned an invalid signature")?;
+
+        Ok(signature)
+    }
+
+    async fn send_request(&self, request: &SignRequest) -> Result<SignResponse> {
+        let stream = UnixStream::connect(&self.socket).await.with_context(|| {
+            format!(
+                "failed to connect to the remote signer at {}",
+                self.socket.display()
+            )
+        })?;
+        let (reader, mut writer) = stream.into_split();
+
+        write_line(&mut writer, request).await?;
+
+        let mut line = String::new();
+        BufReader::new(reader)
+            .read_line(&mut line)
+            .await
+            .context("failed to read signer response")?;
+
+        serde_json::from_str(&line).context("invalid signer response")
+    }
+}
+
+/// Request to sign the wallet external message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignRequest {
+    /// Expected signer public key
+    #[serde(with = "serde_hex_array")]
+    pub public_key: [u8; 32],
+    /// Base64 encoded data to sign (message hash extended with the signature id)
+    pub data: String,
+    /// Base64 encoded BOC with the unsigned message body
+    pub body: String,
+    /// Wallet ABI version of the body
+    pub abi_version: [u8; 2],
+    /// Network signature id which is mixed into the data to sign
+    #[serde(default)]
+    pub signature_id: Option<i32>,
+    /// Message description
+    pub purpose: String,
+    /// Wallet address
+    #[serde(with = "serde_string")]
+    pub wallet: ton_block::MsgAddressInt,
+    /// Destination of the transfer (if any)
+    #[serde(default, with = "serde_optional_string")]
+    pub destination: Option<ton_block::MsgAddressInt>,
+    /// Transfer amount in nano tokens (if any)
+    #[serde(default, with = "serde_optional_string")]
+    pub amount: Option<u128>,
+    pub expire_at: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+enum SignResponse {
+    Ok {
+        #[serde(with = "serde_base64_array")]
+        signature: [u8; 64],
+    },
+    Error {
+        error: String,
+    },
+}
+
+/// Reference signer which signs all requests with the specified keys
+pub async fn serve<P: AsRef<Path>>(path: P, keypair: ed25519_dalek::Keypair) -> Result<()> {
+    let path = path.as_ref();
+
+    let listener = system::bind_private_socket(path).context("failed to bind signer socket")?;
+    tracing::info!(
+        public_key = %hex::encode(keypair.public.as_bytes()),
+        "listening for sign requests on {}",
+        path.display()
+    );
+
+    let keypair = Arc::new(keypair);
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .context("failed to accept signer connection")?;
+
+        let keypair = keypair.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &keypair).await {
+                tracing::warn!("failed to handle signer connection: {e:?}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: UnixStream, keypair: &ed25519_dalek::Keypair) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = String::new();
+    BufReader::new(reader)
+        .take(MAX_REQUEST_LEN)
+        .read_line(&mut line)
+        .await
+        .context("failed to read sign request")?;
+    anyhow::ensure!(
+        (line.len() as u64) < MAX_REQUEST_LEN,
+        "sign request is too long"
+    );
+
+    let response = match serde_json::from_str::<SignRequest>(&line) {
+        Ok(request) => match sign_request(&request, keypair) {
+            Ok(signature) => SignResponse::Ok { signature },
+            Err(e) => SignResponse::Error {
+                error: e.to_string(),
+            },
+        },
+        Err(e) => SignResponse::Error {
+            error: format!("invalid sign request: {e}"),
+        },
+    };
+
+    write_line(&mut writer, &response).await
+}
+
+fn sign_request(request: &SignRequest, keypair: &ed25519_dalek::Keypair) -> Result<[u8; 64]> {
+    anyhow::ensure!(
+        request.public_key == keypair.public.to_bytes(),
+        "unknown public key"
+    );
+    anyhow::ensure!(
+        request.expire_at > broxus_util::now(),
+        "message already expired"
+    );
+
+    // NOTE: request fields are not trusted, everything is checked against the body
+    let body = base64::decode(&request.body).context("invalid message body")?;
+    let body = ton_types::deserialize_tree_of_cells(&mut body.as_slice())
+        .context("invalid message body")?;
+
+    let data = compute_data_to_sign(
+        &request.wallet,
+        request.abi_version,
+        &body,
+        request.signature_id,
+    )?;
+    anyhow::ensure!(
+        base64::decode(&request.data).context("invalid data to sign")? == data,
+        "data to sign doesn't match the message body"
+    );
+
+    let transfer = decode_transfer(request.abi_version, &request.public_key, &body)
+        .context("failed to decode wallet message")?;
+    match (&transfer, &request.destination, request.amount) {
+        (Some(transfer), Some(destination), Some(amount)) => {
+            anyhow::ensure!(
+                &transfer.dst == destination,
+                "transfer destination mismatch"
+            );
+            anyhow::ensure!(
+                transfer.value == amount && !transfer.all_balance,
+                "transfer amount mismatch"
+            );
+        }
+        (None, None, None) => {}
+        (Some(_), ..) => anyhow::bail!("message body contains an undeclared transfer"),
+        (None, ..) => anyhow::bail!("message body doesn't contain the declared transfer"),
+    }
+
+    tracing::info!(
+        purpose = request.purpose,
+        wallet = %request.wallet,
+        destination = ?request.destination.as_ref().map(ToString::to_string),
+        amount = ?request.amount,
+        expire_at = request.expire_at,
+        "signing message"
+    );
+
+    Ok(keypair.sign(&data).to_bytes())
+}
+
+async fn write_line<W, T>(writer: &mut W, value: &T) -> Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+    T: Serialize,
+{
+    let mut data = serde_json::to_vec(value)?;
+    data.push(b'\n');
+    writer
+        .write_all(&data)
+        .await
+        .context("failed to write signer message")
+}
+
+/// Max length of the sign request line (in bytes)
+const MAX_REQUEST_LEN: u64 = 1 << 20;
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use nekoton_abi::PackAbiPlain;
+
+    use super::super::{ever_wallet, OfflineMessage};
+    use super::*;
+
+    const ONE: u128 = 1_000_000_000;
+
+    fn make_keypair(byte: u8) -> ed25519_dalek::Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[byte; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        ed25519_dalek::Keypair { secret, public }
+    }
+
+    fn make_address(byte: u8) -> ton_block::MsgAddressInt {
+        ton_block::MsgAddressInt::from_str(&format!("0:{}", hex::encode([byte; 32]))).unwrap()
+    }
+
+    fn make_request(value: u128) -> SignRequest {
+        let inputs = ever_wallet::SendTransactionInputs {
+            dest: make_address(2),
+            value,
+            bounce: false,
+            flags: 3,
+            payload: Default::default(),
+        }
+        .pack();
+
+        let message = OfflineMessage::new(
+            "transfer",
+            &make_address(1),
+            &make_keypair(1).public,
+            ever_wallet::send_transaction(),
+            &inputs,
+            None,
+            60,
+            Some(42),
+        )
+        .unwrap();
+
+        SignRequest {
+            public_key: message.public_key,
+            data: message.data_to_sign,
+            body: message.body,
+            abi_version: message.abi_version,
+            signature_id: message.signature_id,
+            purpose: message.purpose,
+            wallet: message.dst,
+            destination: Some(make_address(2)),
+            amount: Some(value),
+            expire_at: message.expire_at,
+        }
+    }
+
+    #[test]
+    fn signs_declared_transfer() {
+        let keypair = make_keypair(1);
+        let request = make_request(10 * ONE);
+
+        let signature = sign_request(&request, &keypair).unwrap();
+        let data = base64::decode(&request.data).unwrap();
+        keypair
+            .public
+            .verify_strict(
+                &data,
+                &ed25519_dalek::Signature::from_bytes(&signature).unwrap(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let keypair = make_keypair(1);
+        let request = make_request(10 * ONE);
+
+        assert!(sign_request(&request, &make_keypair(2)).is_err());
+
+        let mut expired = request.clone();
+        expired.expire_at = 1;
+        assert!(sign_request(&expired, &keypair).is_err());
+
+        // Data to sign from another message
+        let mut tampered = request.clone();
+        tampered.data = make_request(20 * ONE).data;
+        assert!(sign_request(&tampered, &keypair).is_err());
+
+        let mut tampered = request.clone();
+        tampered.signature_id = None;
+        assert!(sign_request(&tampered, &keypair).is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_transfer() {
+        let keypair = make_keypair(1);
+        let request = make_request(10 * ONE);
+
+        let mut tampered = request.clone();
+        tampered.destination = Some(make_address(3));
+        assert!(sign_request(&tampered, &keypair).is_err());
+
+        let mut tampered = request.clone();
+        tampered.amount = Some(ONE);
+        assert!(sign_request(&tampered, &keypair).is_err());
+
+        let mut tampered = request;
+        tampered.destination = None;
+        tampered.amount = None;
+        assert!(sign_request(&tampered, &keypair).is_err());
+    }
+}
@@ -0,0 +1,489 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use broxus_util::{serde_hex_array, serde_string};
+use ed25519_dalek::Signer;
+use serde::{Deserialize, Serialize};
+use ton_block::{Deserializable, Serializable};
+
+use super::remote::RemoteSigner;
+use crate::config::{AppConfigSigner, StoredKeys};
+use crate::dirs::ProjectDirs;
+use crate::network::Subscription;
+use crate::util::TransactionWithHash;
+
+/// Signs wallet external messages
+pub enum WalletSigner {
+    /// Secret key is stored on this host
+    Local(ed25519_dalek::Keypair),
+    /// Messages are exported to files and signed on another host
+    Offline(OfflineSigner),
+    /// Messages are signed by an external process
+    Remote(RemoteSigner),
+}
+
+impl WalletSigner {
+    /// Creates the validator wallet signer from the app config
+    pub fn load(config: Option<&AppConfigSigner>, dirs: &ProjectDirs) -> Result<Self> {
+        match config {
+            None => {
+                let keys = StoredKeys::load(&dirs.validator_keys)
+                    .context("failed to load validator wallet keys")?;
+                Ok(Self::Local(keys.as_keypair()))
+            }
+            Some(AppConfigSigner::Offline {
+                public_key,
+                timeout_sec,
+            }) => Ok(Self::Offline(OfflineSigner {
+                public: ed25519_dalek::PublicKey::from_bytes(public_key)
+                    .context("invalid signer public key")?,
+                dir: dirs.offline_messages.clone(),
+                timeout: *timeout_sec,
+            })),
+            Some(AppConfigSigner::Remote { public_key, socket }) => {
+                Ok(Self::Remote(RemoteSigner {
+                    public: ed25519_dalek::PublicKey::from_bytes(public_key)
+                        .context("invalid signer public key")?,
+                    socket: socket.clone().unwrap_or_else(|| dirs.signer_socket.clone()),
+                }))
+            }
+        }
+    }
+
+    pub fn public_key(&self) -> &ed25519_dalek::PublicKey {
+        match self {
+            Self::Local(keypair) => &keypair.public,
+            Self::Offline(signer) => &signer.public,
+            Self::Remote(signer) => &signer.public,
+        }
+    }
+}
+
+pub struct OfflineSigner {
+    pub public: ed25519_dalek::PublicKey,
+    /// Directory for the exported messages
+    pub dir: PathBuf,
+    /// Message expiration timeout in seconds
+    pub timeout: u32,
+}
+
+impl OfflineSigner {
+    /// Exports the message and waits until it is signed on another host
+    pub async fn sign(&self, message: &OfflineMessage) -> Result<OfflineMessage> {
+        const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+        std::fs::create_dir_all(&self.dir).context("failed to create offline messages dir")?;
+        let path = self.dir.join(message.file_name());
+        message.save(&path)?;
+
+        tracing::warn!(
+            path = %path.display(),
+            purpose = message.purpose,
+            expire_at = message.expire_at,
+            "waiting for the message to be signed offline"
+        );
+
+        loop {
+            let message = OfflineMessage::load(&path)?;
+            if message.signature.is_some() {
+                return Ok(message);
+            }
+
+            anyhow::ensure!(
+                message.expire_at > broxus_util::now(),
+                "offline message was not signed in time"
+            );
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// External message which is built on the online host and signed on another one
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OfflineMessage {
+    #[serde(with = "serde_string")]
+    pub dst: ton_block::MsgAddressInt,
+    pub purpose: String,
+    pub expire_at: u32,
+    pub signature_id: Option<i32>,
+    #[serde(with = "serde_hex_array")]
+    pub public_key: [u8; 32],
+    pub abi_version: [u8; 2],
+    /// Base64 encoded BOC with the unsigned body
+    pub body: String,
+    /// Base64 encoded BOC with the state init
+    pub state_init: Option<String>,
+    /// Base64 encoded data to sign
+    pub data_to_sign: String,
+    /// Base64 encoded signature
+    pub signature: Option<String>,
+}
+
+impl OfflineMessage {
+    /// Encodes an unsigned function call
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        purpose: &str,
+        dst: &ton_block::MsgAddressInt,
+        public_key: &ed25519_dalek::PublicKey,
+        function: &ton_abi::Function,
+        inputs: &[ton_abi::Token],
+        state_init: Option<&ton_block::StateInit>,
+        timeout: u32,
+        signature_id: Option<i32>,
+    ) -> Result<Self> {
+        let (expire_at, headers) = crate::util::make_default_headers(Some(*public_key), timeout);
+
+        let (body, hash) = function
+            .create_unsigned_call(&headers, inputs, false, true, Some(dst.clone()))
+            .context("failed to encode unsigned message")?;
+        let data_to_sign = ton_abi::extend_signature_with_id(&hash, signature_id);
+
+        let state_init = state_init
+            .map(|state_init| state_init.write_to_bytes().map(base64::encode))
+            .transpose()
+            .context("failed to serialize state init")?;
+
+        Ok(Self {
+            dst: dst.clone(),
+            purpose: purpose.to_owned(),
+            expire_at,
+            signature_id,
+            public_key: public_key.to_bytes(),
+            abi_version: [function.abi_version.major, function.abi_version.minor],
+            body: base64::encode(ton_types::serialize_toc(&body.into_cell()?)?),
+            state_init,
+            data_to_sign: base64::encode(data_to_sign),
+            signature: None,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path).context("failed to read message file")?;
+        serde_json::from_str(&data).context("invalid message file")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).context("failed to serialize message")?;
+        std::fs::write(path, data).context("failed to save message file")
+    }
+
+    pub fn file_name(&self) -> String {
+        let hash = ton_types::UInt256::calc_file_hash(self.data_to_sign.as_bytes());
+        format!("{}-{}.json", self.expire_at, &hash.to_hex_string()[..16])
+    }
+
+    /// Signs the message with the wallet keys
+    ///
+    /// NOTE: data to sign is recomputed from the body to not sign
+    /// anything other than the message which is displayed.
+    pub fn sign(&mut self, keypair: &ed25519_dalek::Keypair) -> Result<()> {
+        anyhow::ensure!(
+            keypair.public.as_bytes() == &self.public_key,
+            "keys don't match the message public key"
+        );
+
+        let data = self.compute_data_to_sign()?;
+        anyhow::ensure!(
+            base64::decode(&self.data_to_sign).context("invalid data to sign")? == data,
+            "data to sign doesn't match the message body"
+        );
+
+        self.signature = Some(base64::encode(keypair.sign(&data).to_bytes()));
+        Ok(())
+    }
+
+    /// Decodes the wallet transfer from the message body
+    pub fn decode_transfer(&self) -> Result<Option<WalletTransfer>> {
+        decode_transfer(self.abi_version, &self.public_key, &self.body_cell()?)
+    }
+
+    fn compute_data_to_sign(&self) -> Result<Vec<u8>> {
+        compute_data_to_sign(
+            &self.dst,
+            self.abi_version,
+            &self.body_cell()?,
+            self.signature_id,
+        )
+    }
+
+    fn body_cell(&self) -> Result<ton_types::Cell> {
+        let body = base64::decode(&self.body).context("invalid message body")?;
+        ton_types::deserialize_tree_of_cells(&mut body.as_slice()).context("invalid message body")
+    }
+
+    /// Builds the signed external message
+    pub fn build(&self) -> Result<ton_block::Message> {
+        let signature = self.signature.as_ref().context("message is not signed")?;
+        let signature = base64::decode(signature).context("invalid signature")?;
+
+        let public_key = ed25519_dalek::PublicKey::from_bytes(&self.public_key)?;
+        let data = base64::decode(&self.data_to_sign).context("invalid data to sign")?;
+        public_key
+            .verify_strict(&data, &ed25519_dalek::Signature::from_bytes(&signature)?)
+            .context("invalid signature")?;
+
+        let body = self.body_cell()?;
+
+        let body = ton_abi::Function::fill_sign(
+            &make_abi_version(self.abi_version),
+            Some(&signature),
+            Some(&self.public_key),
+            ton_types::BuilderData::from_cell(&body)?,
+        )
+        .context("failed to insert signature")?;
+
+        let mut message =
+            ton_block::Message::with_ext_in_header(ton_block::ExternalInboundMessageHeader {
+                dst: self.dst.clone(),
+                ..Default::default()
+            });
+        message.set_body(ton_types::SliceData::load_builder(body)?);
+
+        if let Some(state_init) = &self.state_init {
+            let state_init = base64::decode(state_init).context("invalid state init")?;
+            message.set_state_init(ton_block::StateInit::construct_from_bytes(&state_init)?);
+        }
+
+        Ok(message)
+    }
+
+    /// Sends the signed message and waits for the transaction
+    pub async fn broadcast(&self, subscription: &Subscription) -> Result<TransactionWithHash> {
+        anyhow::ensure!(self.expire_at > broxus_util::now(), "message expired");
+
+        let message = self.build()?;
+        subscription
+            .send_message(&message, self.expire_at, &self.purpose)
+            .await?
+            .context("message expired")
+    }
+}
+
+/// Transfer decoded from the wallet message body
+#[derive(Debug, Clone)]
+pub struct WalletTransfer {
+    pub dst: ton_block::MsgAddressInt,
+    pub value: u128,
+    pub all_balance: bool,
+    pub payload: ton_types::Cell,
+}
+
+/// Computes the hash of the unsigned body extended with the signature id
+pub fn compute_data_to_sign(
+    wallet: &ton_block::MsgAddressInt,
+    abi_version: [u8; 2],
+    body: &ton_types::Cell,
+    signature_id: Option<i32>,
+) -> Result<Vec<u8>> {
+    // NOTE: since ABI 2.3 the wallet address is signed with the body
+    let hash = if (abi_version[0], abi_version[1]) >= (2, 3) {
+        let mut builder = wallet.write_to_new_cell()?;
+        builder.append_builder(&ton_types::BuilderData::from_cell(body)?)?;
+        builder.into_cell()?.repr_hash()
+    } else {
+        body.repr_hash()
+    };
+    Ok(ton_abi::extend_signature_with_id(
+        hash.as_slice(),
+        signature_id,
+    ))
+}
+
+/// Decodes the transfer from the unsigned wallet message body,
+/// returns `None` for other wallet methods
+pub fn decode_transfer(
+    abi_version: [u8; 2],
+    public_key: &[u8; 32],
+    body: &ton_types::Cell,
+) -> Result<Option<WalletTransfer>> {
+    use nekoton_abi::UnpackAbiPlain;
+
+    let abi_version = make_abi_version(abi_version);
+
+    // Restore the layout of the signed body to decode it
+    let body = ton_abi::Function::fill_sign(
+        &abi_version,
+        Some(&[0; 64]),
+        Some(public_key),
+        ton_types::BuilderData::from_cell(body)?,
+    )
+    .context("failed to restore message body")?;
+    let body = ton_types::SliceData::load_builder(body)?;
+
+    let send_transaction = super::ever_wallet::send_transaction();
+    if send_transaction.abi_version == abi_version {
+        if let Ok(tokens) = send_transaction.decode_input(body.clone(), false, false) {
+            let inputs: super::ever_wallet::SendTransactionInputs =
+                tokens.unpack().context("invalid wallet transfer")?;
+            return Ok(Some(WalletTransfer {
+                dst: inputs.dest,
+                value: inputs.value,
+                all_balance: inputs.flags & 128 != 0,
+                payload: inputs.payload,
+            }));
+        }
+    }
+
+    let submit_transaction = super::multisig::submit_transaction();
+    if submit_transaction.abi_version == abi_version {
+        if let Ok(tokens) = submit_transaction.decode_input(body, false, false) {
+            let inputs: super::multisig::SubmitTransactionInputs =
+                tokens.unpack().context("invalid multisig transfer")?;
+            return Ok(Some(WalletTransfer {
+                dst: inputs.dest,
+                value: inputs.value,
+                all_balance: inputs.all_balance,
+                payload: inputs.payload,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+fn make_abi_version(version: [u8; 2]) -> ton_abi::contract::AbiVersion {
+    ton_abi::contract::AbiVersion {
+        major: version[0],
+        minor: version[1],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use nekoton_abi::PackAbiPlain;
+
+    use super::super::{ever_wallet, multisig};
+    use super::*;
+
+    const ONE: u128 = 1_000_000_000;
+
+    fn make_keypair(byte: u8) -> ed25519_dalek::Keypair {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[byte; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        ed25519_dalek::Keypair { secret, public }
+    }
+
+    fn make_address(byte: u8) -> ton_block::MsgAddressInt {
+        ton_block::MsgAddressInt::from_str(&format!("0:{}", hex::encode([byte; 32]))).unwrap()
+    }
+
+    fn ever_wallet_transfer(signature_id: Option<i32>) -> OfflineMessage {
+        let inputs = ever_wallet::SendTransactionInputs {
+            dest: make_address(2),
+            value: 10 * ONE,
+            bounce: false,
+            flags: 3,
+            payload: Default::default(),
+        }
+        .pack();
+
+        OfflineMessage::new(
+            "transfer",
+            &make_address(1),
+            &make_keypair(1).public,
+            ever_wallet::send_transaction(),
+            &inputs,
+            None,
+            60,
+            signature_id,
+        )
+        .unwrap()
+    }
+
+    fn multisig_transfer() -> OfflineMessage {
+        let inputs = multisig::SubmitTransactionInputs {
+            dest: make_address(3),
+            value: 0,
+            bounce: true,
+            all_balance: true,
+            payload: Default::default(),
+        }
+        .pack();
+
+        OfflineMessage::new(
+            "transfer",
+            &make_address(1),
+            &make_keypair(1).public,
+            multisig::submit_transaction(),
+            &inputs,
+            None,
+            60,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn data_to_sign_matches_body() {
+        for signature_id in [None, Some(42)] {
+            let message = ever_wallet_transfer(signature_id);
+            assert_eq!(
+                message.compute_data_to_sign().unwrap(),
+                base64::decode(&message.data_to_sign).unwrap()
+            );
+        }
+
+        let message = multisig_transfer();
+        assert_eq!(
+            message.compute_data_to_sign().unwrap(),
+            base64::decode(&message.data_to_sign).unwrap()
+        );
+    }
+
+    #[test]
+    fn sign_checks_message() {
+        let mut message = ever_wallet_transfer(None);
+
+        // Keys mismatch
+        assert!(message.clone().sign(&make_keypair(2)).is_err());
+
+        // Data to sign doesn't match the body
+        let mut tampered = message.clone();
+        tampered.data_to_sign = multisig_transfer().data_to_sign;
+        assert!(tampered.sign(&make_keypair(1)).is_err());
+
+        // Signature id is a part of the signed data
+        let mut tampered = message.clone();
+        tampered.signature_id = Some(42);
+        assert!(tampered.sign(&make_keypair(1)).is_err());
+
+        message.sign(&make_keypair(1)).unwrap();
+        message.build().unwrap();
+    }
+
+    #[test]
+    fn decodes_wallet_transfers() {
+        let transfer = ever_wallet_transfer(None)
+            .decode_transfer()
+            .unwrap()
+            .unwrap();
+        assert_eq!(transfer.dst, make_address(2));
+        assert_eq!(transfer.value, 10 * ONE);
+        assert!(!transfer.all_balance);
+
+        let transfer = multisig_transfer().decode_transfer().unwrap().unwrap();
+        assert_eq!(transfer.dst, make_address(3));
+        assert!(transfer.all_balance);
+    }
+
+    #[test]
+    fn other_methods_are_not_transfers() {
+        let inputs = multisig::ConfirmTransactionInputs { transaction_id: 1 }.pack();
+        let message = OfflineMessage::new(
+            "confirm",
+            &make_address(1),
+            &make_keypair(1).public,
+            multisig::confirm_transaction(),
+            &inputs,
+            None,
+            60,
+            None,
+        )
+        .unwrap();
+        assert!(message.decode_transfer().unwrap().is_none());
+    }
+}
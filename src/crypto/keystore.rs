@@ -0,0 +1,152 @@
+use anyhow::Result;
+use broxus_util::serde_hex_array;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Mac, NewMac};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+const PBKDF_ITERATIONS: u32 = 200_000;
+/// Allowed range of iterations in the keystore file
+const PBKDF_ITERATIONS_RANGE: std::ops::RangeInclusive<u32> = 10_000..=10_000_000;
+
+/// Password protected data (PBKDF2-SHA256 + AES-256-CTR + HMAC-SHA256)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptedData {
+    pub version: u8,
+    pub iterations: u32,
+    #[serde(with = "serde_hex_array")]
+    pub salt: [u8; 32],
+    #[serde(with = "serde_hex_array")]
+    pub iv: [u8; 16],
+    #[serde(with = "hex_bytes")]
+    pub ciphertext: Vec<u8>,
+    #[serde(with = "serde_hex_array")]
+    pub mac: [u8; 32],
+}
+
+impl EncryptedData {
+    pub const VERSION: u8 = 1;
+
+    pub fn encrypt(data: &[u8], password: &str) -> Self {
+        let rng = &mut rand::thread_rng();
+        let salt: [u8; 32] = rng.gen();
+        let iv: [u8; 16] = rng.gen();
+
+        let (enc_key, mac_key) = derive_keys(password, &salt, PBKDF_ITERATIONS);
+
+        let mut ciphertext = data.to_vec();
+        Aes256Ctr::new(&enc_key.into(), &iv.into()).apply_keystream(&mut ciphertext);
+
+        let mac = compute_mac(&mac_key, &iv, &ciphertext)
+            .finalize()
+            .into_bytes();
+
+        Self {
+            version: Self::VERSION,
+            iterations: PBKDF_ITERATIONS,
+            salt,
+            iv,
+            ciphertext,
+            mac: mac.into(),
+        }
+    }
+
+    pub fn decrypt(&self, password: &str) -> Result<Vec<u8>> {
+        anyhow::ensure!(
+            self.version == Self::VERSION,
+            "unsupported keystore version: {}",
+            self.version
+        );
+
+        anyhow::ensure!(
+            PBKDF_ITERATIONS_RANGE.contains(&self.iterations),
+            "keystore iterations are out of range: {}",
+            self.iterations
+        );
+
+        let (enc_key, mac_key) = derive_keys(password, &self.salt, self.iterations);
+
+        compute_mac(&mac_key, &self.iv, &self.ciphertext)
+            .verify(&self.mac)
+            .map_err(|_| anyhow::anyhow!("invalid password"))?;
+
+        let mut data = self.ciphertext.clone();
+        Aes256Ctr::new(&enc_key.into(), &self.iv.into()).apply_keystream(&mut data);
+        Ok(data)
+    }
+}
+
+/// Derives encryption and authentication keys from the password
+fn derive_keys(password: &str, salt: &[u8], iterations: u32) -> ([u8; 32], [u8; 32]) {
+    let mut res = [0; 64];
+    pbkdf2::pbkdf2::<HmacSha256>(password.as_bytes(), salt, iterations, &mut res);
+
+    let mut enc_key = [0; 32];
+    let mut mac_key = [0; 32];
+    enc_key.copy_from_slice(&res[..32]);
+    mac_key.copy_from_slice(&res[32..]);
+    (enc_key, mac_key)
+}
+
+fn compute_mac(mac_key: &[u8; 32], iv: &[u8; 16], ciphertext: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(mac_key).unwrap();
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        use serde::de::Error;
+
+        let data = String::deserialize(deserializer)?;
+        hex::decode(data).map_err(Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let data = b"validator keys";
+        let encrypted = EncryptedData::encrypt(data, "password");
+        assert_eq!(encrypted.decrypt("password").unwrap(), data);
+
+        // Check serialization
+        let encrypted: EncryptedData =
+            serde_json::from_str(&serde_json::to_string(&encrypted).unwrap()).unwrap();
+        assert_eq!(encrypted.decrypt("password").unwrap(), data);
+    }
+
+    #[test]
+    fn wrong_password() {
+        let encrypted = EncryptedData::encrypt(b"validator keys", "password");
+        assert!(encrypted.decrypt("other password").is_err());
+    }
+
+    #[test]
+    fn tampered_data() {
+        let mut encrypted = EncryptedData::encrypt(b"validator keys", "password");
+        encrypted.ciphertext[0] ^= 1;
+        assert!(encrypted.decrypt("password").is_err());
+    }
+
+    #[test]
+    fn iterations_out_of_range() {
+        let mut encrypted = EncryptedData::encrypt(b"validator keys", "password");
+        encrypted.iterations = u32::MAX;
+        assert!(encrypted.decrypt("password").is_err());
+    }
+}
@@ -4,7 +4,10 @@ use anyhow::Result;
 use hmac::digest::Digest;
 use rand::Rng;
 
+pub use self::keystore::EncryptedData;
+
 mod bip39;
+mod keystore;
 mod legacy;
 
 const LANGUAGE: ::bip39::Language = ::bip39::Language::English;
@@ -1,5 +1,9 @@
 use std::path::{Path, PathBuf};
 
+use anyhow::Result;
+
+use crate::util::FileLock;
+
 const ENV: &str = "NODEKEEPER_ROOT";
 
 pub const VALIDATOR_SERVICE: &str = "validator";
@@ -8,6 +12,15 @@ pub const VALIDATOR_EXPORTER_SERVICE: &str = "validator-exporter";
 
 pub struct ProjectDirs {
     pub app_config: PathBuf,
+    pub election_journal: PathBuf,
+    pub election_keys: PathBuf,
+    pub wallet_lock: PathBuf,
+    pub message_queue: PathBuf,
+    pub control_socket: PathBuf,
+    pub signer_socket: PathBuf,
+    pub spending_ledger: PathBuf,
+    pub depool_rewards: PathBuf,
+    pub offline_messages: PathBuf,
     pub node_config: PathBuf,
     pub node_log_config: PathBuf,
     pub global_config: PathBuf,
@@ -52,6 +65,15 @@ impl ProjectDirs {
 
         Self {
             app_config: root.join("config.toml"),
+            election_journal: root.join("elections.jsonl"),
+            election_keys: root.join("election_keys.json"),
+            wallet_lock: root.join("wallet.lock"),
+            message_queue: root.join("message_queue.json"),
+            control_socket: root.join("validator.sock"),
+            signer_socket: root.join("signer.sock"),
+            spending_ledger: root.join("spending.json"),
+            depool_rewards: root.join("depool_rewards.json"),
+            offline_messages: root.join("offline"),
             node_config: node_configs_dir.join("config.json"),
             node_log_config: node_configs_dir.join("log_cfg.yml"),
             global_config: node_configs_dir.join("global-config.json"),
@@ -70,6 +92,24 @@ impl ProjectDirs {
         }
     }
 
+    /// Locks the validator wallet or fails if it is used by another process
+    pub fn try_lock_wallet(&self, holder: &str) -> Result<FileLock> {
+        match FileLock::try_acquire(&self.wallet_lock, holder)? {
+            Some(lock) => Ok(lock),
+            None => anyhow::bail!(
+                "validator wallet is used by another process ({}), try again later",
+                FileLock::current_holder(&self.wallet_lock)
+                    .as_deref()
+                    .unwrap_or("unknown")
+            ),
+        }
+    }
+
+    /// Waits until the validator wallet is not used by other processes
+    pub async fn lock_wallet(&self, holder: &str) -> Result<FileLock> {
+        FileLock::acquire(&self.wallet_lock, holder).await
+    }
+
     pub fn default_root_dir() -> PathBuf {
         if let Ok(path) = std::env::var(ENV) {
             PathBuf::from(path)
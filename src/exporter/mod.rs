@@ -9,6 +9,8 @@ pub use self::stdout_target::StdoutExporterTarget;
 use crate::config::{AppConfig, AppConfigValidator, DePoolType};
 use crate::dirs::ProjectDirs;
 use crate::network::{NodeStats, NodeTcpRpc, ValidatorSetEntry};
+use crate::validator::journal::{self, ElectionJournal, ElectionOutcome};
+use crate::validator::rewards::{self, RewardHistory, RoundReward};
 
 mod file_target;
 mod http_target;
@@ -61,10 +63,38 @@ impl Exporter {
 
         tracing::debug!("collected node stats");
 
+        let (last_outcome, last_maintenance_skip) =
+            match ElectionJournal::new(&self.dirs.election_journal).load() {
+                Ok(entries) => (
+                    journal::last_outcome(&entries),
+                    journal::last_maintenance_skip(&entries),
+                ),
+                Err(e) => {
+                    tracing::warn!("failed to load election journal: {e:?}");
+                    (None, None)
+                }
+            };
+
+        let rewards = match &config.validator {
+            Some(AppConfigValidator::DePool(_)) => {
+                match RewardHistory::new(&self.dirs.depool_rewards).load() {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        tracing::warn!("failed to load DePool rewards history: {e:?}");
+                        Vec::new()
+                    }
+                }
+            }
+            _ => Vec::new(),
+        };
+
         let metrics = Metrics {
             collected_at,
             config,
             stats: &stats,
+            last_outcome,
+            last_maintenance_skip,
+            rewards: &rewards,
         };
         self.export(&metrics);
 
@@ -119,6 +149,9 @@ struct Metrics<'a> {
     collected_at: u32,
     config: &'a AppConfig,
     stats: &'a NodeStats,
+    last_outcome: Option<(u32, ElectionOutcome)>,
+    last_maintenance_skip: Option<u32>,
+    rewards: &'a [RoundReward],
 }
 
 impl std::fmt::Display for Metrics<'_> {
@@ -131,6 +164,26 @@ impl std::fmt::Display for Metrics<'_> {
 
         f.begin_metric("collected_at").value(self.collected_at)?;
 
+        if let Some((election_id, outcome)) = self.last_outcome {
+            f.begin_metric("validator_last_reconciled_election")
+                .value(election_id)?;
+            f.begin_metric("validator_election_outcome")
+                .label("outcome", outcome.as_str())
+                .value(1)?;
+        }
+
+        let in_maintenance = self
+            .config
+            .maintenance
+            .iter()
+            .any(|window| window.contains(self.collected_at));
+        f.begin_metric("validator_maintenance_active")
+            .value(in_maintenance as u8)?;
+        if let Some(election_id) = self.last_maintenance_skip {
+            f.begin_metric("validator_maintenance_skipped_election")
+                .value(election_id)?;
+        }
+
         let stats = match self.stats {
             NodeStats::NotReady(sync_status) => {
                 return f
@@ -214,6 +267,51 @@ impl std::fmt::Display for Metrics<'_> {
                         .label("validator", &depool.owner)
                         .label("depool", &depool.depool)
                         .value(depool.depool_type.into_u8())?;
+
+                    const DEPOOL_LABEL: &str = "depool";
+
+                    let rewards = self
+                        .rewards
+                        .iter()
+                        .filter(|entry| entry.depool == depool.depool)
+                        .collect::<Vec<_>>();
+
+                    f.begin_metric("validator_depool_rounds_recorded")
+                        .label(DEPOOL_LABEL, &depool.depool)
+                        .value(rewards.len())?;
+                    f.begin_metric("validator_depool_participant_reward_total")
+                        .label(DEPOOL_LABEL, &depool.depool)
+                        .value(
+                            rewards
+                                .iter()
+                                .map(|entry| entry.participant_reward as u128)
+                                .sum::<u128>(),
+                        )?;
+                    if let Some(average) =
+                        rewards::average_annualized_yield(rewards.iter().copied())
+                    {
+                        f.begin_metric("validator_depool_average_annualized_yield")
+                            .label(DEPOOL_LABEL, &depool.depool)
+                            .value(average)?;
+                    }
+
+                    if let Some(last) = rewards.last() {
+                        f.begin_metric("validator_depool_last_round")
+                            .label(DEPOOL_LABEL, &depool.depool)
+                            .value(last.id)?;
+                        f.begin_metric("validator_depool_last_round_reward")
+                            .label(DEPOOL_LABEL, &depool.depool)
+                            .value(last.total_reward())?;
+                        f.begin_metric("validator_depool_last_round_participant_reward")
+                            .label(DEPOOL_LABEL, &depool.depool)
+                            .value(last.participant_reward)?;
+                        f.begin_metric("validator_depool_last_round_validator_fee_share")
+                            .label(DEPOOL_LABEL, &depool.depool)
+                            .value(last.validator_fee_share())?;
+                        f.begin_metric("validator_depool_last_round_annualized_yield")
+                            .label(DEPOOL_LABEL, &depool.depool)
+                            .value(last.annualized_yield())?;
+                    }
                 }
             }
         } else {
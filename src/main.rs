@@ -41,6 +41,7 @@ mod defaults;
 mod dirs;
 mod exporter;
 mod network;
+mod notifier;
 mod util;
 mod validator;
 
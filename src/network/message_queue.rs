@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use broxus_util::{serde_hex_array, serde_string};
+use serde::{Deserialize, Serialize};
+
+use crate::util::write_file_atomic;
+
+/// External messages which were broadcasted but not yet resolved
+///
+/// Entries are persisted before sending to detect messages which were
+/// left in flight by the crashed process.
+pub struct MessageQueue {
+    path: PathBuf,
+    lock: parking_lot::Mutex<()>,
+}
+
+impl MessageQueue {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: Default::default(),
+        }
+    }
+
+    pub fn load(&self) -> Result<Vec<QueuedMessage>> {
+        let _guard = self.lock.lock();
+        self.read()
+    }
+
+    pub fn push(&self, message: QueuedMessage) -> Result<()> {
+        let _guard = self.lock.lock();
+        let mut entries = self.read()?;
+        entries.retain(|item| item.hash != message.hash);
+        entries.push(message);
+        self.write(&entries)
+    }
+
+    pub fn remove(&self, hash: &[u8; 32]) -> Result<()> {
+        let _guard = self.lock.lock();
+        let mut entries = self.read()?;
+        let len = entries.len();
+        entries.retain(|item| &item.hash != hash);
+        if entries.len() != len {
+            self.write(&entries)?;
+        }
+        Ok(())
+    }
+
+    fn read(&self) -> Result<Vec<QueuedMessage>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(data) => serde_json::from_str(&data).context("invalid message queue"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e).context("failed to read message queue"),
+        }
+    }
+
+    fn write(&self, entries: &[QueuedMessage]) -> Result<()> {
+        let data =
+            serde_json::to_string_pretty(entries).context("failed to serialize message queue")?;
+        write_file_atomic(&self.path, data).context("failed to save message queue")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    #[serde(with = "serde_hex_array")]
+    pub hash: [u8; 32],
+    #[serde(with = "serde_string")]
+    pub dst: ton_block::MsgAddressInt,
+    pub created_at: u32,
+    pub expire_at: u32,
+    pub purpose: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn make_queue(name: &str) -> MessageQueue {
+        let path = std::env::temp_dir().join(format!(
+            "nodekeeper-queue-{}-{name}.json",
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+        MessageQueue::new(path)
+    }
+
+    fn message(hash: u8, purpose: &str) -> QueuedMessage {
+        QueuedMessage {
+            hash: [hash; 32],
+            dst: ton_block::MsgAddressInt::from_str(&format!("-1:{}", hex::encode([3; 32])))
+                .unwrap(),
+            created_at: 100,
+            expire_at: 160,
+            purpose: purpose.to_owned(),
+        }
+    }
+
+    #[test]
+    fn empty_queue() {
+        let queue = make_queue("empty");
+        assert!(queue.load().unwrap().is_empty());
+        queue.remove(&[1; 32]).unwrap();
+    }
+
+    #[test]
+    fn push_and_remove() {
+        let queue = make_queue("push");
+        queue.push(message(1, "first")).unwrap();
+        queue.push(message(2, "second")).unwrap();
+
+        // Same message replaces the previous entry
+        queue.push(message(1, "resent")).unwrap();
+
+        let entries = queue.load().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].hash, [2; 32]);
+        assert_eq!(entries[1].purpose, "resent");
+
+        queue.remove(&[2; 32]).unwrap();
+        queue.remove(&[4; 32]).unwrap();
+        let entries = queue.load().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].hash, [1; 32]);
+    }
+
+    #[test]
+    fn queue_is_persistent() {
+        let queue = make_queue("persistent");
+        queue.push(message(1, "stake")).unwrap();
+
+        let entries = MessageQueue::new(&queue.path).load().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].dst, message(1, "stake").dst);
+        assert_eq!(entries[0].expire_at, 160);
+    }
+}
@@ -1,7 +1,9 @@
+pub use self::message_queue::{MessageQueue, QueuedMessage};
 pub use self::node_tcp_rpc::*;
 pub use self::node_udp_rpc::NodeUdpRpc;
-pub use self::subscription::Subscription;
+pub use self::subscription::{ReconciledMessage, Subscription, TransactionsRx};
 
+mod message_queue;
 mod node_tcp_rpc;
 mod node_udp_rpc;
 mod subscription;
@@ -12,6 +12,7 @@ use tokio::sync::{mpsc, oneshot, Notify};
 use tokio_util::sync::{CancellationToken, DropGuard};
 use ton_block::{Deserializable, Serializable};
 
+use super::message_queue::{MessageQueue, QueuedMessage};
 use super::node_tcp_rpc::{ConfigWithId, NodeTcpRpc};
 use super::node_udp_rpc::NodeUdpRpc;
 use crate::util::{split_address, BlockStuff, FxDashMap, TransactionWithHash};
@@ -26,6 +27,7 @@ pub struct Subscription {
     mc_subscriptions: AccountSubscriptions,
     sc_subscriptions: AccountSubscriptions,
     global_id: tokio::sync::Mutex<Option<i32>>,
+    message_queue: ArcSwapOption<MessageQueue>,
     _cancellation: DropGuard,
 }
 
@@ -43,6 +45,7 @@ impl Subscription {
             mc_subscriptions: Default::default(),
             sc_subscriptions: Default::default(),
             global_id: Default::default(),
+            message_queue: Default::default(),
             _cancellation: cancellation.clone().drop_guard(),
         });
 
@@ -73,6 +76,11 @@ impl Subscription {
         Ok(())
     }
 
+    /// Persists all sent external messages into the specified queue
+    pub fn set_message_queue(&self, message_queue: Arc<MessageQueue>) {
+        self.message_queue.store(Some(message_queue));
+    }
+
     pub fn tcp_rpc(&self) -> &NodeTcpRpc {
         &self.node_tcp_rpc
     }
@@ -116,18 +124,31 @@ impl Subscription {
         }
     }
 
-    pub async fn send_message_with_retires<F>(&self, mut f: F) -> Result<TransactionWithHash>
+    pub async fn send_message_with_retires<F>(
+        &self,
+        purpose: &str,
+        mut f: F,
+    ) -> Result<TransactionWithHash>
     where
         F: FnMut(u32, Option<i32>) -> Result<(ton_block::Message, u32)>,
     {
+        const MAX_ATTEMPTS: usize = 5;
+
         let signature_id = self.get_signature_id().await?;
 
         let timeout = 60;
+        let mut attempt = 0;
         loop {
             let (message, expire_at) = f(timeout, signature_id)?;
-            if let Some(tx) = self.send_message(&message, expire_at).await? {
+            if let Some(tx) = self.send_message(&message, expire_at, purpose).await? {
                 break Ok(tx);
             }
+
+            attempt += 1;
+            if attempt >= MAX_ATTEMPTS {
+                anyhow::bail!("{purpose}: message expired after {attempt} attempts");
+            }
+            tracing::warn!(purpose, attempt, "retrying expired external message");
         }
     }
 
@@ -135,6 +156,7 @@ impl Subscription {
         &self,
         message: &ton_block::Message,
         expire_at: u32,
+        purpose: &str,
     ) -> Result<Option<TransactionWithHash>> {
         // Prepare dst address
         let raw_dst = match message.ext_in_header() {
@@ -185,8 +207,30 @@ impl Subscription {
         // Wait until subscription loop was definitely started
         subscription_loop_works.await;
 
+        // Persist the message before sending
+        let message_queue = self.message_queue.load_full();
+        let res = match &message_queue {
+            Some(queue) => queue
+                .push(QueuedMessage {
+                    hash: msg_hash.inner(),
+                    dst: raw_dst.clone(),
+                    created_at: broxus_util::now(),
+                    expire_at,
+                    purpose: purpose.to_owned(),
+                })
+                .context("failed to persist external message"),
+            None => Ok(()),
+        };
+
         // Send the message
-        if let Err(e) = self.node_tcp_rpc.send_message(data).await {
+        let res = match res {
+            Ok(()) => self.node_tcp_rpc.send_message(data).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            // NOTE: the queued message is left as is because it could still
+            // be broadcasted, it will be resolved during the next reconciliation
+
             // Remove pending message from the map before returning an error
             match subscriptions.entry(dst) {
                 dashmap::mapref::entry::Entry::Occupied(mut entry) => {
@@ -208,10 +252,13 @@ impl Subscription {
             };
             return Err(e);
         }
-        tracing::debug!(dst = %raw_dst, ?msg_hash, "external message broadcasted");
+        tracing::debug!(dst = %raw_dst, ?msg_hash, purpose, "external message broadcasted");
 
         // Wait for the message execution
         let tx = rx.await?;
+        if let Some(queue) = &message_queue {
+            remove_queued_message(queue, &msg_hash);
+        }
         match &tx {
             Some(tx) => {
                 tracing::debug!(
@@ -233,6 +280,146 @@ impl Subscription {
         Ok(tx)
     }
 
+    /// Resolves external messages which were left in the queue by the previous process.
+    ///
+    /// Waits until each message expires and then searches for its transaction
+    /// among the last transactions of the destination account. Messages which
+    /// can't be resolved are kept until the grace period after their expiration.
+    pub async fn reconcile_message_queue(&self) -> Vec<ReconciledMessage> {
+        const GRACE_PERIOD: u32 = 3600;
+
+        let mut reconciled = Vec::new();
+
+        let Some(queue) = self.message_queue.load_full() else {
+            return reconciled;
+        };
+        let messages = match queue.load() {
+            Ok(messages) => messages,
+            Err(e) => {
+                tracing::error!("failed to load message queue: {e:?}");
+                return reconciled;
+            }
+        };
+
+        for message in messages {
+            let msg_hash = ton_types::UInt256::from(message.hash);
+
+            // Message can still be delivered until it expires
+            let now = broxus_util::now();
+            if message.expire_at >= now {
+                tracing::info!(
+                    dst = %message.dst,
+                    ?msg_hash,
+                    purpose = message.purpose,
+                    "waiting for the pending external message to expire"
+                );
+                let delay = (message.expire_at - now + 1) as u64;
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+            }
+
+            let tx = match self
+                .find_transaction(&message.dst, &msg_hash, message.created_at)
+                .await
+            {
+                Ok(tx) => tx,
+                Err(e) if broxus_util::now() > message.expire_at.saturating_add(GRACE_PERIOD) => {
+                    tracing::error!(
+                        dst = %message.dst,
+                        ?msg_hash,
+                        purpose = message.purpose,
+                        "dropping unresolved pending external message: {e:?}"
+                    );
+                    remove_queued_message(&queue, &msg_hash);
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        dst = %message.dst,
+                        ?msg_hash,
+                        purpose = message.purpose,
+                        "failed to reconcile pending external message: {e:?}"
+                    );
+                    continue;
+                }
+            };
+
+            match &tx {
+                Some(tx) => tracing::info!(
+                    dst = %message.dst,
+                    ?msg_hash,
+                    tx_hash = ?tx.hash,
+                    purpose = message.purpose,
+                    "pending external message was delivered"
+                ),
+                None => tracing::warn!(
+                    dst = %message.dst,
+                    ?msg_hash,
+                    purpose = message.purpose,
+                    "pending external message was not delivered"
+                ),
+            }
+
+            remove_queued_message(&queue, &msg_hash);
+            reconciled.push(ReconciledMessage {
+                tx_hash: tx.map(|tx| *tx.hash.as_array()),
+                message,
+            });
+        }
+
+        reconciled
+    }
+
+    /// Searches for the account transaction with the specified inbound message,
+    /// walks back from the latest known block until `since` is reached
+    pub async fn find_transaction(
+        &self,
+        address: &ton_block::MsgAddressInt,
+        msg_hash: &ton_types::UInt256,
+        since: u32,
+    ) -> Result<Option<TransactionWithHash>> {
+        const MAX_BLOCKS: usize = 10000;
+
+        let (workchain, account) = split_address(address)?;
+        let account_id = address.address();
+
+        // Find the latest block of the account shard
+        let last_mc_block = self.update_last_mc_block().await?;
+        let mut block_id = if workchain == ton_block::MASTERCHAIN_ID {
+            last_mc_block.data.id().clone()
+        } else {
+            let mut shard_block_id = None;
+            for (shard, id) in last_mc_block.data.shard_blocks()? {
+                if shard.workchain_id() == workchain
+                    && shard.contains_account(account_id.clone())?
+                {
+                    shard_block_id = Some(id);
+                    break;
+                }
+            }
+            shard_block_id.context("account shard not found")?
+        };
+
+        for _ in 0..MAX_BLOCKS {
+            let block = self.node_udp_rpc.get_block(&block_id).await?;
+            if let Some(tx) = find_block_transaction(block.block(), &account, msg_hash)? {
+                return Ok(Some(tx));
+            }
+
+            let info = block.read_brief_info()?;
+            if info.gen_utime < since {
+                return Ok(None);
+            }
+
+            // Follow the account shard after merges
+            block_id = match info.prev2 {
+                Some(prev2) if prev2.shard().contains_account(account_id.clone())? => prev2,
+                _ => info.prev1,
+            };
+        }
+
+        anyhow::bail!("transaction not found in the last {MAX_BLOCKS} blocks")
+    }
+
     pub fn subscribe(&self, address: &ton_block::MsgAddressInt) -> TransactionsRx {
         let (tx, rx) = mpsc::unbounded_channel();
         let subscriptions = if address.workchain_id() == ton_block::MASTERCHAIN_ID {
@@ -488,6 +675,56 @@ impl Subscription {
     }
 }
 
+/// External message from the previous process with the resolved delivery status
+pub struct ReconciledMessage {
+    pub message: QueuedMessage,
+    pub tx_hash: Option<[u8; 32]>,
+}
+
+fn remove_queued_message(queue: &MessageQueue, msg_hash: &ton_types::UInt256) {
+    if let Err(e) = queue.remove(msg_hash.as_slice()) {
+        tracing::error!(?msg_hash, "failed to update message queue: {e:?}");
+    }
+}
+
+fn find_block_transaction(
+    block: &ton_block::Block,
+    account: &ton_types::UInt256,
+    msg_hash: &ton_types::UInt256,
+) -> Result<Option<TransactionWithHash>> {
+    use ton_block::HashmapAugType;
+
+    let mut result = None;
+
+    let extra = block.read_extra()?;
+    let account_blocks = extra.read_account_blocks()?;
+    account_blocks.iterate_with_keys(|address, account_block| {
+        if &address != account {
+            return Ok(true);
+        }
+
+        account_block
+            .transactions()
+            .iterate_slices_with_keys(|_, tx| {
+                let cell = tx.reference(0)?;
+                let hash = cell.repr_hash();
+                let data = ton_block::Transaction::construct_from_cell(cell)?;
+
+                match &data.in_msg {
+                    Some(in_msg) if &in_msg.hash() == msg_hash => {
+                        result = Some(TransactionWithHash { hash, data });
+                        Ok(false)
+                    }
+                    _ => Ok(true),
+                }
+            })?;
+
+        Ok(false)
+    })?;
+
+    Ok(result)
+}
+
 #[derive(Default)]
 struct AccountSubscription {
     pending_messages: FxHashMap<ton_types::UInt256, PendingMessage>,
@@ -574,3 +811,76 @@ fn requires_signature_id(capabilities: u64) -> bool {
 
     capabilities & CAP_WITH_SIGNATURE_ID != 0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_message(seed: u8) -> ton_block::Message {
+        let mut message =
+            ton_block::Message::with_ext_in_header(ton_block::ExternalInboundMessageHeader {
+                dst: ton_block::MsgAddressInt::with_standart(
+                    None,
+                    0,
+                    ton_types::UInt256::from([1; 32]).into(),
+                )
+                .unwrap(),
+                ..Default::default()
+            });
+        let mut body = ton_types::BuilderData::new();
+        body.append_u8(seed).unwrap();
+        message.set_body(ton_types::SliceData::load_builder(body).unwrap());
+        message
+    }
+
+    fn make_block(account: &ton_types::UInt256, in_msg: &ton_block::Message) -> ton_block::Block {
+        let mut tx = ton_block::Transaction::with_address_and_status(
+            account.clone().into(),
+            ton_block::AccountStatus::AccStateActive,
+        );
+        tx.write_in_msg(Some(in_msg)).unwrap();
+        let cell = tx.serialize().unwrap();
+
+        let mut account_blocks = ton_block::ShardAccountBlocks::default();
+        account_blocks
+            .add_serialized_transaction(&tx, &cell)
+            .unwrap();
+
+        let mut extra = ton_block::BlockExtra::default();
+        extra.write_account_blocks(&account_blocks).unwrap();
+
+        ton_block::Block::with_params(
+            0,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            extra,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn finds_transaction_by_inbound_message() {
+        let account = ton_types::UInt256::from([1; 32]);
+        let message = make_message(1);
+        let msg_hash = message.serialize().unwrap().repr_hash();
+        let block = make_block(&account, &message);
+
+        let tx = find_block_transaction(&block, &account, &msg_hash)
+            .unwrap()
+            .unwrap();
+        assert_eq!(tx.data.in_msg.unwrap().hash(), msg_hash);
+
+        // Other message of the same account
+        let other_hash = make_message(2).serialize().unwrap().repr_hash();
+        assert!(find_block_transaction(&block, &account, &other_hash)
+            .unwrap()
+            .is_none());
+
+        // Same message hash for another account
+        let other_account = ton_types::UInt256::from([2; 32]);
+        assert!(find_block_transaction(&block, &other_account, &msg_hash)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,494 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use broxus_util::serde_string;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+
+use crate::config::{AppConfigNotificationSink, NotificationKind, NotificationTarget};
+use crate::util::Tokens;
+use crate::validator::journal::ElectionOutcome;
+
+/// Sends validator events to the configured sinks
+pub struct Notifier {
+    client: reqwest::Client,
+    state: parking_lot::Mutex<NotifierState>,
+}
+
+#[derive(Default)]
+struct NotifierState {
+    sinks: Vec<AppConfigNotificationSink>,
+    last_sent: FxHashMap<(usize, NotificationKind), Instant>,
+    reported: FxHashSet<String>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            state: Default::default(),
+        }
+    }
+}
+
+impl Notifier {
+    /// Replaces sinks if they were changed in the config
+    pub fn set_sinks(&self, sinks: &[AppConfigNotificationSink]) {
+        let mut state = self.state.lock();
+        if state.sinks != sinks {
+            state.sinks = sinks.to_vec();
+            state.last_sent.clear();
+        }
+    }
+
+    /// Sends the notification in the background
+    pub fn notify(&self, notification: Notification) {
+        let kind = notification.kind();
+        let now = Instant::now();
+
+        let targets = {
+            let mut state = self.state.lock();
+            let state = &mut *state;
+
+            let mut targets = Vec::new();
+            for (i, sink) in state.sinks.iter().enumerate() {
+                if !sink.accepts(kind) {
+                    continue;
+                }
+
+                let min_interval = Duration::from_secs(sink.min_interval_sec as u64);
+                match state.last_sent.get(&(i, kind)) {
+                    Some(last_sent) if now.duration_since(*last_sent) < min_interval => {
+                        tracing::debug!(?kind, "notification rate limited");
+                    }
+                    _ => {
+                        state.last_sent.insert((i, kind), now);
+                        targets.push(sink.target.clone());
+                    }
+                }
+            }
+            targets
+        };
+
+        if targets.is_empty() {
+            return;
+        }
+
+        let message = NotificationMessage {
+            timestamp: broxus_util::now(),
+            text: notification.to_string(),
+            notification,
+        };
+
+        for target in targets {
+            let client = self.client.clone();
+            let message = message.clone();
+            tokio::spawn(async move {
+                if let Err(e) = send(&client, &target, &message).await {
+                    tracing::warn!("failed to send notification: {e:?}");
+                }
+            });
+        }
+    }
+
+    /// Same as [`notify`], but sends the notification with the specified key only once
+    ///
+    /// [`notify`]: Self::notify
+    pub fn notify_once(&self, key: String, notification: Notification) {
+        if self.state.lock().reported.insert(key) {
+            self.notify(notification);
+        }
+    }
+
+    /// Forgets keys of [`notify_once`] for which `f` returns `false`
+    ///
+    /// [`notify_once`]: Self::notify_once
+    pub fn retain_reported<F: FnMut(&String) -> bool>(&self, f: F) {
+        self.state.lock().reported.retain(f);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    StakeSent {
+        election_id: u32,
+        #[serde(with = "serde_string")]
+        participant: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        stake: u128,
+    },
+    StakeRecovered {
+        election_id: u32,
+        #[serde(with = "serde_string")]
+        amount: u128,
+    },
+    Elected {
+        election_id: u32,
+    },
+    NotElected {
+        election_id: u32,
+        outcome: ElectionOutcome,
+    },
+    DepoolRoundFailed {
+        #[serde(with = "serde_string")]
+        depool: ton_block::MsgAddressInt,
+        round_id: u64,
+        reason: String,
+    },
+    BalanceWaiting {
+        #[serde(with = "serde_string")]
+        address: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        balance: u128,
+        #[serde(with = "serde_string")]
+        target: u128,
+    },
+    NodeOutOfSync {
+        status: String,
+    },
+    SpendingBlocked {
+        #[serde(with = "serde_string")]
+        destination: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        amount: u128,
+        reason: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl Notification {
+    pub fn kind(&self) -> NotificationKind {
+        match self {
+            Self::StakeSent { .. } => NotificationKind::StakeSent,
+            Self::StakeRecovered { .. } => NotificationKind::StakeRecovered,
+            Self::Elected { .. } => NotificationKind::Elected,
+            Self::NotElected { .. } => NotificationKind::NotElected,
+            Self::DepoolRoundFailed { .. } => NotificationKind::DepoolRoundFailed,
+            Self::BalanceWaiting { .. } => NotificationKind::BalanceWaiting,
+            Self::NodeOutOfSync { .. } => NotificationKind::NodeOutOfSync,
+            Self::SpendingBlocked { .. } => NotificationKind::SpendingBlocked,
+            Self::Error { .. } => NotificationKind::Error,
+        }
+    }
+}
+
+impl std::fmt::Display for Notification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StakeSent {
+                election_id,
+                participant,
+                stake,
+            } => write!(
+                f,
+                "Stake {} sent from {participant} to the elections {election_id}",
+                Tokens(*stake)
+            ),
+            Self::StakeRecovered {
+                election_id,
+                amount,
+            } => write!(
+                f,
+                "Stake {} recovered before the elections {election_id}",
+                Tokens(*amount)
+            ),
+            Self::Elected { election_id } => {
+                write!(f, "Validator was elected in the elections {election_id}")
+            }
+            Self::NotElected {
+                election_id,
+                outcome,
+            } => write!(
+                f,
+                "Validator was not elected in the elections {election_id} ({})",
+                outcome.as_str()
+            ),
+            Self::DepoolRoundFailed {
+                depool,
+                round_id,
+                reason,
+            } => write!(f, "DePool {depool} round {round_id} completed: {reason}"),
+            Self::BalanceWaiting {
+                address,
+                balance,
+                target,
+            } => write!(
+                f,
+                "Wallet {address} balance {} is still less than {}",
+                Tokens(*balance),
+                Tokens(*target)
+            ),
+            Self::NodeOutOfSync { status } => write!(f, "Node is not synced: {status}"),
+            Self::SpendingBlocked {
+                destination,
+                amount,
+                reason,
+            } => write!(
+                f,
+                "Transfer of {} to {destination} was blocked by the spending policy: {reason}",
+                Tokens(*amount)
+            ),
+            Self::Error { message } => write!(f, "Validation error: {message}"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct NotificationMessage {
+    timestamp: u32,
+    text: String,
+    #[serde(flatten)]
+    notification: Notification,
+}
+
+async fn send(
+    client: &reqwest::Client,
+    target: &NotificationTarget,
+    message: &NotificationMessage,
+) -> Result<()> {
+    match target {
+        NotificationTarget::Webhook { url } => post_json(client, url, message).await,
+        NotificationTarget::Telegram {
+            bot_token,
+            chat_id,
+            api_url,
+        } => {
+            let url = format!(
+                "{}/bot{bot_token}/sendMessage",
+                api_url.trim_end_matches('/')
+            );
+            let body = serde_json::json!({
+                "chat_id": chat_id,
+                "text": message.text,
+            });
+            post_json(client, &url, &body).await
+        }
+        NotificationTarget::Slack { url } => {
+            let body = serde_json::json!({
+                "text": message.text,
+            });
+            post_json(client, url, &body).await
+        }
+    }
+}
+
+async fn post_json<T: Serialize>(client: &reqwest::Client, url: &str, body: &T) -> Result<()> {
+    let body = serde_json::to_vec(body).context("failed to serialize notification")?;
+
+    // NOTE: urls are removed from errors because they can contain tokens
+    client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(reqwest::Error::without_url)
+        .context("failed to send request")?
+        .error_for_status()
+        .map_err(reqwest::Error::without_url)
+        .context("notification was rejected")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    type Request = (String, serde_json::Value);
+
+    /// Starts an HTTP server which accepts all requests
+    async fn serve() -> (String, mpsc::UnboundedReceiver<Request>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    let path = line.split_whitespace().nth(1).unwrap().to_owned();
+
+                    let mut content_length = 0;
+                    loop {
+                        line.clear();
+                        stream.read_line(&mut line).await.unwrap();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+
+                    let mut body = vec![0; content_length];
+                    stream.read_exact(&mut body).await.unwrap();
+                    stream
+                        .get_mut()
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                        )
+                        .await
+                        .unwrap();
+
+                    tx.send((path, serde_json::from_slice(&body).unwrap())).ok();
+                });
+            }
+        });
+
+        (url, rx)
+    }
+
+    async fn receive(rx: &mut mpsc::UnboundedReceiver<Request>, count: usize) -> Vec<Request> {
+        let mut requests = Vec::new();
+        for _ in 0..count {
+            let request = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+                .await
+                .expect("request was not sent")
+                .unwrap();
+            requests.push(request);
+        }
+
+        // No other requests are expected
+        let other = tokio::time::timeout(Duration::from_millis(300), rx.recv()).await;
+        assert!(other.is_err(), "unexpected request: {other:?}");
+
+        requests.sort_by(|(a, _), (b, _)| a.cmp(b));
+        requests
+    }
+
+    fn sink(
+        events: Vec<NotificationKind>,
+        min_interval_sec: u32,
+        url: String,
+    ) -> AppConfigNotificationSink {
+        AppConfigNotificationSink {
+            events,
+            min_interval_sec,
+            target: NotificationTarget::Webhook { url },
+        }
+    }
+
+    #[tokio::test]
+    async fn request_bodies() {
+        let (url, mut rx) = serve().await;
+
+        let notifier = Notifier::default();
+        notifier.set_sinks(&[
+            sink(Vec::new(), 0, format!("{url}/webhook")),
+            AppConfigNotificationSink {
+                events: Vec::new(),
+                min_interval_sec: 0,
+                target: NotificationTarget::Telegram {
+                    bot_token: "123:token".to_owned(),
+                    chat_id: "-42".to_owned(),
+                    api_url: format!("{url}/"),
+                },
+            },
+            AppConfigNotificationSink {
+                events: Vec::new(),
+                min_interval_sec: 0,
+                target: NotificationTarget::Slack {
+                    url: format!("{url}/slack"),
+                },
+            },
+        ]);
+
+        notifier.notify(Notification::Elected { election_id: 100 });
+        let text = "Validator was elected in the elections 100";
+
+        let requests = receive(&mut rx, 3).await;
+        assert_eq!(requests[0].0, "/bot123:token/sendMessage");
+        assert_eq!(
+            requests[0].1,
+            serde_json::json!({ "chat_id": "-42", "text": text })
+        );
+
+        assert_eq!(requests[1].0, "/slack");
+        assert_eq!(requests[1].1, serde_json::json!({ "text": text }));
+
+        let webhook = &requests[2];
+        assert_eq!(webhook.0, "/webhook");
+        assert_eq!(webhook.1["event"], "elected");
+        assert_eq!(webhook.1["election_id"], 100);
+        assert_eq!(webhook.1["text"], text);
+        assert!(webhook.1["timestamp"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn filtered_and_rate_limited() {
+        let (url, mut rx) = serve().await;
+
+        let notifier = Notifier::default();
+        notifier.set_sinks(&[
+            sink(Vec::new(), 0, format!("{url}/all")),
+            sink(
+                vec![NotificationKind::Elected],
+                300,
+                format!("{url}/elected"),
+            ),
+        ]);
+
+        notifier.notify(Notification::Error {
+            message: "test".to_owned(),
+        });
+        notifier.notify(Notification::Elected { election_id: 100 });
+        notifier.notify(Notification::Elected { election_id: 200 });
+
+        let requests = receive(&mut rx, 4).await;
+        let paths = requests
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/all", "/all", "/all", "/elected"]);
+        assert_eq!(requests[3].1["election_id"], 100);
+
+        // Rate limits are reset with the new sinks
+        notifier.set_sinks(&[sink(
+            vec![NotificationKind::Elected],
+            300,
+            format!("{url}/new"),
+        )]);
+        notifier.notify(Notification::Elected { election_id: 300 });
+        let requests = receive(&mut rx, 1).await;
+        assert_eq!(requests[0].0, "/new");
+    }
+
+    #[tokio::test]
+    async fn reported_once() {
+        let (url, mut rx) = serve().await;
+
+        let notifier = Notifier::default();
+        notifier.set_sinks(&[sink(Vec::new(), 0, url)]);
+
+        let notification = || Notification::Error {
+            message: "test".to_owned(),
+        };
+        notifier.notify_once("a".to_owned(), notification());
+        notifier.notify_once("a".to_owned(), notification());
+        notifier.notify_once("b".to_owned(), notification());
+        receive(&mut rx, 2).await;
+
+        notifier.retain_reported(|key| key != "a");
+        assert_eq!(notifier.state.lock().reported.len(), 1);
+
+        notifier.notify_once("a".to_owned(), notification());
+        notifier.notify_once("b".to_owned(), notification());
+        receive(&mut rx, 1).await;
+    }
+}
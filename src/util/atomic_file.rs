@@ -0,0 +1,26 @@
+use std::io::Write;
+use std::path::Path;
+
+/// Replaces the file contents so that after a crash it is either old or new, but never partial
+pub fn write_file_atomic<P, D>(path: P, data: D) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+    D: AsRef<[u8]>,
+{
+    let path = path.as_ref();
+    let temp_path = path.with_extension("tmp");
+
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(data.as_ref())?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&temp_path, path)?;
+
+    // Persist the rename itself
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::File::open(dir)?.sync_all()
+}
@@ -11,8 +11,28 @@ use tokio_util::sync::CancellationToken;
 use ton_block::Deserializable;
 
 pub async fn exec(command: &mut Command) -> Result<()> {
-    let mut child = command.spawn()?;
+    let child = command.spawn()?;
+    wait_child(child).await
+}
+
+/// Same as [`exec`], but also writes the input into the stdin of the child process
+pub async fn exec_with_input(command: &mut Command, input: &[u8]) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut child = command.stdin(std::process::Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(input).await {
+            Ok(()) => {}
+            // Child process is allowed to ignore the input
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            Err(e) => return Err(e).context("failed to write child process input"),
+        }
+    }
+
+    wait_child(child).await
+}
 
+async fn wait_child(mut child: tokio::process::Child) -> Result<()> {
     let status = child
         .wait()
         .await
@@ -174,6 +194,106 @@ pub fn print_output<T: std::fmt::Display>(arg: T) {
     .unwrap()
 }
 
+pub fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    fn format_row<'a, I>(items: I, widths: &[usize]) -> String
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let line = items
+            .zip(widths)
+            .map(|(item, &width)| format!("{item:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        line.trim_end().to_owned()
+    }
+
+    let mut widths = header
+        .iter()
+        .map(|item| item.chars().count())
+        .collect::<Vec<_>>();
+    for row in rows {
+        for (width, item) in widths.iter_mut().zip(row) {
+            *width = std::cmp::max(*width, item.chars().count());
+        }
+    }
+
+    let mut stdout = std::io::stdout().lock();
+
+    let header = format_row(header.iter().copied(), &widths);
+    if is_terminal() {
+        writeln!(stdout, "{}", console::style(header).bold())
+    } else {
+        writeln!(stdout, "{header}")
+    }
+    .unwrap();
+
+    for row in rows {
+        writeln!(
+            stdout,
+            "{}",
+            format_row(row.iter().map(String::as_str), &widths)
+        )
+        .unwrap();
+    }
+}
+
+pub fn print_csv(header: &[&str], rows: &[Vec<String>]) {
+    fn format_row<'a, I>(items: I) -> String
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        items
+            .map(|item| {
+                if item.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+                    format!("\"{}\"", item.replace('"', "\"\""))
+                } else {
+                    item.to_owned()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    let mut stdout = std::io::stdout().lock();
+
+    writeln!(stdout, "{}", format_row(header.iter().copied())).unwrap();
+    for row in rows {
+        writeln!(stdout, "{}", format_row(row.iter().map(String::as_str))).unwrap();
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    Table,
+    Csv,
+}
+
+impl OutputFormat {
+    /// Prints rows as a table or as CSV
+    pub fn print_rows(self, header: &[&str], rows: &[Vec<String>]) {
+        match self {
+            Self::Csv => print_csv(header, rows),
+            _ => print_table(header, rows),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "table" => Ok(Self::Table),
+            "csv" => Ok(Self::Csv),
+            _ => Err(anyhow::anyhow!(
+                "unknown output format (expected `json`, `table` or `csv`)"
+            )),
+        }
+    }
+}
+
 pub fn print_error(text: impl std::fmt::Display) {
     if is_terminal() {
         eprintln!("{}", console::style(format!("✘ {text}")).red().bold());
@@ -0,0 +1,78 @@
+use std::io::{Seek, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+/// Exclusive advisory lock on a file which is shared between processes
+///
+/// Lock is released when the guard is dropped (or the process exits).
+pub struct FileLock {
+    file: std::fs::File,
+}
+
+impl FileLock {
+    /// Tries to acquire the lock without blocking. Returns `None` if the lock is busy.
+    pub fn try_acquire<P: AsRef<Path>>(path: P, holder: &str) -> Result<Option<Self>> {
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .context("failed to open lock file")?;
+
+        let res = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if res != 0 {
+            let e = std::io::Error::last_os_error();
+            return match e.raw_os_error() {
+                Some(libc::EWOULDBLOCK) => Ok(None),
+                _ => Err(e).context("failed to acquire file lock"),
+            };
+        }
+
+        // Describe the holder for other processes
+        file.set_len(0).context("failed to truncate lock file")?;
+        file.rewind().context("failed to rewind lock file")?;
+        writeln!(file, "{holder} (pid {})", std::process::id())
+            .context("failed to write lock file")?;
+
+        Ok(Some(Self { file }))
+    }
+
+    /// Waits until the lock is acquired
+    pub async fn acquire<P: AsRef<Path>>(path: P, holder: &str) -> Result<Self> {
+        const INTERVAL: Duration = Duration::from_secs(1);
+
+        let path = path.as_ref();
+        let mut first = true;
+        loop {
+            if let Some(lock) = Self::try_acquire(path, holder)? {
+                break Ok(lock);
+            }
+
+            if std::mem::take(&mut first) {
+                tracing::info!(
+                    holder = Self::current_holder(path).as_deref().unwrap_or("unknown"),
+                    "waiting for the lock {}",
+                    path.display()
+                );
+            }
+            tokio::time::sleep(INTERVAL).await;
+        }
+    }
+
+    /// Returns the description of the last lock holder
+    pub fn current_holder<P: AsRef<Path>>(path: P) -> Option<String> {
+        let holder = std::fs::read_to_string(path).ok()?;
+        let holder = holder.trim();
+        (!holder.is_empty()).then(|| holder.to_owned())
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
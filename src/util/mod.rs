@@ -2,13 +2,17 @@ use std::hash::BuildHasherDefault;
 
 use dashmap::DashMap;
 
+pub use self::atomic_file::write_file_atomic;
 pub use self::block_stuff::*;
 pub use self::cli::*;
+pub use self::file_lock::FileLock;
 pub use self::serde::*;
 pub use self::transaction::*;
 
+mod atomic_file;
 mod block_stuff;
 mod cli;
+mod file_lock;
 mod serde;
 pub mod system;
 mod transaction;
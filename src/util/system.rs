@@ -83,6 +83,65 @@ pub fn statvfs<P: AsRef<Path>>(path: P) -> Result<FsStats> {
     })
 }
 
+/// Removes the Unix socket left by the previous process.
+///
+/// Fails if the socket is still served by another process.
+pub fn remove_stale_socket<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => anyhow::bail!("socket {} is used by another process", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        // NOTE: nobody listens on the socket
+        Err(_) => {}
+    }
+
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).context("failed to remove stale socket"),
+    }
+}
+
+/// Binds the Unix socket which is accessible only by the current user.
+///
+/// The socket is created in a private directory and is moved to the `path`
+/// after its permissions are restricted.
+pub fn bind_private_socket<P: AsRef<Path>>(path: P) -> Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let path = path.as_ref();
+
+    // Remove the socket left by the previous process
+    remove_stale_socket(path)?;
+
+    let file_name = path.file_name().context("invalid socket path")?;
+    let mut dir_name = OsString::from(".");
+    dir_name.push(file_name);
+    dir_name.push(".tmp");
+    let dir = path.with_file_name(dir_name);
+
+    // NOTE: directory could be left by the interrupted process
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .context("failed to create socket directory")?;
+
+    let bind = || -> Result<tokio::net::UnixListener> {
+        let tmp_path = dir.join(file_name);
+        let listener =
+            tokio::net::UnixListener::bind(&tmp_path).context("failed to bind socket")?;
+        std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600))
+            .context("failed to set socket permissions")?;
+        std::fs::rename(&tmp_path, path).context("failed to move socket")?;
+        Ok(listener)
+    };
+    let res = bind();
+
+    std::fs::remove_dir_all(&dir).ok();
+    res
+}
+
 unsafe fn get_passwd(uid: u32, buf: &mut Buffer) -> Option<libc::passwd> {
     let mut pwd: MaybeUninit<libc::passwd> = MaybeUninit::uninit();
     let mut pwdp = ptr::null_mut();
@@ -108,3 +167,31 @@ fn make_buffer() -> Buffer {
 }
 
 type Buffer = Vec<libc::c_char>;
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn private_socket() {
+        let dir = std::env::temp_dir().join(format!("nodekeeper-socket-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.sock");
+
+        let listener = bind_private_socket(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        // Socket is still served
+        assert!(bind_private_socket(&path).is_err());
+
+        // Socket is replaced after the listener is closed
+        drop(listener);
+        let _listener = bind_private_socket(&path).unwrap();
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
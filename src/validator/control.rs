@@ -0,0 +1,330 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use broxus_util::{serde_hex_array, serde_string};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::sync::Notify;
+
+use crate::util::system;
+
+/// Shared state of the running validation manager
+#[derive(Default)]
+pub struct ManagerControl {
+    status: parking_lot::Mutex<ManagerStatus>,
+    wakeup: Notify,
+}
+
+impl ManagerControl {
+    pub fn status(&self) -> ManagerStatus {
+        self.status.lock().clone()
+    }
+
+    pub fn update<F: FnOnce(&mut ManagerStatus)>(&self, f: F) {
+        f(&mut self.status.lock());
+    }
+
+    pub fn set_error(&self, error: &anyhow::Error) {
+        let mut status = self.status.lock();
+        status.last_error = Some(format!("{error:?}"));
+        status.last_error_at = Some(broxus_util::now());
+    }
+
+    /// Sleeps for the specified number of seconds or until any command is received
+    pub async fn sleep(&self, seconds: u64) {
+        self.status.lock().next_wakeup = Some(broxus_util::now() + seconds as u32);
+
+        let wakeup = self.wakeup.notified();
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(seconds)) => {},
+            _ = wakeup => tracing::info!("woken up by the control command"),
+        }
+
+        self.status.lock().next_wakeup = None;
+    }
+
+    /// Returns whether the participation in the specified elections is suppressed
+    pub fn check_participation(&self, election_id: u32) -> Participation {
+        let mut status = self.status.lock();
+        if status.skipped_election == Some(election_id) {
+            Participation::Skipped { first: false }
+        } else if status.skip_next {
+            status.skip_next = false;
+            status.skipped_election = Some(election_id);
+            Participation::Skipped { first: true }
+        } else if status.paused {
+            Participation::Paused
+        } else {
+            Participation::Allowed
+        }
+    }
+
+    pub fn take_force_elect(&self) -> bool {
+        std::mem::take(&mut self.status.lock().force_elect)
+    }
+
+    pub fn take_reload(&self) -> bool {
+        std::mem::take(&mut self.status.lock().reload)
+    }
+
+    /// Consumes the operator approval for exactly this transfer,
+    /// otherwise leaves it waiting for the approval
+    pub fn take_spending_approval(&self, request: PendingSpending) -> bool {
+        let now = broxus_util::now();
+
+        let mut status = self.status.lock();
+        match &status.pending_spending {
+            Some(pending)
+                if pending.hash == request.hash
+                    && matches!(pending.approved_until, Some(until) if until > now) =>
+            {
+                status.pending_spending = None;
+                true
+            }
+            _ => {
+                status.pending_spending = Some(request);
+                false
+            }
+        }
+    }
+
+    pub fn execute(&self, command: ControlCommand) -> Result<ManagerStatus> {
+        tracing::info!(?command, "received control command");
+
+        let mut status = self.status.lock();
+        let wakeup = match command {
+            ControlCommand::Status => false,
+            ControlCommand::Pause => {
+                status.paused = true;
+                false
+            }
+            ControlCommand::Resume => {
+                status.paused = false;
+                status.skip_next = false;
+                true
+            }
+            ControlCommand::SkipNext => {
+                status.skip_next = true;
+                true
+            }
+            ControlCommand::ForceElect => {
+                status.force_elect = true;
+                true
+            }
+            ControlCommand::Reload => {
+                status.reload = true;
+                true
+            }
+            ControlCommand::ApproveSpending { hash } => {
+                let pending = match &mut status.pending_spending {
+                    Some(pending) if pending.hash == hash => pending,
+                    Some(pending) => anyhow::bail!(
+                        "transfer {} is not waiting for the approval (pending: {})",
+                        hex::encode(hash),
+                        hex::encode(pending.hash)
+                    ),
+                    None => anyhow::bail!("no transfers are waiting for the approval"),
+                };
+                pending.approved_until = Some(broxus_util::now() + SPENDING_APPROVAL_TTL);
+                true
+            }
+        };
+
+        let result = status.clone();
+        drop(status);
+
+        if wakeup {
+            self.wakeup.notify_waiters();
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Participation {
+    Allowed,
+    Paused,
+    Skipped { first: bool },
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ManagerStatus {
+    pub paused: bool,
+    pub skip_next: bool,
+    pub skipped_election: Option<u32>,
+    pub timeline: Option<String>,
+    pub election_id: Option<u32>,
+    pub random_shift: Option<u32>,
+    pub next_wakeup: Option<u32>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<u32>,
+    pub pending_spending: Option<PendingSpending>,
+    #[serde(skip)]
+    force_elect: bool,
+    #[serde(skip)]
+    reload: bool,
+}
+
+/// Transfer above the confirmation threshold which waits for the operator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSpending {
+    #[serde(with = "serde_hex_array")]
+    pub hash: [u8; 32],
+    #[serde(with = "serde_string")]
+    pub destination: ton_block::MsgAddressInt,
+    #[serde(with = "serde_string")]
+    pub amount: u128,
+    pub approved_until: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControlCommand {
+    Status,
+    Pause,
+    Resume,
+    SkipNext,
+    ForceElect,
+    Reload,
+    ApproveSpending {
+        #[serde(with = "serde_hex_array")]
+        hash: [u8; 32],
+    },
+}
+
+impl FromStr for ControlCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "status" => Self::Status,
+            "pause" => Self::Pause,
+            "resume" => Self::Resume,
+            "skip-next" => Self::SkipNext,
+            "force-elect" => Self::ForceElect,
+            "reload" => Self::Reload,
+            _ => match s.strip_prefix("approve-spending:") {
+                Some(hash) => {
+                    let mut result = [0; 32];
+                    hex::decode_to_slice(hash, &mut result).context("invalid transfer hash")?;
+                    Self::ApproveSpending { hash: result }
+                }
+                None => anyhow::bail!("unknown command: {s}"),
+            },
+        })
+    }
+}
+
+/// How long the approved transfer can wait for the manager
+const SPENDING_APPROVAL_TTL: u32 = 3600;
+
+/// Max length of the control request line (in bytes)
+const MAX_REQUEST_LEN: u64 = 64 << 10;
+
+#[derive(Serialize, Deserialize)]
+struct ControlRequest {
+    command: ControlCommand,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+enum ControlResponse {
+    Ok { status: ManagerStatus },
+    Error { error: String },
+}
+
+/// Accepts commands on the Unix socket
+pub async fn serve<P: AsRef<Path>>(path: P, control: Arc<ManagerControl>) -> Result<()> {
+    let path = path.as_ref();
+
+    let listener = system::bind_private_socket(path).context("failed to bind control socket")?;
+    tracing::info!("listening for control commands on {}", path.display());
+
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .context("failed to accept control connection")?;
+
+        let control = control.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &control).await {
+                tracing::warn!("failed to handle control connection: {e:?}");
+            }
+        });
+    }
+}
+
+/// Sends the command to the running validation manager
+pub async fn send_command<P: AsRef<Path>>(
+    path: P,
+    command: ControlCommand,
+) -> Result<ManagerStatus> {
+    let path = path.as_ref();
+    let stream = UnixStream::connect(path).await.with_context(|| {
+        format!(
+            "failed to connect to the validator manager at {}",
+            path.display()
+        )
+    })?;
+    let (reader, mut writer) = stream.into_split();
+
+    write_line(&mut writer, &ControlRequest { command }).await?;
+
+    let mut line = String::new();
+    BufReader::new(reader)
+        .read_line(&mut line)
+        .await
+        .context("failed to read control response")?;
+
+    match serde_json::from_str(&line).context("invalid control response")? {
+        ControlResponse::Ok { status } => Ok(status),
+        ControlResponse::Error { error } => Err(anyhow::anyhow!(error)),
+    }
+}
+
+async fn handle_connection(stream: UnixStream, control: &ManagerControl) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = String::new();
+    BufReader::new(reader)
+        .take(MAX_REQUEST_LEN)
+        .read_line(&mut line)
+        .await
+        .context("failed to read control request")?;
+    anyhow::ensure!(
+        (line.len() as u64) < MAX_REQUEST_LEN,
+        "control request is too long"
+    );
+
+    let response = match serde_json::from_str::<ControlRequest>(&line) {
+        Ok(request) => match control.execute(request.command) {
+            Ok(status) => ControlResponse::Ok { status },
+            Err(e) => ControlResponse::Error {
+                error: e.to_string(),
+            },
+        },
+        Err(e) => ControlResponse::Error {
+            error: format!("invalid control request: {e}"),
+        },
+    };
+
+    write_line(&mut writer, &response).await
+}
+
+async fn write_line<W, T>(writer: &mut W, value: &T) -> Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+    T: Serialize,
+{
+    let mut data = serde_json::to_vec(value)?;
+    data.push(b'\n');
+    writer
+        .write_all(&data)
+        .await
+        .context("failed to write control message")
+}
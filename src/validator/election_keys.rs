@@ -0,0 +1,43 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::contracts::elector::GeneratedKeys;
+use crate::util::write_file_atomic;
+
+/// Node keys which were generated for each elections
+pub struct ElectionKeysStore {
+    path: PathBuf,
+}
+
+impl ElectionKeysStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn get(&self, election_id: u32) -> Result<GeneratedKeys> {
+        Ok(self.load()?.remove(&election_id).unwrap_or_default())
+    }
+
+    /// Saves keys for the specified elections and removes keys for the previous ones
+    pub fn store(&self, election_id: u32, keys: &GeneratedKeys) -> Result<()> {
+        let mut entries = self.load()?;
+        entries.retain(|&id, _| id >= election_id);
+        entries.insert(election_id, *keys);
+
+        let data =
+            serde_json::to_string_pretty(&entries).context("failed to serialize election keys")?;
+        write_file_atomic(&self.path, data).context("failed to save election keys")
+    }
+
+    fn load(&self) -> Result<BTreeMap<u32, GeneratedKeys>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(data) => serde_json::from_str(&data).context("invalid election keys"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(e).context("failed to read election keys"),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::process::Command;
+
+use crate::config::AppConfigHooks;
+use crate::util::exec_with_input;
+
+/// Runs user-defined scripts at the defined points of the validation flow
+#[derive(Default)]
+pub struct Hooks {
+    config: parking_lot::Mutex<Arc<AppConfigHooks>>,
+}
+
+impl Hooks {
+    pub fn set_config(&self, config: &AppConfigHooks) {
+        *self.config.lock() = Arc::new(config.clone());
+    }
+
+    pub fn has_scripts(&self, point: HookPoint) -> bool {
+        !point.scripts(&self.config.lock()).is_empty()
+    }
+
+    /// Runs all scripts for the specified point with the event as stdin.
+    ///
+    /// Only errors of the `pre_` hooks are returned, others are logged.
+    pub async fn run(&self, point: HookPoint, mut event: serde_json::Value) -> Result<()> {
+        let config = self.config.lock().clone();
+        let scripts = point.scripts(&config);
+        if scripts.is_empty() {
+            return Ok(());
+        }
+
+        if let serde_json::Value::Object(fields) = &mut event {
+            fields.insert("hook".to_owned(), point.as_str().into());
+            fields.insert("timestamp".to_owned(), broxus_util::now().into());
+        }
+        let input = serde_json::to_vec(&event).context("failed to serialize hook event")?;
+        let timeout = Duration::from_secs(config.timeout_sec as u64);
+
+        for script in scripts {
+            tracing::info!(hook = point.as_str(), script = %script.display(), "running hook");
+
+            let mut command = Command::new(script);
+            command.kill_on_drop(true);
+
+            let res =
+                match tokio::time::timeout(timeout, exec_with_input(&mut command, &input)).await {
+                    Ok(res) => res,
+                    Err(_) => Err(anyhow::anyhow!("timeout reached")),
+                }
+                .with_context(|| format!("{} hook {} failed", point.as_str(), script.display()));
+
+            match res {
+                Ok(()) => {}
+                Err(e) if point.is_pre() => return Err(e),
+                Err(e) => tracing::warn!("{e:?}"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HookPoint {
+    PreStake,
+    PostStake,
+    PostTicktock,
+    OnOutcome,
+    OnError,
+}
+
+impl HookPoint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PreStake => "pre_stake",
+            Self::PostStake => "post_stake",
+            Self::PostTicktock => "post_ticktock",
+            Self::OnOutcome => "on_outcome",
+            Self::OnError => "on_error",
+        }
+    }
+
+    /// Whether a failed hook must abort the step
+    pub fn is_pre(&self) -> bool {
+        matches!(self, Self::PreStake)
+    }
+
+    fn scripts<'a>(&self, config: &'a AppConfigHooks) -> &'a [PathBuf] {
+        match self {
+            Self::PreStake => &config.pre_stake,
+            Self::PostStake => &config.post_stake,
+            Self::PostTicktock => &config.post_ticktock,
+            Self::OnOutcome => &config.on_outcome,
+            Self::OnError => &config.on_error,
+        }
+    }
+}
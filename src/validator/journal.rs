@@ -0,0 +1,579 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use broxus_util::{serde_hex_array, serde_optional_hex_array, serde_optional_string, serde_string};
+use serde::{Deserialize, Serialize};
+
+/// Append-only journal of the elections participation
+pub struct ElectionJournal {
+    path: PathBuf,
+    cache: parking_lot::Mutex<JournalCache>,
+}
+
+/// Entries which were already parsed and the position of the file tail
+#[derive(Default)]
+struct JournalCache {
+    entries: Vec<JournalEntry>,
+    offset: u64,
+    lines: usize,
+}
+
+impl ElectionJournal {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            cache: Default::default(),
+        }
+    }
+
+    pub fn load(&self) -> Result<Vec<JournalEntry>> {
+        self.with_entries(<[JournalEntry]>::to_vec)
+    }
+
+    /// Calls `f` with all journal entries, only the part of the file
+    /// which was appended since the previous call is parsed
+    pub fn with_entries<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&[JournalEntry]) -> T,
+    {
+        let mut cache = self.cache.lock();
+        if let Err(e) = self.read_tail(&mut cache) {
+            // Parse the whole file next time
+            *cache = Default::default();
+            return Err(e);
+        }
+        Ok(f(&cache.entries))
+    }
+
+    fn read_tail(&self, cache: &mut JournalCache) -> Result<()> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                *cache = Default::default();
+                return Ok(());
+            }
+            Err(e) => return Err(e).context("failed to open election journal"),
+        };
+
+        let len = file
+            .metadata()
+            .context("failed to read election journal")?
+            .len();
+        if len < cache.offset {
+            // File was replaced
+            *cache = Default::default();
+        }
+
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(cache.offset))
+            .and_then(|_| file.read_to_end(&mut data))
+            .context("failed to read election journal")?;
+
+        // NOTE: the last line could still be written by another process
+        let Some(end) = data.iter().rposition(|&byte| byte == b'\n') else {
+            return Ok(());
+        };
+
+        for line in data[..end].split(|&byte| byte == b'\n') {
+            cache.lines += 1;
+            let line = std::str::from_utf8(line).context("invalid election journal")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry = serde_json::from_str(line).with_context(|| {
+                format!("invalid election journal entry at line {}", cache.lines)
+            })?;
+            cache.entries.push(entry);
+        }
+        cache.offset += end as u64 + 1;
+
+        Ok(())
+    }
+
+    pub fn append(&self, election_id: u32, event: JournalEvent) -> Result<()> {
+        let entry = JournalEntry {
+            timestamp: broxus_util::now(),
+            election_id,
+            event,
+        };
+
+        let mut line =
+            serde_json::to_string(&entry).context("failed to serialize journal entry")?;
+        line.push('\n');
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("failed to open election journal")?;
+        file.write_all(line.as_bytes())
+            .context("failed to write election journal")?;
+        file.sync_data().context("failed to sync election journal")
+    }
+
+    /// Same as [`append`], but only logs an error
+    ///
+    /// [`append`]: Self::append
+    pub fn record(&self, election_id: u32, event: JournalEvent) {
+        if let Err(e) = self.append(election_id, event) {
+            tracing::error!(election_id, "failed to update election journal: {e:?}");
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub timestamp: u32,
+    pub election_id: u32,
+    #[serde(flatten)]
+    pub event: JournalEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "event")]
+pub enum JournalEvent {
+    /// Participation in the elections has started
+    Started {
+        mode: ValidatorMode,
+        #[serde(with = "serde_string")]
+        wallet: ton_block::MsgAddressInt,
+    },
+    /// Unfrozen stake was recovered from the elector
+    StakeRecovered {
+        #[serde(with = "serde_string")]
+        amount: u128,
+    },
+    /// DePool or proxy balance was replenished
+    BalanceReplenished {
+        #[serde(with = "serde_string")]
+        target: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        amount: u128,
+    },
+    /// Validator ordinary stake was added to the DePool pooling round
+    OrdinaryStakeAdded {
+        #[serde(with = "serde_string")]
+        amount: u64,
+    },
+    /// DePool rounds were updated
+    TicktockSent,
+    /// DePool round is not ready for the elections
+    RoundSkipped { reason: String },
+    /// Validator round overlaps the maintenance window
+    MaintenanceSkipped {
+        round_until: u32,
+        window_since: u32,
+        window_until: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// Participant is already in the elector members list
+    AlreadyElected {
+        #[serde(with = "serde_string")]
+        participant: ton_block::MsgAddressInt,
+    },
+    /// New validator keys were added to the node
+    KeysGenerated {
+        #[serde(with = "serde_string")]
+        participant: ton_block::MsgAddressInt,
+        #[serde(with = "serde_hex_array")]
+        permanent_key_hash: [u8; 32],
+        #[serde(with = "serde_hex_array")]
+        public_key: [u8; 32],
+        #[serde(with = "serde_hex_array")]
+        adnl_addr: [u8; 32],
+    },
+    /// Election message was delivered
+    StakeSent {
+        #[serde(with = "serde_string")]
+        participant: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        stake: u128,
+        stake_factor: u32,
+        #[serde(with = "serde_hex_array")]
+        tx_hash: [u8; 32],
+    },
+    /// Additional stake was sent during the same elections
+    StakeToppedUp {
+        #[serde(with = "serde_string")]
+        participant: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        amount: u128,
+        #[serde(with = "serde_hex_array")]
+        tx_hash: [u8; 32],
+    },
+    /// Validator wallet surplus was sent to the cold wallet
+    Swept {
+        #[serde(with = "serde_string")]
+        destination: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        amount: u128,
+        #[serde(with = "serde_hex_array")]
+        tx_hash: [u8; 32],
+    },
+    /// DePool round was advanced by the background ticktock
+    ScheduledTicktock {
+        round_id: u64,
+        step: String,
+        #[serde(with = "serde_string")]
+        fees: u128,
+        #[serde(with = "serde_hex_array")]
+        tx_hash: [u8; 32],
+    },
+    /// External message of the previous process was resolved after restart
+    MessageReconciled {
+        purpose: String,
+        #[serde(with = "serde_hex_array")]
+        msg_hash: [u8; 32],
+        #[serde(
+            default,
+            with = "serde_optional_hex_array",
+            skip_serializing_if = "Option::is_none"
+        )]
+        tx_hash: Option<[u8; 32]>,
+    },
+    /// Elections result was checked
+    Reconciled {
+        outcome: ElectionOutcome,
+        #[serde(
+            default,
+            with = "serde_optional_string",
+            skip_serializing_if = "Option::is_none"
+        )]
+        true_stake: Option<u128>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        in_next_vset: Option<bool>,
+    },
+}
+
+impl JournalEvent {
+    fn is_delivered_stake(&self) -> bool {
+        matches!(
+            self,
+            Self::MessageReconciled { purpose, tx_hash: Some(_), .. } if purpose == super::STAKE_PURPOSE
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidatorMode {
+    Single,
+    DePool,
+}
+
+/// Result of the participation in the elections
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElectionOutcome {
+    /// Participant is in the new validator set
+    Elected,
+    /// Stake was accepted but returned to the credits after the elections
+    StakeTooSmall,
+    /// Stake was returned by the elector without participation
+    Bounced,
+    /// Stake was not sent during the elections
+    NotSubmitted,
+    /// DePool round was not ready for the elections
+    RoundSkipped,
+    /// Stake was delivered after restart but the participant is not known
+    Unknown,
+}
+
+impl ElectionOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Elected => "elected",
+            Self::StakeTooSmall => "stake_too_small",
+            Self::Bounced => "bounced",
+            Self::NotSubmitted => "not_submitted",
+            Self::RoundSkipped => "round_skipped",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Started elections which were not reconciled yet
+#[derive(Debug, Clone, Default)]
+pub struct PendingElection {
+    pub election_id: u32,
+    pub participant: Option<ton_block::MsgAddressInt>,
+    pub public_key: Option<[u8; 32]>,
+    pub stake_sent: bool,
+    pub round_skipped: bool,
+}
+
+pub fn pending_elections(entries: &[JournalEntry]) -> Vec<PendingElection> {
+    let mut pending = BTreeMap::<u32, PendingElection>::new();
+
+    for entry in entries {
+        let election_id = entry.election_id;
+        if let JournalEvent::Reconciled { .. } = &entry.event {
+            pending.remove(&election_id);
+            continue;
+        }
+        // NOTE: background ticktocks and other messages are not related
+        // to the elections participation
+        match &entry.event {
+            JournalEvent::ScheduledTicktock { .. } => continue,
+            JournalEvent::MessageReconciled { .. } if !entry.event.is_delivered_stake() => continue,
+            _ => {}
+        }
+
+        let election = pending
+            .entry(election_id)
+            .or_insert_with(|| PendingElection {
+                election_id,
+                ..Default::default()
+            });
+
+        match &entry.event {
+            JournalEvent::AlreadyElected { participant } => {
+                election.participant = Some(participant.clone());
+                election.stake_sent = true;
+            }
+            JournalEvent::KeysGenerated {
+                participant,
+                public_key,
+                ..
+            } => {
+                election.participant = Some(participant.clone());
+                election.public_key = Some(*public_key);
+            }
+            JournalEvent::StakeSent { participant, .. }
+            | JournalEvent::StakeToppedUp { participant, .. } => {
+                election.participant = Some(participant.clone());
+                election.stake_sent = true;
+            }
+            JournalEvent::MessageReconciled { .. } => {
+                election.stake_sent = true;
+            }
+            JournalEvent::RoundSkipped { .. } | JournalEvent::MaintenanceSkipped { .. } => {
+                election.round_skipped = true;
+            }
+            _ => {}
+        }
+    }
+
+    pending.into_values().collect()
+}
+
+/// Returns whether the stake of the previous process was delivered for these elections
+pub fn stake_delivered(entries: &[JournalEntry], election_id: u32) -> bool {
+    entries
+        .iter()
+        .any(|entry| entry.election_id == election_id && entry.event.is_delivered_stake())
+}
+
+/// Returns the last elections skipped due to maintenance
+pub fn last_maintenance_skip(entries: &[JournalEntry]) -> Option<u32> {
+    entries.iter().rev().find_map(|entry| match &entry.event {
+        JournalEvent::MaintenanceSkipped { .. } => Some(entry.election_id),
+        _ => None,
+    })
+}
+
+/// Returns the last reconciled elections
+pub fn last_outcome(entries: &[JournalEntry]) -> Option<(u32, ElectionOutcome)> {
+    entries.iter().rev().find_map(|entry| match &entry.event {
+        JournalEvent::Reconciled { outcome, .. } => Some((entry.election_id, *outcome)),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn participant() -> ton_block::MsgAddressInt {
+        ton_block::MsgAddressInt::from_str(&format!("0:{}", hex::encode([1; 32]))).unwrap()
+    }
+
+    fn entry(election_id: u32, event: JournalEvent) -> JournalEntry {
+        JournalEntry {
+            timestamp: 0,
+            election_id,
+            event,
+        }
+    }
+
+    fn stake_sent(election_id: u32) -> JournalEntry {
+        entry(
+            election_id,
+            JournalEvent::StakeSent {
+                participant: participant(),
+                stake: 1,
+                stake_factor: 65536,
+                tx_hash: [0; 32],
+            },
+        )
+    }
+
+    fn reconciled(election_id: u32) -> JournalEntry {
+        entry(
+            election_id,
+            JournalEvent::Reconciled {
+                outcome: ElectionOutcome::Elected,
+                true_stake: None,
+                in_next_vset: None,
+            },
+        )
+    }
+
+    fn message_reconciled(election_id: u32, purpose: &str, delivered: bool) -> JournalEntry {
+        entry(
+            election_id,
+            JournalEvent::MessageReconciled {
+                purpose: purpose.to_owned(),
+                msg_hash: [0; 32],
+                tx_hash: delivered.then_some([0; 32]),
+            },
+        )
+    }
+
+    #[test]
+    fn journal_tail_is_parsed_once() {
+        let path = std::env::temp_dir().join(format!(
+            "nodekeeper-journal-{}-tail.jsonl",
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+
+        let journal = ElectionJournal::new(&path);
+        assert!(journal.load().unwrap().is_empty());
+
+        journal.append(100, stake_sent(100).event).unwrap();
+        assert_eq!(journal.load().unwrap().len(), 1);
+
+        // Entries from another instance are appended to the parsed ones
+        ElectionJournal::new(&path)
+            .append(100, reconciled(100).event)
+            .unwrap();
+        assert!(journal.with_entries(pending_elections).unwrap().is_empty());
+
+        // Incomplete line is left for the next read
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        let line = serde_json::to_string(&stake_sent(200)).unwrap();
+        file.write_all(line.as_bytes()).unwrap();
+        assert_eq!(journal.load().unwrap().len(), 2);
+
+        file.write_all(b"\n").unwrap();
+        let pending = journal.with_entries(pending_elections).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].election_id, 200);
+
+        // Replaced file is parsed again
+        std::fs::remove_file(&path).unwrap();
+        ElectionJournal::new(&path)
+            .append(300, stake_sent(300).event)
+            .unwrap();
+        let entries = journal.load().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].election_id, 300);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn reconciled_elections_are_not_pending() {
+        let entries = [
+            stake_sent(100),
+            reconciled(100),
+            stake_sent(200),
+            entry(
+                300,
+                JournalEvent::RoundSkipped {
+                    reason: "test".to_owned(),
+                },
+            ),
+        ];
+
+        let pending = pending_elections(&entries);
+        assert_eq!(pending.len(), 2);
+
+        assert_eq!(pending[0].election_id, 200);
+        assert_eq!(pending[0].participant, Some(participant()));
+        assert!(pending[0].stake_sent);
+        assert!(!pending[0].round_skipped);
+
+        assert_eq!(pending[1].election_id, 300);
+        assert!(!pending[1].stake_sent);
+        assert!(pending[1].round_skipped);
+    }
+
+    #[test]
+    fn keys_without_stake() {
+        let entries = [entry(
+            100,
+            JournalEvent::KeysGenerated {
+                participant: participant(),
+                permanent_key_hash: [0; 32],
+                public_key: [2; 32],
+                adnl_addr: [0; 32],
+            },
+        )];
+
+        let pending = pending_elections(&entries);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].public_key, Some([2; 32]));
+        assert!(!pending[0].stake_sent);
+    }
+
+    #[test]
+    fn unrelated_events_are_ignored() {
+        let entries = [
+            entry(
+                100,
+                JournalEvent::ScheduledTicktock {
+                    round_id: 1,
+                    step: "WaitingUnfreeze".to_owned(),
+                    fees: 0,
+                    tx_hash: [0; 32],
+                },
+            ),
+            message_reconciled(100, "ticktock", true),
+            message_reconciled(200, super::super::STAKE_PURPOSE, false),
+        ];
+        assert!(pending_elections(&entries).is_empty());
+    }
+
+    #[test]
+    fn delivered_stake_after_restart() {
+        let entries = [message_reconciled(100, super::super::STAKE_PURPOSE, true)];
+
+        let pending = pending_elections(&entries);
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].stake_sent);
+        assert_eq!(pending[0].participant, None);
+
+        assert!(stake_delivered(&entries, 100));
+        assert!(!stake_delivered(&entries, 200));
+
+        // Participant is known from the generated keys
+        let entries = [
+            entry(
+                100,
+                JournalEvent::KeysGenerated {
+                    participant: participant(),
+                    permanent_key_hash: [0; 32],
+                    public_key: [2; 32],
+                    adnl_addr: [0; 32],
+                },
+            ),
+            message_reconciled(100, super::super::STAKE_PURPOSE, true),
+        ];
+
+        let pending = pending_elections(&entries);
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].stake_sent);
+        assert_eq!(pending[0].participant, Some(participant()));
+    }
+}
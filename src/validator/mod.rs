@@ -7,24 +7,64 @@ use futures_util::FutureExt;
 use rand::Rng;
 use tokio::sync::Mutex;
 
+use self::control::{ManagerControl, Participation};
+use self::election_keys::ElectionKeysStore;
+use self::hooks::{HookPoint, Hooks};
+use self::journal::{ElectionJournal, ElectionOutcome, JournalEvent, ValidatorMode};
+use self::plan::ElectionPlan;
+use self::rewards::RewardHistory;
+use self::spending::SpendingGuard;
+pub use self::ticktock::DePoolTicker;
 use crate::config::*;
 use crate::contracts::*;
 use crate::dirs::ProjectDirs;
-use crate::network::{ConfigWithId, NodeStats, NodeTcpRpc, NodeUdpRpc, Subscription};
-use crate::util::Tokens;
+use crate::network::{
+    ConfigWithId, MessageQueue, NodeStats, NodeTcpRpc, NodeUdpRpc, ReconciledMessage, Subscription,
+};
+use crate::notifier::{Notification, Notifier};
+use crate::util::{Tokens, TransactionWithHash};
+
+pub mod control;
+mod election_keys;
+mod hooks;
+pub mod journal;
+pub mod plan;
+pub mod preflight;
+pub mod rewards;
+mod spending;
+mod ticktock;
 
 pub struct ValidationManager {
     dirs: ProjectDirs,
     params: ValidationParams,
+    journal: ElectionJournal,
+    election_keys: ElectionKeysStore,
+    message_queue: Arc<MessageQueue>,
+    control: Arc<ManagerControl>,
+    notifier: Notifier,
+    hooks: Hooks,
+    spending: SpendingGuard,
     last_params: parking_lot::Mutex<Option<AppConfigValidator>>,
     guard: Arc<Mutex<()>>,
 }
 
 impl ValidationManager {
     pub fn new(dirs: ProjectDirs, params: ValidationParams) -> Self {
+        let journal = ElectionJournal::new(&dirs.election_journal);
+        let election_keys = ElectionKeysStore::new(&dirs.election_keys);
+        let message_queue = Arc::new(MessageQueue::new(&dirs.message_queue));
+        let control = Arc::<ManagerControl>::default();
+        let spending = SpendingGuard::new(&dirs.spending_ledger, control.clone());
         Self {
             dirs,
             params,
+            journal,
+            election_keys,
+            message_queue,
+            control,
+            notifier: Default::default(),
+            hooks: Default::default(),
+            spending,
             last_params: Default::default(),
             guard: Default::default(),
         }
@@ -34,6 +74,32 @@ impl ValidationManager {
         &self.guard
     }
 
+    pub fn control(&self) -> &Arc<ManagerControl> {
+        &self.control
+    }
+
+    /// Creates a background task for the DePool ticktocks between the elections
+    pub fn ticker(&self) -> DePoolTicker {
+        DePoolTicker::new(
+            ProjectDirs::new(&self.dirs.root),
+            self.control.clone(),
+            self.guard.clone(),
+        )
+    }
+
+    /// Remembers the validation error, sends it to the notification sinks and hooks
+    pub async fn report_error(&self, error: &anyhow::Error) {
+        let message = format!("{error:#}");
+        self.control.set_error(error);
+        self.notifier.notify(Notification::Error {
+            message: message.clone(),
+        });
+        self.hooks
+            .run(HookPoint::OnError, serde_json::json!({ "error": message }))
+            .await
+            .ok();
+    }
+
     pub async fn try_validate(&mut self) -> Result<()> {
         const SYNC_CHECK_INTERVAL: u32 = 10;
 
@@ -46,11 +112,27 @@ impl ValidationManager {
             // Sleep with the requested interval
             if interval > 0 {
                 interval = std::cmp::max(interval, 10);
-                tokio::time::sleep(Duration::from_secs(interval as u64)).await;
+                self.control.sleep(interval as u64).await;
+            }
+
+            // Handle control commands
+            if self.control.take_reload() {
+                tracing::info!("reloading config");
+                *self.last_params.lock() = None;
+            }
+            if self.control.take_force_elect() {
+                tracing::info!("force elect requested");
+                if let Err(e) = self.force_elect().await {
+                    tracing::error!("failed to force elect: {e:?}");
+                    self.report_error(&e).await;
+                }
             }
 
             // Read config
             let mut config = AppConfig::load(&self.dirs.app_config)?;
+            self.notifier.set_sinks(&config.notifications);
+            self.hooks.set_config(&config.hooks);
+            self.spending.set_policy(config.spending_policy.as_ref())?;
             let validator = match config.validator.take() {
                 Some(validator) => validator,
                 None => {
@@ -71,13 +153,21 @@ impl ValidationManager {
             let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
             subscription.ensure_ready().await?;
 
+            // Resolve messages which were sent before restart
+            subscription.set_message_queue(self.message_queue.clone());
+            let reconciled = subscription.reconcile_message_queue().await;
+
             // Get current network config params
             let ConfigWithId {
                 block_id: target_block,
                 config: ref blockchain_config,
             } = subscription.tcp_rpc().get_config_all().await?;
+            self.record_reconciled(reconciled, blockchain_config);
 
-            if !self.params.ignore_deploy && self.ensure_deployed(&validator, &subscription).await?
+            if !self.params.ignore_deploy
+                && self
+                    .ensure_deployed(&validator, config.signer.as_ref(), &subscription)
+                    .await?
             {
                 // Proceed to the next iteration after contracts deployment
                 continue;
@@ -93,6 +183,8 @@ impl ValidationManager {
                 .validator_set()
                 .context("invalid validator set")?;
 
+            let elector = Elector::new(elector_address, subscription.clone());
+
             // Get block with the config
             tracing::info!("target block id: {target_block}");
             let target_block = subscription.udp_rpc().get_block(&target_block).await?;
@@ -103,6 +195,8 @@ impl ValidationManager {
             // Compute where are we on the validation timeline
             let timeline = Timeline::compute(&timings, &current_vset, target_block_info.gen_utime);
             tracing::info!("timeline: {timeline}");
+            self.control
+                .update(|status| status.timeline = Some(timeline.to_string()));
 
             let elections_end = match timeline {
                 // If elections were not started yet, wait for the start (with an additional offset)
@@ -110,6 +204,7 @@ impl ValidationManager {
                     until_elections_start,
                 } => {
                     random_shift = None; // reset random shift before each elections
+                    self.control.update(|status| status.random_shift = None);
                     tracing::info!("waiting for the elections to start");
                     interval = until_elections_start + self.params.elections_start_offset;
                     continue;
@@ -120,18 +215,11 @@ impl ValidationManager {
                     until_elections_end,
                     elections_end,
                 } => {
-                    let random_shift = match random_shift {
-                        Some(shift) => shift,
-                        None if self.params.disable_random_shift => *random_shift.insert(0),
-                        None => {
-                            // Compute the random offset in the first 1/4 of elections
-                            let range = (since_elections_start + until_elections_end)
-                                .saturating_sub(self.params.elections_end_offset)
-                                .saturating_sub(self.params.elections_start_offset)
-                                / 4;
-                            *random_shift.insert(rand::thread_rng().gen_range(0..range))
-                        }
-                    };
+                    let random_shift = *random_shift.get_or_insert_with(|| {
+                        self.compute_random_shift(since_elections_start, until_elections_end)
+                    });
+                    self.control
+                        .update(|status| status.random_shift = Some(random_shift));
 
                     let start_offset = self.params.elections_start_offset + random_shift;
 
@@ -156,14 +244,28 @@ impl ValidationManager {
                 }
                 // Elections were already finished, wait for the new round
                 Timeline::AfterElections { until_round_end } => {
-                    tracing::info!("waiting for the new round to start");
-                    interval = until_round_end;
+                    // Check the results of the finished elections
+                    let has_unfinished =
+                        match self.reconcile_journal(&elector, blockchain_config).await {
+                            Ok(has_unfinished) => has_unfinished,
+                            Err(e) => {
+                                tracing::warn!("failed to reconcile election journal: {e:?}");
+                                false
+                            }
+                        };
+
+                    if has_unfinished {
+                        // Elector has not conducted the elections yet
+                        interval = std::cmp::min(until_round_end, RECONCILE_INTERVAL);
+                    } else {
+                        tracing::info!("waiting for the new round to start");
+                        interval = until_round_end;
+                    }
                     continue;
                 }
             };
 
             // Participate in elections
-            let elector = Elector::new(elector_address, subscription.clone());
             let elector_data = elector
                 .get_data()
                 .await
@@ -175,6 +277,50 @@ impl ValidationManager {
                 interval = 1; // retry nearly immediate
                 continue;
             };
+            self.control
+                .update(|status| status.election_id = Some(election_id));
+
+            // Check whether the participation was suppressed by the operator
+            match self.control.check_participation(election_id) {
+                Participation::Allowed => {}
+                participation => {
+                    if let Participation::Skipped { first: true } = participation {
+                        self.journal.record(
+                            election_id,
+                            JournalEvent::RoundSkipped {
+                                reason: "skipped by the operator".to_owned(),
+                            },
+                        );
+                    }
+                    tracing::info!(election_id, ?participation, "participation suppressed");
+                    interval = elections_end.saturating_sub(now());
+                    continue;
+                }
+            }
+
+            // Skip elections for the round which overlaps the maintenance window
+            let round_until = election_id + timings.validators_elected_for;
+            if let Some(window) = config.find_maintenance(election_id, round_until) {
+                tracing::warn!(
+                    election_id,
+                    round_until,
+                    window_since = window.since,
+                    window_until = window.until,
+                    reason = window.reason.as_deref().unwrap_or_default(),
+                    "skipping elections due to the maintenance window"
+                );
+                self.journal.record(
+                    election_id,
+                    JournalEvent::MaintenanceSkipped {
+                        round_until,
+                        window_since: window.since,
+                        window_until: window.until,
+                        reason: window.reason.clone(),
+                    },
+                );
+                interval = elections_end.saturating_sub(now());
+                continue;
+            }
 
             // Wait until stakes are unfrozen
             if let Some(mut unfreeze_at) = elector_data.nearest_unfreeze_at(election_id) {
@@ -187,14 +333,16 @@ impl ValidationManager {
                     );
                 } else if let Some(until_unfreeze) = unfreeze_at.checked_sub(now()) {
                     if until_unfreeze > 0 {
+                        // NOTE: control commands are handled on the next iteration
                         tracing::info!(until_unfreeze, "waiting for stakes to unfreeze");
-                        tokio::time::sleep(Duration::from_secs(until_unfreeze as u64)).await;
+                        interval = until_unfreeze;
+                        continue;
                     }
                 }
             }
 
             // Prepare context
-            let keypair = self.dirs.load_validator_keys()?;
+            let signer = WalletSigner::load(config.signer.as_ref(), &self.dirs)?;
             let ctx = ElectionsContext {
                 subscription,
                 elector,
@@ -202,13 +350,25 @@ impl ValidationManager {
                 election_id,
                 timings,
                 blockchain_config,
+                dirs: &self.dirs,
+                journal: &self.journal,
+                election_keys: &self.election_keys,
+                notifier: &self.notifier,
+                hooks: &self.hooks,
+                spending: &self.spending,
+                sweep: config.sweep.as_ref(),
+                plan: None,
                 guard: &self.guard,
             };
 
+            // Single validator stake could grow during the elections
+            let top_up =
+                matches!(&validator, AppConfigValidator::Single(single) if single.stake_top_up);
+
             // Prepare election future
             let validation = match validator {
-                AppConfigValidator::Single(validation) => validation.elect(keypair, ctx).boxed(),
-                AppConfigValidator::DePool(validation) => validation.elect(keypair, ctx).boxed(),
+                AppConfigValidator::Single(validation) => validation.elect(signer, ctx).boxed(),
+                AppConfigValidator::DePool(validation) => validation.elect(signer, ctx).boxed(),
             };
 
             // Try elect
@@ -223,13 +383,24 @@ impl ValidationManager {
                 Err(_) => tracing::warn!("elections deadline reached"),
             }
 
-            interval = elections_end.saturating_sub(now());
+            let until_deadline = elections_end
+                .saturating_sub(self.params.elections_end_offset)
+                .saturating_sub(now());
+            interval = if top_up && until_deadline > 0 {
+                // Check the stake top-up until the end of the elections
+                std::cmp::min(until_deadline, TOP_UP_CHECK_INTERVAL)
+            } else {
+                elections_end.saturating_sub(now())
+            };
         }
     }
 
     pub async fn force_elect(&self) -> Result<()> {
         // Read config
         let mut config = AppConfig::load(&self.dirs.app_config)?;
+        self.notifier.set_sinks(&config.notifications);
+        self.hooks.set_config(&config.hooks);
+        self.spending.set_policy(config.spending_policy.as_ref())?;
         let validator = config
             .validator
             .take()
@@ -246,14 +417,20 @@ impl ValidationManager {
         let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
         subscription.ensure_ready().await?;
 
+        // Resolve messages which were sent before restart
+        subscription.set_message_queue(self.message_queue.clone());
+        let reconciled = subscription.reconcile_message_queue().await;
+
         // Get current network config params
         let ConfigWithId {
             config: ref blockchain_config,
             ..
         } = subscription.tcp_rpc().get_config_all().await?;
+        self.record_reconciled(reconciled, blockchain_config);
 
         if !self.params.ignore_deploy {
-            self.ensure_deployed(&validator, &subscription).await?;
+            self.ensure_deployed(&validator, config.signer.as_ref(), &subscription)
+                .await?;
         }
 
         // Get addresses
@@ -277,7 +454,7 @@ impl ValidationManager {
         };
 
         // Prepare context
-        let keypair = self.dirs.load_validator_keys()?;
+        let signer = WalletSigner::load(config.signer.as_ref(), &self.dirs)?;
         let ctx = ElectionsContext {
             subscription,
             elector,
@@ -285,19 +462,354 @@ impl ValidationManager {
             election_id,
             timings,
             blockchain_config,
+            dirs: &self.dirs,
+            journal: &self.journal,
+            election_keys: &self.election_keys,
+            notifier: &self.notifier,
+            hooks: &self.hooks,
+            spending: &self.spending,
+            sweep: config.sweep.as_ref(),
+            plan: None,
             guard: &self.guard,
         };
 
         // Prepare election future
         match validator {
-            AppConfigValidator::Single(validation) => validation.elect(keypair, ctx).await,
-            AppConfigValidator::DePool(validation) => validation.elect(keypair, ctx).await,
+            AppConfigValidator::Single(validation) => validation.elect(signer, ctx).await,
+            AppConfigValidator::DePool(validation) => validation.elect(signer, ctx).await,
+        }
+    }
+
+    /// Computes actions for the current elections without sending any messages
+    pub async fn dry_run(&self, force: bool) -> Result<ElectionPlan> {
+        // Read config
+        let mut config = AppConfig::load(&self.dirs.app_config)?;
+        self.spending.set_policy(config.spending_policy.as_ref())?;
+        let validator = config
+            .validator
+            .take()
+            .context("validator entry not found in the app config")?;
+
+        // Create tcp rpc and check that node is synced
+        let node_tcp_rpc = NodeTcpRpc::new(config.control()?).await?;
+        if !self.is_synced(&node_tcp_rpc, validator.is_single()).await? {
+            anyhow::bail!("node not synced");
+        }
+        let node_udp_rpc = NodeUdpRpc::new(config.adnl()?).await?;
+
+        // Create subscription
+        let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
+        subscription.ensure_ready().await?;
+
+        // Get current network config params
+        let ConfigWithId {
+            block_id: target_block,
+            config: ref blockchain_config,
+        } = subscription.tcp_rpc().get_config_all().await?;
+
+        let mut plan = ElectionPlan::default();
+        if !self.params.ignore_deploy {
+            plan.note("contracts deployment is not checked in dry-run mode");
+        }
+
+        let elector_address = blockchain_config
+            .elector_address()
+            .context("invalid elector address")?;
+        let timings = blockchain_config
+            .elector_params()
+            .context("invalid elector params")?;
+        let current_vset = blockchain_config
+            .validator_set()
+            .context("invalid validator set")?;
+
+        // Compute where are we on the validation timeline
+        let target_block = subscription.udp_rpc().get_block(&target_block).await?;
+        let target_block_info = target_block
+            .read_brief_info()
+            .context("invalid target block")?;
+        let timeline = Timeline::compute(&timings, &current_vset, target_block_info.gen_utime);
+        plan.timeline = Some(timeline.to_string());
+
+        let elections_end = match timeline {
+            Timeline::BeforeElections {
+                until_elections_start,
+            } => {
+                let interval = until_elections_start + self.params.elections_start_offset;
+                if !force {
+                    plan.wait(interval, "waiting for the elections to start");
+                    return Ok(plan);
+                }
+                None
+            }
+            Timeline::Elections {
+                since_elections_start,
+                until_elections_end,
+                elections_end,
+            } => {
+                let random_shift =
+                    self.compute_random_shift(since_elections_start, until_elections_end);
+                plan.random_shift = Some(random_shift);
+
+                let start_offset = self.params.elections_start_offset + random_shift;
+                if let Some(offset) = start_offset.checked_sub(since_elections_start) {
+                    if offset > 0 && !force {
+                        plan.wait(offset, "waiting for the random shift after elections start");
+                    }
+                } else if let Some(offset) = self
+                    .params
+                    .elections_end_offset
+                    .checked_sub(until_elections_end)
+                {
+                    if !force {
+                        plan.wait(
+                            offset,
+                            "elections will end soon, waiting for the next round",
+                        );
+                        return Ok(plan);
+                    }
+                }
+                Some(elections_end)
+            }
+            Timeline::AfterElections { until_round_end } => {
+                if !force {
+                    plan.wait(until_round_end, "waiting for the new round to start");
+                    return Ok(plan);
+                }
+                None
+            }
+        };
+
+        let elector = Elector::new(elector_address, subscription.clone());
+        let elector_data = elector
+            .get_data()
+            .await
+            .context("failed to get elector data")?;
+
+        // Get current election id
+        let Some(election_id) = elector_data.election_id() else {
+            anyhow::bail!("no current elections in the elector state");
+        };
+        plan.election_id = Some(election_id);
+
+        // Check maintenance windows
+        let round_until = election_id + timings.validators_elected_for;
+        if let Some(window) = config.find_maintenance(election_id, round_until) {
+            plan.note(format!(
+                "round {election_id}..{round_until} overlaps the maintenance window {}..{}",
+                window.since, window.until
+            ));
+            if !force {
+                return Ok(plan);
+            }
+        }
+
+        // Check stakes unfreeze time
+        if let Some(mut unfreeze_at) = elector_data.nearest_unfreeze_at(election_id) {
+            unfreeze_at += self.params.stake_unfreeze_offset;
+            match elections_end {
+                Some(elections_end)
+                    if unfreeze_at
+                        > elections_end.saturating_sub(self.params.elections_end_offset) =>
+                {
+                    plan.note("stakes will unfreeze after the end of the elections");
+                }
+                _ => {
+                    if let Some(until_unfreeze) = unfreeze_at.checked_sub(now()) {
+                        if until_unfreeze > 0 && !force {
+                            plan.wait(until_unfreeze, "waiting for stakes to unfreeze");
+                        }
+                    }
+                }
+            }
+        }
+
+        // Prepare context
+        let signer = WalletSigner::load(config.signer.as_ref(), &self.dirs)?;
+        let ctx = ElectionsContext {
+            subscription,
+            elector,
+            elector_data,
+            election_id,
+            timings,
+            blockchain_config,
+            dirs: &self.dirs,
+            journal: &self.journal,
+            election_keys: &self.election_keys,
+            notifier: &self.notifier,
+            hooks: &self.hooks,
+            spending: &self.spending,
+            sweep: config.sweep.as_ref(),
+            plan: Some(&plan),
+            guard: &self.guard,
+        };
+
+        match validator {
+            AppConfigValidator::Single(validation) => validation.elect(signer, ctx).await?,
+            AppConfigValidator::DePool(validation) => validation.elect(signer, ctx).await?,
+        }
+
+        Ok(plan)
+    }
+
+    fn compute_random_shift(&self, since_elections_start: u32, until_elections_end: u32) -> u32 {
+        if self.params.disable_random_shift {
+            return 0;
+        }
+
+        // Compute the random offset in the first 1/4 of elections
+        let range = (since_elections_start + until_elections_end)
+            .saturating_sub(self.params.elections_end_offset)
+            .saturating_sub(self.params.elections_start_offset)
+            / 4;
+        rand::thread_rng().gen_range(0..range)
+    }
+
+    /// Journals external messages which were resolved after restart
+    fn record_reconciled(
+        &self,
+        reconciled: Vec<ReconciledMessage>,
+        blockchain_config: &ton_block::ConfigParams,
+    ) {
+        if reconciled.is_empty() {
+            return;
+        }
+
+        let current_vset = match blockchain_config.validator_set() {
+            Ok(vset) => vset,
+            Err(e) => {
+                tracing::error!("failed to journal reconciled messages: {e:?}");
+                return;
+            }
+        };
+
+        for ReconciledMessage { message, tx_hash } in reconciled {
+            // NOTE: Messages from the previous rounds are no longer related to the
+            // current elections (which are held for the round after the current one)
+            if message.created_at < current_vset.utime_since() {
+                continue;
+            }
+
+            self.journal.record(
+                current_vset.utime_until(),
+                JournalEvent::MessageReconciled {
+                    purpose: message.purpose,
+                    msg_hash: message.hash,
+                    tx_hash,
+                },
+            );
+        }
+    }
+
+    /// Classifies the outcome of the finished elections
+    ///
+    /// Returns whether there are elections which were not conducted yet
+    async fn reconcile_journal(
+        &self,
+        elector: &Elector,
+        blockchain_config: &ton_block::ConfigParams,
+    ) -> Result<bool> {
+        let pending = self.journal.with_entries(journal::pending_elections)?;
+        if pending.is_empty() {
+            return Ok(false);
+        }
+
+        let elector_data = elector
+            .get_data()
+            .await
+            .context("failed to get elector data")?;
+
+        // Validator set which was elected (if it is still available)
+        let next_vset = blockchain_config.next_validator_set().ok();
+
+        let mut has_unfinished = false;
+        for election in pending {
+            let election_id = election.election_id;
+            if !elector_data.is_past_election(election_id) {
+                // Elections are still in progress
+                has_unfinished |= elector_data.election_id() == Some(election_id);
+                continue;
+            }
+
+            let in_next_vset = match (&next_vset, &election.public_key) {
+                (Some(vset), Some(public_key)) if vset.utime_since() == election_id => Some(
+                    vset.list()
+                        .iter()
+                        .any(|descr| descr.public_key.as_slice() == public_key),
+                ),
+                _ => None,
+            };
+
+            let (outcome, true_stake) = match (&election.participant, election.stake_sent) {
+                (_, false) if election.round_skipped => (ElectionOutcome::RoundSkipped, None),
+                (_, false) => (ElectionOutcome::NotSubmitted, None),
+                (Some(participant), true) => {
+                    match elector_data.frozen_stake(election_id, participant) {
+                        Some(true_stake) => (ElectionOutcome::Elected, Some(true_stake)),
+                        None if in_next_vset == Some(true) => (ElectionOutcome::Elected, None),
+                        // Stakes of not elected participants are returned to credits
+                        None if elector_data.has_unfrozen_stake(participant).is_some() => {
+                            (ElectionOutcome::StakeTooSmall, None)
+                        }
+                        None => (ElectionOutcome::Bounced, None),
+                    }
+                }
+                (None, true) if in_next_vset == Some(true) => (ElectionOutcome::Elected, None),
+                // NOTE: stake could be delivered only by the message
+                // of the previous process which has no participant
+                (None, true) => (ElectionOutcome::Unknown, None),
+            };
+
+            if outcome == ElectionOutcome::Elected {
+                tracing::info!(
+                    election_id,
+                    outcome = outcome.as_str(),
+                    true_stake = true_stake.map(|stake| Tokens(stake).to_string()),
+                    ?in_next_vset,
+                    "reconciled elections"
+                );
+            } else {
+                tracing::warn!(
+                    election_id,
+                    outcome = outcome.as_str(),
+                    participant = ?election.participant.as_ref().map(ToString::to_string),
+                    "validator was not elected"
+                );
+            }
+
+            let event = JournalEvent::Reconciled {
+                outcome,
+                true_stake,
+                in_next_vset,
+            };
+            let mut hook_event = serde_json::to_value(&event)?;
+            hook_event["election_id"] = election_id.into();
+
+            self.journal.record(election_id, event);
+            if let Err(e) = self.hooks.run(HookPoint::OnOutcome, hook_event).await {
+                // NOTE: other elections must still be reconciled
+                tracing::error!(election_id, "outcome hook failed: {e:?}");
+            }
+
+            match outcome {
+                ElectionOutcome::Elected => {
+                    self.notifier.notify(Notification::Elected { election_id });
+                }
+                // Skipped rounds are intentional
+                ElectionOutcome::RoundSkipped => {}
+                _ => self.notifier.notify(Notification::NotElected {
+                    election_id,
+                    outcome,
+                }),
+            }
         }
+
+        Ok(has_unfinished)
     }
 
     async fn ensure_deployed(
         &self,
         validator: &AppConfigValidator,
+        signer: Option<&AppConfigSigner>,
         subscription: &Arc<Subscription>,
     ) -> Result<bool> {
         if matches!(&*self.last_params.lock(), Some(last_params) if last_params == validator) {
@@ -306,7 +818,10 @@ impl ValidationManager {
 
         let ctx = DeploymentContext {
             subscription,
+            signer,
             dirs: &self.dirs,
+            notifier: &self.notifier,
+            spending: &self.spending,
             guard: &self.guard,
         };
 
@@ -322,23 +837,29 @@ impl ValidationManager {
         let interval = Duration::from_secs(10);
         let mut attempts = 6;
         loop {
-            match node_rpc.get_stats().await? {
+            let status = match node_rpc.get_stats().await? {
                 NodeStats::Running(stats) => {
                     if stats.mc_time_diff < self.params.max_time_diff
                         && (only_mc || stats.sc_time_diff < self.params.max_time_diff)
                     {
                         break Ok(true);
                     }
+                    format!(
+                        "mc_time_diff: {}s, sc_time_diff: {}s",
+                        stats.mc_time_diff, stats.sc_time_diff
+                    )
                 }
                 NodeStats::NotReady(sync_status) => {
                     tracing::trace!(%sync_status, "node not synced");
+                    sync_status.to_string()
                 }
-            }
+            };
 
             attempts -= 1;
             if attempts > 0 {
                 tokio::time::sleep(interval).await;
             } else {
+                self.notifier.notify(Notification::NodeOutOfSync { status });
                 break Ok(false);
             }
         }
@@ -358,7 +879,10 @@ pub struct ValidationParams {
 #[derive(Clone, Copy)]
 struct DeploymentContext<'a> {
     subscription: &'a Arc<Subscription>,
+    signer: Option<&'a AppConfigSigner>,
     dirs: &'a ProjectDirs,
+    notifier: &'a Notifier,
+    spending: &'a SpendingGuard,
     guard: &'a Mutex<()>,
 }
 
@@ -369,108 +893,624 @@ struct ElectionsContext<'a> {
     election_id: u32,
     timings: ton_block::ConfigParam15,
     blockchain_config: &'a ton_block::ConfigParams,
+    dirs: &'a ProjectDirs,
+    journal: &'a ElectionJournal,
+    election_keys: &'a ElectionKeysStore,
+    notifier: &'a Notifier,
+    hooks: &'a Hooks,
+    spending: &'a SpendingGuard,
+    sweep: Option<&'a AppConfigSweep>,
+    plan: Option<&'a ElectionPlan>,
     guard: &'a Mutex<()>,
 }
 
+impl DeploymentContext<'_> {
+    /// Sends an internal message from the wallet with the spending policy checks
+    ///
+    /// NOTE: multisig confirmations are waited after releasing the wallet lock
+    async fn call(&self, wallet: &Wallet, message: InternalMessage) -> Result<TransactionWithHash> {
+        let call = {
+            let _wallet_lock = self.dirs.lock_wallet(WALLET_LOCK_HOLDER).await?;
+            self.spending.check(&message, self.notifier)?;
+            let call = wallet.call(message.clone()).await?;
+            self.spending.record(&message, self.notifier);
+            call
+        };
+
+        match call {
+            WalletCall::Delivered(tx) => Ok(tx),
+            WalletCall::Submitted(pending) => pending.wait().await,
+        }
+    }
+}
+
 impl ElectionsContext<'_> {
+    /// Saves completed DePool rounds before they are dropped by the contract
+    fn record_rewards(&self, depool: &ton_block::MsgAddressInt, rounds: &depool::RoundsMap) {
+        if self.plan.is_some() {
+            return;
+        }
+
+        match RewardHistory::new(&self.dirs.depool_rewards).update(depool, rounds) {
+            Ok(0) => {}
+            Ok(added) => tracing::info!(added, "updated DePool rewards history"),
+            Err(e) => tracing::error!("failed to update DePool rewards history: {e:?}"),
+        }
+    }
+
+    /// Sends an internal message from the wallet or adds it to the plan in dry-run mode
+    async fn call(
+        &self,
+        wallet: &Wallet,
+        message: InternalMessage,
+        purpose: &'static str,
+    ) -> Result<Option<TransactionWithHash>> {
+        // NOTE: elector is always an allowed destination
+        self.spending.allow(self.elector.address());
+
+        if let Some(plan) = self.plan {
+            if let Some(reason) = self.spending.explain(&message)? {
+                plan.note(format!(
+                    "{purpose} will be blocked by the spending policy: {reason}"
+                ));
+            }
+            plan.message(purpose, wallet.address(), &message);
+            return Ok(None);
+        }
+
+        let call = {
+            let _wallet_lock = self.dirs.lock_wallet(WALLET_LOCK_HOLDER).await?;
+            self.spending.check(&message, self.notifier)?;
+            let call = wallet.call(message.clone()).await?;
+            self.spending.record(&message, self.notifier);
+            call
+        };
+        Ok(Some(track_confirmation(call, purpose)))
+    }
+
+    /// Transfers the wallet balance above `keep` and the reserve to the cold wallet
+    async fn sweep(&self, wallet: &Wallet, keep: u128) -> Result<()> {
+        let Some(sweep) = self.sweep else {
+            return Ok(());
+        };
+
+        let balance = wallet.get_balance().await?.unwrap_or_default();
+        let keep = keep + sweep.reserve as u128 + ONE_EVER;
+        let Some(amount) = balance
+            .checked_sub(keep)
+            .filter(|&amount| amount >= sweep.min_amount as u128)
+        else {
+            tracing::debug!(
+                balance = %Tokens(balance),
+                keep = %Tokens(keep),
+                "nothing to sweep"
+            );
+            return Ok(());
+        };
+
+        // NOTE: cold wallet is always an allowed destination
+        self.spending.allow(&sweep.address);
+        let message = InternalMessage::empty(sweep.address.clone(), amount, false);
+
+        if let Some(plan) = self.plan {
+            if let Some(reason) = self.spending.explain(&message)? {
+                plan.note(format!(
+                    "sweep will be blocked by the spending policy: {reason}"
+                ));
+            }
+            plan.message("sweep surplus", wallet.address(), &message);
+            return Ok(());
+        }
+
+        // Prevent shutdown during the transfer
+        let _guard = self.guard.lock().await;
+
+        tracing::info!(
+            destination = %sweep.address,
+            amount = %Tokens(amount),
+            "sweeping wallet surplus"
+        );
+        let tx = {
+            let _wallet_lock = self.dirs.lock_wallet(WALLET_LOCK_HOLDER).await?;
+            self.spending.check(&message, self.notifier)?;
+            let tx = wallet
+                .transfer(message.clone())
+                .await
+                .context("failed to sweep wallet surplus")?;
+            self.spending.record(&message, self.notifier);
+            tx
+        };
+
+        self.record(JournalEvent::Swept {
+            destination: sweep.address.clone(),
+            amount,
+            tx_hash: *tx.hash.as_array(),
+        });
+        Ok(())
+    }
+
+    /// Waits for the wallet balance or only checks it in dry-run mode
+    async fn wait_for_balance(&self, wallet: &Wallet, target: u128) -> Result<()> {
+        match self.plan {
+            Some(plan) => {
+                let balance = wallet.get_balance().await?.unwrap_or_default();
+                if balance < target {
+                    plan.note(format!(
+                        "waiting until wallet balance {} is at least {}",
+                        Tokens(balance),
+                        Tokens(target)
+                    ));
+                }
+            }
+            None => {
+                const BALANCE_ALERT_DELAY: Duration = Duration::from_secs(600);
+
+                let wait = wallet.wait_for_balance(target);
+                tokio::pin!(wait);
+
+                match tokio::time::timeout(BALANCE_ALERT_DELAY, &mut wait).await {
+                    Ok(res) => {
+                        res?;
+                    }
+                    Err(_) => {
+                        let balance = wallet.get_balance().await?.unwrap_or_default();
+                        self.notifier.notify(Notification::BalanceWaiting {
+                            address: wallet.address().clone(),
+                            balance,
+                            target,
+                        });
+                        wait.await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds validator keys to the node and builds the elector payload.
+    /// Returns `None` if the keys for these elections are already in the members list
+    /// (or, for a top-up, if they are not).
+    ///
+    /// NOTE: node keystore is not used in dry-run mode
+    async fn prepare_election_payload(
+        &self,
+        participant: &ton_block::MsgAddressInt,
+        stake_factor: u32,
+        top_up: bool,
+    ) -> Result<Option<elector::ElectionPayload>> {
+        if self.plan.is_some() {
+            let payload =
+                self.elector
+                    .participation_preview(self.election_id, participant, stake_factor)?;
+            return Ok(Some(elector::ElectionPayload {
+                keys: elector::ValidatorKeys {
+                    permanent_key_hash: [0; 32],
+                    public_key: [0; 32],
+                    adnl_addr: [0; 32],
+                },
+                payload,
+            }));
+        }
+
+        // Reuse keys from the previous attempts
+        let mut generated = self.election_keys.get(self.election_id)?;
+        let is_member = matches!(
+            &generated.public_key,
+            Some(public_key) if self.elector_data.is_member(public_key)
+        );
+        match (is_member, top_up) {
+            (true, false) => {
+                tracing::info!("validator key is already in the elections members");
+                return Ok(None);
+            }
+            (false, true) => {
+                tracing::warn!("validator key for the submitted stake not found");
+                return Ok(None);
+            }
+            _ => {}
+        }
+
+        let payload = self
+            .elector
+            .participate_in_elections(
+                self.election_id,
+                participant,
+                stake_factor,
+                &self.timings,
+                &mut generated,
+                |keys| self.election_keys.store(self.election_id, keys),
+            )
+            .await
+            .context("failed to prepare new validator key")?;
+
+        tracing::info!("generated election payload");
+        self.record_keys_generated(participant, &payload.keys);
+        Ok(Some(payload))
+    }
+
+    /// Checks the stake against the elector limits before sending it
+    fn check_stake(
+        &self,
+        participant: &ton_block::MsgAddressInt,
+        stake: u128,
+        stake_factor: u32,
+    ) -> Result<Option<preflight::StakeCheck>> {
+        let limits = preflight::StakeLimits::new(self.blockchain_config)?;
+        let election = self.elector_data.current_election_stakes(participant);
+        let check = preflight::check_stake(&limits, election.as_ref(), stake, stake_factor);
+
+        for adjustment in &check.adjustments {
+            tracing::warn!(%participant, "{adjustment}");
+            if let Some(plan) = self.plan {
+                plan.note(adjustment.clone());
+            }
+        }
+
+        if let Some(reason) = &check.reason {
+            tracing::warn!(%participant, "stake refused: {reason}");
+            if let Some(plan) = self.plan {
+                plan.note(format!("stake refused: {reason}"));
+            }
+            return Ok(None);
+        }
+
+        Ok(Some(check))
+    }
+
+    fn record(&self, event: JournalEvent) {
+        if self.plan.is_some() {
+            return;
+        }
+
+        let notification = match &event {
+            JournalEvent::StakeSent {
+                participant, stake, ..
+            } => Some(Notification::StakeSent {
+                election_id: self.election_id,
+                participant: participant.clone(),
+                stake: *stake,
+            }),
+            JournalEvent::StakeRecovered { amount } => Some(Notification::StakeRecovered {
+                election_id: self.election_id,
+                amount: *amount,
+            }),
+            _ => None,
+        };
+
+        self.journal.record(self.election_id, event);
+        if let Some(notification) = notification {
+            self.notifier.notify(notification);
+        }
+    }
+
+    /// Runs hooks for the step, only adds a note in dry-run mode
+    async fn run_hook(&self, point: HookPoint, mut event: serde_json::Value) -> Result<()> {
+        event["election_id"] = self.election_id.into();
+        match self.plan {
+            Some(plan) => {
+                if self.hooks.has_scripts(point) {
+                    plan.note(format!("{} hooks will be executed", point.as_str()));
+                }
+                Ok(())
+            }
+            None => self.hooks.run(point, event).await,
+        }
+    }
+
+    /// Same as [`record`], but also runs hooks with the recorded event
+    ///
+    /// [`record`]: Self::record
+    async fn record_with_hook(&self, point: HookPoint, event: JournalEvent) -> Result<()> {
+        let hook_event = serde_json::to_value(&event)?;
+        self.record(event);
+        self.run_hook(point, hook_event).await
+    }
+
+    async fn run_pre_stake_hook(
+        &self,
+        participant: &ton_block::MsgAddressInt,
+        stake: u128,
+        stake_factor: u32,
+        top_up: bool,
+    ) -> Result<()> {
+        self.run_hook(
+            HookPoint::PreStake,
+            serde_json::json!({
+                "participant": participant.to_string(),
+                "stake": stake.to_string(),
+                "stake_factor": stake_factor,
+                "top_up": top_up,
+            }),
+        )
+        .await
+        .context("stake was aborted by the hook")
+    }
+
     async fn check_can_be_elected(&mut self, address: &ton_block::MsgAddressInt) -> Result<bool> {
         self.elector_data = self.elector.get_data().await?;
         let Some(current_election_id) = self.elector_data.election_id() else {
             return Ok(false);
         };
 
+        // Stake of the previous process could be delivered but not processed yet
+        let election_id = self.election_id;
+        if self
+            .journal
+            .with_entries(|entries| journal::stake_delivered(entries, election_id))?
+        {
+            tracing::info!("stake was already delivered before restart");
+            return Ok(false);
+        }
+
         Ok(current_election_id == self.election_id && !self.elector_data.elected(address))
     }
+
+    fn record_already_elected(&self, participant: &ton_block::MsgAddressInt) {
+        self.record(JournalEvent::AlreadyElected {
+            participant: participant.clone(),
+        });
+    }
+
+    fn record_keys_generated(
+        &self,
+        participant: &ton_block::MsgAddressInt,
+        keys: &elector::ValidatorKeys,
+    ) {
+        self.record(JournalEvent::KeysGenerated {
+            participant: participant.clone(),
+            permanent_key_hash: keys.permanent_key_hash,
+            public_key: keys.public_key,
+            adnl_addr: keys.adnl_addr,
+        });
+    }
 }
 
 impl AppConfigValidatorSingle {
-    async fn deploy(&self, _: DeploymentContext<'_>) -> Result<()> {
-        // TODO: deploy validator wallet if it differs from ever wallet
-        Ok(())
+    async fn deploy(&self, ctx: DeploymentContext<'_>) -> Result<()> {
+        // NOTE: ever wallet is deployed with the first transfer
+        deploy_multisig_wallet(
+            self.wallet_type,
+            &self.address,
+            self.wallet_deploy.as_ref(),
+            ctx,
+        )
+        .await
     }
 
-    async fn elect(
-        self,
-        keypair: ed25519_dalek::Keypair,
-        mut ctx: ElectionsContext<'_>,
-    ) -> Result<()> {
+    async fn elect(self, signer: WalletSigner, mut ctx: ElectionsContext<'_>) -> Result<()> {
         tracing::info!(
             election_id = ctx.election_id,
             address = %self.address,
-            stake = %Tokens(self.stake_per_round),
+            stake = ?self.stake_policy(),
             stake_factor = ?self.stake_factor,
             "election as single"
         );
 
-        let wallet = Wallet::new(-1, keypair, ctx.subscription.clone());
-        anyhow::ensure!(
-            wallet.address() == &self.address,
-            "validator wallet address mismatch"
-        );
+        let wallet = Wallet::with_signer(
+            self.wallet_type,
+            self.address.clone(),
+            signer,
+            ctx.subscription.clone(),
+        )?;
+
+        ctx.record(JournalEvent::Started {
+            mode: ValidatorMode::Single,
+            wallet: wallet.address().clone(),
+        });
 
         if let Some(stake) = ctx.elector_data.has_unfrozen_stake(wallet.address()) {
-            wallet.wait_for_balance(2 * ONE_EVER).await?;
+            ctx.wait_for_balance(&wallet, 2 * ONE_EVER).await?;
 
             // Prevent shutdown during stake recovery
             let _guard = ctx.guard.lock().await;
 
             // Send recover stake message
             tracing::info!(stake = %Tokens(stake.as_u128()), "recovering stake");
-            wallet
-                .call(ctx.elector.recover_stake()?)
+            ctx.call(&wallet, ctx.elector.recover_stake()?, "recover stake")
                 .await
                 .context("failed to recover stake")?;
+
+            ctx.record(JournalEvent::StakeRecovered {
+                amount: stake.as_u128(),
+            });
         }
 
+        // Send rewards to the cold wallet, keeping the stake for this round
+        let stake = self.compute_stake(&wallet, &ctx, 0).await?;
+        ctx.sweep(&wallet, stake + 2 * ONE_EVER).await?;
+
         // Check whether validator was already elected before waiting for balance
         if !ctx.check_can_be_elected(wallet.address()).await? {
             tracing::info!("validator already elected");
+            ctx.record_already_elected(wallet.address());
+            if self.stake_top_up {
+                self.top_up_stake(&wallet, &ctx).await?;
+            }
             return Ok(());
         }
 
+        // Compute stake for this round
+        let stake = self.compute_stake(&wallet, &ctx, 0).await?;
+        let stake_factor = self.stake_factor.unwrap_or(DEFAULT_STAKE_FACTOR);
+        let Some(preflight::StakeCheck {
+            stake,
+            stake_factor,
+            ..
+        }) = ctx.check_stake(wallet.address(), stake, stake_factor)?
+        else {
+            return Ok(());
+        };
+
         // Wait until validator wallet balance is enough
-        let target_balance = self.stake_per_round as u128 + 2 * ONE_EVER;
-        wallet.wait_for_balance(target_balance).await?;
+        let target_balance = stake + 2 * ONE_EVER;
+        ctx.wait_for_balance(&wallet, target_balance).await?;
 
         // Check whether validator was already elected after waiting for balance
         if !ctx.check_can_be_elected(wallet.address()).await? {
             tracing::info!("validator already elected");
+            ctx.record_already_elected(wallet.address());
             return Ok(());
         }
 
-        let signature_id = ctx.subscription.get_signature_id().await?;
+        ctx.run_pre_stake_hook(wallet.address(), stake, stake_factor, false)
+            .await?;
 
         // Prevent shutdown while electing
         let _guard = ctx.guard.lock().await;
 
         // Prepare node for elections
-        let payload = ctx
-            .elector
-            .participate_in_elections(
-                ctx.election_id,
-                wallet.address(),
-                self.stake_factor.unwrap_or(DEFAULT_STAKE_FACTOR),
-                &ctx.timings,
-                signature_id,
-            )
-            .await
-            .context("failed to prepare new validator key")?;
-        tracing::info!("generated election payload");
+        let Some(elector::ElectionPayload { payload, .. }) = ctx
+            .prepare_election_payload(wallet.address(), stake_factor, false)
+            .await?
+        else {
+            ctx.record_already_elected(wallet.address());
+            return Ok(());
+        };
 
         // Send election message
-        wallet
-            .call(InternalMessage {
-                dst: ctx.elector.address().clone(),
-                amount: self.stake_per_round as u128 + ONE_EVER,
-                payload,
-                bounce: false,
-            })
+        let message = InternalMessage {
+            dst: ctx.elector.address().clone(),
+            amount: stake + ONE_EVER,
+            payload,
+            bounce: false,
+        };
+        let Some(tx) = ctx
+            .call(&wallet, message, STAKE_PURPOSE)
             .await
-            .context("failed to participate in elections")?;
+            .context("failed to participate in elections")?
+        else {
+            return Ok(());
+        };
 
         // Done
         tracing::info!("sent validator stake");
-        Ok(())
+        ctx.record_with_hook(
+            HookPoint::PostStake,
+            JournalEvent::StakeSent {
+                participant: wallet.address().clone(),
+                stake,
+                stake_factor,
+                tx_hash: *tx.hash.as_array(),
+            },
+        )
+        .await
+    }
+
+    /// Sends an additional stake if the submitted one is less than the desired stake
+    ///
+    /// NOTE: top-up is sent with the same validator key and is limited
+    /// by the elections deadline as the main stake.
+    async fn top_up_stake(&self, wallet: &Wallet, ctx: &ElectionsContext<'_>) -> Result<()> {
+        let Some(submitted) = ctx.elector_data.member_stake(wallet.address()) else {
+            // Elections have changed
+            return Ok(());
+        };
+
+        // Compute the desired stake including the submitted one
+        let stake = self.compute_stake(wallet, ctx, submitted).await?;
+        let stake_factor = self.stake_factor.unwrap_or(DEFAULT_STAKE_FACTOR);
+        let Some(preflight::StakeCheck {
+            stake,
+            stake_factor,
+            ..
+        }) = ctx.check_stake(wallet.address(), stake, stake_factor)?
+        else {
+            return Ok(());
+        };
+
+        // Elector ignores too small stakes
+        let total_stake = ctx
+            .elector_data
+            .current_election_stakes(wallet.address())
+            .map(|election| election.total_stake)
+            .unwrap_or_default();
+        let min_top_up = std::cmp::max(ONE_EVER, total_stake >> 12);
+
+        let Some(top_up) = stake
+            .checked_sub(submitted)
+            .filter(|&top_up| top_up >= min_top_up)
+        else {
+            return Ok(());
+        };
+
+        // Don't wait for the balance here to not miss the elections end
+        let balance = wallet.get_balance().await?.unwrap_or_default();
+        if balance < top_up + 2 * ONE_EVER {
+            tracing::warn!(
+                submitted = %Tokens(submitted),
+                top_up = %Tokens(top_up),
+                balance = %Tokens(balance),
+                "wallet balance is not enough for the stake top-up"
+            );
+            return Ok(());
+        }
+
+        ctx.run_pre_stake_hook(wallet.address(), top_up, stake_factor, true)
+            .await?;
+
+        // Prevent shutdown while electing
+        let _guard = ctx.guard.lock().await;
+
+        // Sign payload with the same keys
+        let Some(elector::ElectionPayload { payload, .. }) = ctx
+            .prepare_election_payload(wallet.address(), stake_factor, true)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        // Send additional stake
+        tracing::info!(
+            submitted = %Tokens(submitted),
+            top_up = %Tokens(top_up),
+            "topping up validator stake"
+        );
+        let message = InternalMessage {
+            dst: ctx.elector.address().clone(),
+            amount: top_up + ONE_EVER,
+            payload,
+            bounce: false,
+        };
+        let Some(tx) = ctx
+            .call(wallet, message, "top up stake")
+            .await
+            .context("failed to top up stake")?
+        else {
+            return Ok(());
+        };
+
+        // Done
+        tracing::info!("sent validator stake top-up");
+        ctx.record_with_hook(
+            HookPoint::PostStake,
+            JournalEvent::StakeToppedUp {
+                participant: wallet.address().clone(),
+                amount: top_up,
+                tx_hash: *tx.hash.as_array(),
+            },
+        )
+        .await
+    }
+
+    /// Evaluates the stake policy against the wallet balance
+    async fn compute_stake(
+        &self,
+        wallet: &Wallet,
+        ctx: &ElectionsContext<'_>,
+        submitted: u128,
+    ) -> Result<u128> {
+        let policy = self.stake_policy();
+        let stake = match policy {
+            StakePolicy::Fixed { amount } => amount as u128,
+            _ => {
+                let balance = wallet.get_balance().await?.unwrap_or_default();
+                let frozen = ctx.elector_data.total_frozen_stake(wallet.address());
+                preflight::compute_single_stake(&policy, balance + submitted, frozen)
+            }
+        };
+
+        tracing::info!(stake = %Tokens(stake), "computed stake");
+        Ok(stake)
     }
 }
 
@@ -479,6 +1519,7 @@ impl AppConfigValidatorDePool {
         struct LazyWallet<'a> {
             state: Option<Wallet>,
             target: &'a ton_block::MsgAddressInt,
+            ty: WalletType,
             ctx: DeploymentContext<'a>,
         }
 
@@ -487,21 +1528,32 @@ impl AppConfigValidatorDePool {
                 match &mut self.state {
                     Some(wallet) => Ok(wallet),
                     state => {
-                        let keypair = self.ctx.dirs.load_validator_keys()?;
-                        let res = Wallet::new(0, keypair, self.ctx.subscription.clone());
-                        anyhow::ensure!(
-                            res.address() == self.target,
-                            "validator wallet address mismatch"
-                        );
+                        let signer = WalletSigner::load(self.ctx.signer, self.ctx.dirs)?;
+                        let res = Wallet::with_signer(
+                            self.ty,
+                            self.target.clone(),
+                            signer,
+                            self.ctx.subscription.clone(),
+                        )?;
                         Ok(state.get_or_insert(res))
                     }
                 }
             }
         }
 
+        // Ensure that the owner wallet is deployed
+        deploy_multisig_wallet(
+            self.wallet_type,
+            &self.owner,
+            self.wallet_deploy.as_ref(),
+            ctx,
+        )
+        .await?;
+
         let mut wallet = LazyWallet {
             state: None,
             target: &self.owner,
+            ty: self.wallet_type,
             ctx,
         };
 
@@ -510,6 +1562,7 @@ impl AppConfigValidatorDePool {
             self.depool.clone(),
             ctx.subscription.clone(),
         );
+        ctx.spending.allow(depool.address());
 
         // Ensure that depool is deployed
         if depool
@@ -556,14 +1609,12 @@ impl AppConfigValidatorDePool {
                 let _guard = ctx.guard.lock();
 
                 tracing::info!("transferring initial funds to the DePool");
-                wallet
-                    .call(InternalMessage::empty(
-                        depool.address().clone(),
-                        balance,
-                        false,
-                    ))
-                    .await
-                    .context("failed to transfer funds to the DePool contract")?;
+                ctx.call(
+                    wallet,
+                    InternalMessage::empty(depool.address().clone(), balance, false),
+                )
+                .await
+                .context("failed to transfer funds to the DePool contract")?;
             }
 
             // Prevent shutdown during the operation
@@ -609,8 +1660,7 @@ impl AppConfigValidatorDePool {
 
                 // Set strategy as an allowed participant
                 tracing::info!(%strategy, "setting DePool strategy");
-                wallet
-                    .call(depool.set_allowed_participant(&strategy)?)
+                ctx.call(wallet, depool.set_allowed_participant(&strategy)?)
                     .await
                     .context("failed to set DePool strategy")?;
                 tracing::info!(%strategy, "DePool strategy successfully updated");
@@ -621,11 +1671,7 @@ impl AppConfigValidatorDePool {
         Ok(())
     }
 
-    async fn elect(
-        self,
-        keypair: ed25519_dalek::Keypair,
-        mut ctx: ElectionsContext<'_>,
-    ) -> Result<()> {
+    async fn elect(self, signer: WalletSigner, mut ctx: ElectionsContext<'_>) -> Result<()> {
         tracing::info!(
             election_id = ctx.election_id,
             depool = %self.depool,
@@ -635,11 +1681,17 @@ impl AppConfigValidatorDePool {
             "election as DePool"
         );
 
-        let wallet = Wallet::new(0, keypair, ctx.subscription.clone());
-        anyhow::ensure!(
-            wallet.address() == &self.owner,
-            "validator wallet address mismatch"
-        );
+        let wallet = Wallet::with_signer(
+            self.wallet_type,
+            self.owner.clone(),
+            signer,
+            ctx.subscription.clone(),
+        )?;
+
+        ctx.record(JournalEvent::Started {
+            mode: ValidatorMode::DePool,
+            wallet: wallet.address().clone(),
+        });
 
         let depool = DePool::new(
             self.depool_type,
@@ -660,73 +1712,130 @@ impl AppConfigValidatorDePool {
         );
         anyhow::ensure!(depool_info.proxies.len() == 2, "invalid DePool proxies");
 
+        ctx.spending.allow(depool.address());
+        for proxy in &depool_info.proxies {
+            ctx.spending.allow(proxy);
+        }
+
         // Ensure that depool and proxy balances are enough
         self.maintain_balances(&wallet, &depool, &ctx)
             .await
             .context("failed to maintain balances")?;
 
+        // Owner wallet surplus is sent to the cold wallet after the stakes are added
+        let sweep_keep = compute_target_balance_for_depool(Some(depool_info.validator_assurance));
+
+        // Closed DePool only needs ticktocks to complete rounds and return stakes
+        if depool_info.pool_closed {
+            tracing::info!("DePool is closed, skipping elections");
+            ctx.wait_for_balance(&wallet, 2 * ONE_EVER).await?;
+            ctx.call(&wallet, depool.ticktock()?, "ticktock")
+                .await
+                .context("failed to send ticktock")?;
+            if ctx.plan.is_none() {
+                ctx.record_with_hook(HookPoint::PostTicktock, JournalEvent::TicktockSent)
+                    .await?;
+            }
+            ctx.record(JournalEvent::RoundSkipped {
+                reason: "DePool is closed".to_owned(),
+            });
+            return ctx.sweep(&wallet, sweep_keep).await;
+        }
+
         // Update depool
-        let (round_id, step) = match self
+        let round = self
             .update_depool(&wallet, &depool, &depool_info, depool_state, &ctx)
             .await
-            .context("failed to update depool")?
-        {
+            .context("failed to update depool")?;
+        ctx.sweep(&wallet, sweep_keep).await?;
+
+        let (round_id, step, round_stake) = match round {
             Some(round) => round,
             None => {
                 tracing::info!("skipping round");
+                ctx.record(JournalEvent::RoundSkipped {
+                    reason: "initial fake round".to_owned(),
+                });
                 return Ok(());
             }
         };
 
+        // NOTE: There is no stake top-up for DePool. The whole round stake is sent
+        // with the validator request and new stakes are added to the pooling round.
+        // (`stake_top_up` is rejected for DePool configs at load)
+        let proxy = &depool_info.proxies[round_id as usize % 2];
+
         if step != depool::RoundStep::WaitingValidatorRequest {
             tracing::info!("depool is not waiting for the validator request");
+            ctx.record(JournalEvent::RoundSkipped {
+                reason: format!("unexpected round step: {step:?}"),
+            });
             return Ok(());
         }
 
-        let proxy = &depool_info.proxies[round_id as usize % 2];
-
         // Check whether proxy was already elected after waiting for balance
         if !ctx.check_can_be_elected(proxy).await? {
             tracing::info!(%proxy, "proxy already elected");
+            ctx.record_already_elected(proxy);
             return Ok(());
         }
 
+        // Check round stake against the elector limits
+        let stake_factor = self.stake_factor.unwrap_or(DEFAULT_STAKE_FACTOR);
+        let Some(preflight::StakeCheck { stake_factor, .. }) =
+            ctx.check_stake(proxy, round_stake as u128, stake_factor)?
+        else {
+            ctx.record(JournalEvent::RoundSkipped {
+                reason: "round stake was refused by pre-flight checks".to_owned(),
+            });
+            return Ok(());
+        };
+
         // Wait until validator wallet balance is enough
-        wallet.wait_for_balance(2 * ONE_EVER).await?;
+        ctx.wait_for_balance(&wallet, 2 * ONE_EVER).await?;
 
-        let signature_id = ctx.subscription.get_signature_id().await?;
+        ctx.run_pre_stake_hook(proxy, round_stake as u128, stake_factor, false)
+            .await?;
 
         // Prevent shutdown while electing
         let _guard = ctx.guard.lock().await;
 
         // Prepare node for elections
-        let payload = ctx
-            .elector
-            .participate_in_elections(
-                ctx.election_id,
-                proxy,
-                self.stake_factor.unwrap_or(DEFAULT_STAKE_FACTOR),
-                &ctx.timings,
-                signature_id,
-            )
-            .await
-            .context("failed to prepare new validator key")?;
-        tracing::info!("generated election payload");
+        let Some(elector::ElectionPayload { payload, .. }) = ctx
+            .prepare_election_payload(proxy, stake_factor, false)
+            .await?
+        else {
+            ctx.record_already_elected(proxy);
+            return Ok(());
+        };
 
         // Send election message
-        wallet
-            .call(InternalMessage {
-                dst: depool.address().clone(),
-                amount: ONE_EVER,
-                payload,
-                bounce: false,
-            })
+        let message = InternalMessage {
+            dst: depool.address().clone(),
+            amount: ONE_EVER,
+            payload,
+            bounce: false,
+        };
+        let Some(tx) = ctx
+            .call(&wallet, message, STAKE_PURPOSE)
             .await
-            .context("failed to participate in elections")?;
+            .context("failed to participate in elections")?
+        else {
+            return Ok(());
+        };
 
         // Done
         tracing::info!("sent validator stake");
-        Ok(())
+        ctx.record_with_hook(
+            HookPoint::PostStake,
+            JournalEvent::StakeSent {
+                participant: proxy.clone(),
+                stake: round_stake as u128,
+                stake_factor,
+                tx_hash: *tx.hash.as_array(),
+            },
+        )
+        .await
     }
 
     async fn maintain_balances(
@@ -744,16 +1853,19 @@ impl AppConfigValidatorDePool {
                 "replenishing depool contracts"
             );
 
-            wallet.wait_for_balance(message.amount + ONE_EVER).await?;
+            ctx.wait_for_balance(wallet, message.amount + ONE_EVER)
+                .await?;
 
             // Prevent shutdown during operation
             let _guard = ctx.guard.lock().await;
 
             // Send some funds to depool contracts
-            wallet
-                .call(message)
+            let (target, amount) = (message.dst.clone(), message.amount);
+            ctx.call(wallet, message, "replenish depool contracts")
                 .await
                 .context("failed to replenish depool contracts")?;
+
+            ctx.record(JournalEvent::BalanceReplenished { target, amount });
         }
 
         Ok(())
@@ -766,7 +1878,7 @@ impl AppConfigValidatorDePool {
         depool_info: &depool::DePoolInfo,
         mut depool_state: ton_block::AccountStuff,
         ctx: &ElectionsContext<'_>,
-    ) -> Result<Option<(u64, depool::RoundStep)>> {
+    ) -> Result<Option<(u64, depool::RoundStep, u64)>> {
         const TICKTOCK_INTERVAL: Duration = Duration::from_secs(60);
 
         let mut attempts = 4;
@@ -780,11 +1892,43 @@ impl AppConfigValidatorDePool {
             // Get all depool rounds
             let rounds = depool
                 .get_rounds(&depool_state)
-                .context("failed to get depool rounds")?
-                .into_values()
-                .collect::<Vec<_>>();
+                .context("failed to get depool rounds")?;
+            ctx.record_rewards(depool.address(), &rounds);
+
+            let rounds = rounds.into_values().collect::<Vec<_>>();
             anyhow::ensure!(rounds.len() == 4, "DePool rounds number mismatch");
 
+            // Report rounds which were completed without a reward
+            if ctx.plan.is_none() {
+                let prefix = format!("{}:", depool.address());
+                let mut reported = Vec::new();
+                for round in &rounds {
+                    if matches!(
+                        round.completion_reason,
+                        depool::CompletionReason::Undefined
+                            | depool::CompletionReason::FakeRound
+                            | depool::CompletionReason::RewardIsReceived
+                    ) {
+                        continue;
+                    }
+
+                    let key = format!("{prefix}{}", round.id);
+                    ctx.notifier.notify_once(
+                        key.clone(),
+                        Notification::DepoolRoundFailed {
+                            depool: depool.address().clone(),
+                            round_id: round.id,
+                            reason: format!("{:?}", round.completion_reason),
+                        },
+                    );
+                    reported.push(key);
+                }
+
+                // Forget rounds which are no longer stored in the DePool
+                ctx.notifier
+                    .retain_reported(|key| !key.starts_with(&prefix) || reported.contains(key));
+            }
+
             let prev_round = &rounds[0];
             let target_round = &rounds[1];
             let pooling_round = &rounds[2];
@@ -810,8 +1954,7 @@ impl AppConfigValidatorDePool {
             {
                 if remaining_stake > 0 {
                     remaining_stake = std::cmp::max(remaining_stake, depool_info.min_stake);
-                    wallet
-                        .wait_for_balance(remaining_stake as u128 + ONE_EVER)
+                    ctx.wait_for_balance(wallet, remaining_stake as u128 + ONE_EVER)
                         .await?;
 
                     // Prevent shutdown during sending stake
@@ -819,16 +1962,24 @@ impl AppConfigValidatorDePool {
 
                     // Send recover stake message
                     tracing::info!(stake = %Tokens(remaining_stake), "adding ordinary stake");
-                    wallet
-                        .call(depool.add_ordinary_stake(remaining_stake)?)
+                    let message = depool.add_ordinary_stake(remaining_stake)?;
+                    ctx.call(wallet, message, "add ordinary stake")
                         .await
                         .context("failed to add ordinary stake")?;
+
+                    ctx.record(JournalEvent::OrdinaryStakeAdded {
+                        amount: remaining_stake,
+                    });
                 }
             }
 
             if target_round.supposed_elected_at == ctx.election_id {
                 // Return target round if it is configured
-                break Ok(Some((target_round.id, target_round.step)));
+                break Ok(Some((
+                    target_round.id,
+                    target_round.step,
+                    target_round.stake,
+                )));
             } else if sent_ticktock
                 && target_round.completion_reason == depool::CompletionReason::FakeRound
             {
@@ -841,13 +1992,19 @@ impl AppConfigValidatorDePool {
             }
 
             // Update rounds
-            wallet.wait_for_balance(2 * ONE_EVER).await?;
+            ctx.wait_for_balance(wallet, 2 * ONE_EVER).await?;
 
             tracing::info!("sending ticktock");
-            wallet
-                .call(depool.ticktock()?)
+            ctx.call(wallet, depool.ticktock()?, "ticktock")
                 .await
                 .context("failed to send ticktock")?;
+            if let Some(plan) = ctx.plan {
+                // Rounds can't be updated without sending a ticktock
+                plan.note("target round will be known only after the ticktock");
+                break Ok(None);
+            }
+            ctx.record_with_hook(HookPoint::PostTicktock, JournalEvent::TicktockSent)
+                .await?;
             sent_ticktock = true;
             tokio::time::sleep(TICKTOCK_INTERVAL).await;
 
@@ -927,6 +2084,56 @@ impl Timeline {
     }
 }
 
+/// Deploys the validator multisig wallet if it was not deployed yet
+async fn deploy_multisig_wallet(
+    wallet_type: WalletType,
+    address: &ton_block::MsgAddressInt,
+    params: Option<&AppConfigMultisigDeploymentParams>,
+    ctx: DeploymentContext<'_>,
+) -> Result<()> {
+    if !wallet_type.is_multisig() {
+        return Ok(());
+    }
+
+    let signer = WalletSigner::load(ctx.signer, ctx.dirs)?;
+    let wallet = Wallet::with_signer(
+        wallet_type,
+        address.clone(),
+        signer,
+        ctx.subscription.clone(),
+    )?;
+
+    if wallet
+        .is_deployed()
+        .await
+        .context("failed to check wallet")?
+    {
+        tracing::info!("multisig wallet was already deployed");
+        return Ok(());
+    }
+    tracing::info!("multisig wallet was not deployed yet");
+
+    // Load deployment params
+    let params = params.context("multisig deployment params not found")?;
+    let tvc = params.load_tvc()?;
+    let custodians = params.parse_custodians()?;
+
+    // Wait until there are enough funds for the deployment
+    wallet.wait_for_balance(ONE_EVER).await?;
+
+    // Prevent shutdown during the operation
+    let _guard = ctx.guard.lock().await;
+
+    tracing::info!("deploying multisig wallet");
+    wallet
+        .deploy_multisig(&tvc, custodians, params.req_confirms)
+        .await
+        .context("failed to deploy multisig wallet")?;
+    tracing::info!("successfully deployed multisig wallet");
+
+    Ok(())
+}
+
 impl Wallet {
     async fn wait_for_balance(&self, target: u128) -> Result<u128> {
         let interval = Duration::from_secs(1);
@@ -983,16 +2190,52 @@ impl Cluster {
 }
 
 impl ProjectDirs {
-    fn load_validator_keys(&self) -> Result<ed25519_dalek::Keypair> {
-        let keys = StoredKeys::load(&self.validator_keys)
-            .context("failed to load validator wallet keys")?;
-        Ok(keys.as_keypair())
-    }
-
     fn load_depool_keys(&self) -> Result<ed25519_dalek::Keypair> {
         let keys = StoredKeys::load(&self.depool_keys).context("failed to load DePool keys")?;
         Ok(keys.as_keypair())
     }
 }
 
-const DEFAULT_STAKE_FACTOR: u32 = 196608;
+/// Validator wallet balance which is enough for the two next stakes
+pub fn compute_target_balance_for_single(stake_per_round: u64) -> u128 {
+    Wallet::INITIAL_BALANCE + stake_per_round as u128 * 2
+}
+
+/// DePool owner wallet balance which is enough for the deployment and two assurances
+pub fn compute_target_balance_for_depool(validator_assurance: Option<u64>) -> u128 {
+    match validator_assurance {
+        Some(assurance) => {
+            Wallet::INITIAL_BALANCE + DePool::INITIAL_BALANCE + assurance as u128 * 2
+        }
+        None => Wallet::INITIAL_BALANCE,
+    }
+}
+
+pub const DEFAULT_STAKE_FACTOR: u32 = 196608;
+
+/// Returns the call transaction, multisig confirmations are tracked in background
+fn track_confirmation(call: WalletCall, purpose: &str) -> TransactionWithHash {
+    let tx = call.tx().clone();
+    if let WalletCall::Submitted(pending) = call {
+        let purpose = purpose.to_owned();
+        tokio::spawn(async move {
+            match pending.wait().await {
+                Ok(tx) => tracing::info!(
+                    purpose,
+                    tx_hash = ?tx.hash,
+                    "multisig transaction was confirmed"
+                ),
+                Err(e) => tracing::error!(purpose, "multisig transaction failed: {e:?}"),
+            }
+        });
+    }
+    tx
+}
+
+const RECONCILE_INTERVAL: u32 = 60;
+
+const TOP_UP_CHECK_INTERVAL: u32 = 600;
+
+const STAKE_PURPOSE: &str = "participate in elections";
+
+const WALLET_LOCK_HOLDER: &str = "validator-manager";
@@ -0,0 +1,94 @@
+use broxus_util::serde_string;
+use serde::Serialize;
+
+use crate::contracts::{self, InternalMessage};
+
+/// Actions which would be performed during the elections
+#[derive(Default)]
+pub struct ElectionPlan {
+    pub timeline: Option<String>,
+    pub election_id: Option<u32>,
+    pub random_shift: Option<u32>,
+    actions: parking_lot::Mutex<Vec<PlannedAction>>,
+}
+
+impl ElectionPlan {
+    pub fn wait(&self, seconds: u32, reason: impl Into<String>) {
+        self.actions.lock().push(PlannedAction::Wait {
+            seconds,
+            reason: reason.into(),
+        });
+    }
+
+    pub fn note(&self, message: impl Into<String>) {
+        self.actions.lock().push(PlannedAction::Note {
+            message: message.into(),
+        });
+    }
+
+    pub fn message(
+        &self,
+        purpose: &'static str,
+        source: &ton_block::MsgAddressInt,
+        message: &InternalMessage,
+    ) {
+        self.actions.lock().push(PlannedAction::Message {
+            purpose,
+            source: source.clone(),
+            destination: message.dst.clone(),
+            amount: message.amount,
+            bounce: message.bounce,
+            payload: describe_payload(&message.payload),
+        });
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "timeline": self.timeline,
+            "election_id": self.election_id,
+            "random_shift": self.random_shift,
+            "actions": &*self.actions.lock(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case", tag = "action")]
+enum PlannedAction {
+    Wait {
+        seconds: u32,
+        reason: String,
+    },
+    Note {
+        message: String,
+    },
+    Message {
+        purpose: &'static str,
+        #[serde(with = "serde_string")]
+        source: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        destination: ton_block::MsgAddressInt,
+        #[serde(with = "serde_string")]
+        amount: u128,
+        bounce: bool,
+        payload: serde_json::Value,
+    },
+}
+
+fn describe_payload(payload: &ton_types::Cell) -> serde_json::Value {
+    if payload.bit_length() == 0 && payload.references_count() == 0 {
+        return serde_json::Value::Null;
+    }
+
+    match contracts::decode_internal_payload(payload) {
+        Ok(Some(decoded)) => serde_json::json!({
+            "method": decoded.method,
+            "input": decoded.input,
+        }),
+        _ => serde_json::json!({
+            "boc": ton_types::serialize_toc(payload)
+                .map(base64::encode)
+                .unwrap_or_default(),
+        }),
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{Context, Result};
+use broxus_util::serde_string;
+use serde::Serialize;
+
+use crate::config::StakePolicy;
+use crate::contracts::elector::CurrentElectionStakes;
+use crate::contracts::ONE_EVER;
+use crate::util::Tokens;
+
+/// Elector requires the stake factor to be at least 1.0
+pub const MIN_STAKE_FACTOR: u32 = 65536;
+
+/// Stake limits from the blockchain config (ConfigParam16 and ConfigParam17)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StakeLimits {
+    #[serde(with = "serde_string")]
+    pub min_stake: u128,
+    #[serde(with = "serde_string")]
+    pub max_stake: u128,
+    #[serde(with = "serde_string")]
+    pub min_total_stake: u128,
+    pub max_stake_factor: u32,
+    pub max_validators: u32,
+}
+
+impl StakeLimits {
+    pub fn new(config: &ton_block::ConfigParams) -> Result<Self> {
+        let stakes_config = config.stakes_config().context("invalid stakes config")?;
+        let validators_count = config
+            .validators_count()
+            .context("invalid validators count")?;
+
+        Ok(Self {
+            min_stake: stakes_config.min_stake.as_u128(),
+            max_stake: stakes_config.max_stake.as_u128(),
+            min_total_stake: stakes_config.min_total_stake.as_u128(),
+            max_stake_factor: stakes_config.max_stake_factor,
+            max_validators: validators_count.max_validators.as_u32(),
+        })
+    }
+}
+
+/// Result of the stake pre-flight check
+#[derive(Debug, Clone, Serialize)]
+pub struct StakeCheck {
+    pub accepted: bool,
+    #[serde(with = "serde_string")]
+    pub stake: u128,
+    pub stake_factor: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub adjustments: Vec<String>,
+}
+
+impl StakeCheck {
+    fn refuse(mut self, reason: String) -> Self {
+        self.accepted = false;
+        self.reason = Some(reason);
+        self
+    }
+}
+
+/// Checks whether the stake can be accepted by the elector,
+/// clamps it to the max stake and stake factor to the max stake factor
+pub fn check_stake(
+    limits: &StakeLimits,
+    election: Option<&CurrentElectionStakes>,
+    stake: u128,
+    stake_factor: u32,
+) -> StakeCheck {
+    let mut check = StakeCheck {
+        accepted: true,
+        stake,
+        stake_factor,
+        reason: None,
+        adjustments: Vec::new(),
+    };
+
+    // Check stake factor
+    if stake_factor < MIN_STAKE_FACTOR {
+        return check.refuse(format!(
+            "stake factor {stake_factor} is less than {MIN_STAKE_FACTOR} (1.0)"
+        ));
+    }
+    if stake_factor > limits.max_stake_factor {
+        check.stake_factor = limits.max_stake_factor;
+        check.adjustments.push(format!(
+            "stake factor {stake_factor} was limited to the max stake factor {}",
+            limits.max_stake_factor
+        ));
+    }
+
+    // Check stake bounds
+    let min_stake = match election {
+        Some(election) => std::cmp::max(limits.min_stake, election.min_stake),
+        None => limits.min_stake,
+    };
+    if stake < min_stake {
+        return check.refuse(format!(
+            "stake {} is less than the min stake {}",
+            Tokens(stake),
+            Tokens(min_stake)
+        ));
+    }
+    if stake > limits.max_stake {
+        check.stake = limits.max_stake;
+        check.adjustments.push(format!(
+            "stake {} was limited to the max stake {}",
+            Tokens(stake),
+            Tokens(limits.max_stake)
+        ));
+    }
+
+    // Check whether the stake can compete with other participants
+    if let Some(election) = election {
+        let max_validators = limits.max_validators as usize;
+        if max_validators > 0 && election.stakes.len() >= max_validators {
+            let threshold = election.stakes[max_validators - 1];
+            if check.stake < threshold {
+                return check.refuse(format!(
+                    "stake {} is less than the smallest stake {} among top {max_validators} participants",
+                    Tokens(check.stake),
+                    Tokens(threshold)
+                ));
+            }
+        }
+    }
+
+    check
+}
+
+/// Computes the single validator stake using the wallet balance and the stake frozen in the elector
+pub fn compute_single_stake(policy: &StakePolicy, balance: u128, frozen: u128) -> u128 {
+    // Keep some tokens for the fees
+    policy.compute(balance.saturating_sub(2 * ONE_EVER), frozen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> StakeLimits {
+        StakeLimits {
+            min_stake: 10 * ONE_EVER,
+            max_stake: 1000 * ONE_EVER,
+            min_total_stake: 100 * ONE_EVER,
+            max_stake_factor: 3 * MIN_STAKE_FACTOR,
+            max_validators: 3,
+        }
+    }
+
+    #[test]
+    fn accepts_stake_within_limits() {
+        let check = check_stake(&limits(), None, 100 * ONE_EVER, MIN_STAKE_FACTOR);
+        assert!(check.accepted);
+        assert_eq!(check.stake, 100 * ONE_EVER);
+        assert_eq!(check.stake_factor, MIN_STAKE_FACTOR);
+        assert!(check.adjustments.is_empty());
+    }
+
+    #[test]
+    fn clamps_stake_and_stake_factor() {
+        let check = check_stake(&limits(), None, 2000 * ONE_EVER, 10 * MIN_STAKE_FACTOR);
+        assert!(check.accepted);
+        assert_eq!(check.stake, 1000 * ONE_EVER);
+        assert_eq!(check.stake_factor, 3 * MIN_STAKE_FACTOR);
+        assert_eq!(check.adjustments.len(), 2);
+    }
+
+    #[test]
+    fn refuses_invalid_stake() {
+        let check = check_stake(&limits(), None, 100 * ONE_EVER, MIN_STAKE_FACTOR - 1);
+        assert!(!check.accepted);
+
+        let check = check_stake(&limits(), None, ONE_EVER, MIN_STAKE_FACTOR);
+        assert!(!check.accepted);
+
+        // Elections min stake is higher than the config one
+        let election = CurrentElectionStakes {
+            min_stake: 200 * ONE_EVER,
+            total_stake: 0,
+            stakes: Vec::new(),
+        };
+        let check = check_stake(&limits(), Some(&election), 100 * ONE_EVER, MIN_STAKE_FACTOR);
+        assert!(!check.accepted);
+    }
+
+    #[test]
+    fn refuses_stake_below_top_participants() {
+        let election = CurrentElectionStakes {
+            min_stake: 0,
+            total_stake: 1500 * ONE_EVER,
+            stakes: vec![800 * ONE_EVER, 500 * ONE_EVER, 200 * ONE_EVER],
+        };
+
+        let check = check_stake(&limits(), Some(&election), 100 * ONE_EVER, MIN_STAKE_FACTOR);
+        assert!(!check.accepted);
+
+        // Clamped stake is compared with other participants
+        let check = check_stake(
+            &limits(),
+            Some(&election),
+            5000 * ONE_EVER,
+            MIN_STAKE_FACTOR,
+        );
+        assert!(check.accepted);
+        assert_eq!(check.stake, 1000 * ONE_EVER);
+    }
+
+    #[test]
+    fn single_stake_keeps_fees() {
+        let policy = StakePolicy::Balance { reserve: 0 };
+        assert_eq!(
+            compute_single_stake(&policy, 100 * ONE_EVER, 0),
+            98 * ONE_EVER
+        );
+        assert_eq!(compute_single_stake(&policy, ONE_EVER, 0), 0);
+    }
+}
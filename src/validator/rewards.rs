@@ -0,0 +1,328 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use broxus_util::serde_string;
+use serde::{Deserialize, Serialize};
+
+use crate::contracts::depool::{CompletionReason, Round, RoundsMap};
+use crate::util::write_file_atomic;
+
+const YEAR_SEC: f64 = 365.0 * 86400.0;
+
+/// History of the completed DePool rounds (the contract keeps only four rounds)
+pub struct RewardHistory {
+    path: PathBuf,
+}
+
+impl RewardHistory {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn load(&self) -> Result<Vec<RoundReward>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(data) => serde_json::from_str(&data).context("invalid DePool rewards history"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e).context("failed to read DePool rewards history"),
+        }
+    }
+
+    /// Adds completed rounds to the history, returns the number of new rounds
+    pub fn update(&self, depool: &ton_block::MsgAddressInt, rounds: &RoundsMap) -> Result<usize> {
+        let mut entries = self.load()?;
+
+        let mut added = 0;
+        let mut changed = false;
+        for round in rounds.values() {
+            let Some(reward) = RoundReward::from_round(depool, round) else {
+                continue;
+            };
+
+            match entries
+                .iter_mut()
+                .find(|entry| &entry.depool == depool && entry.id == round.id)
+            {
+                Some(entry) if *entry == reward => continue,
+                Some(entry) => *entry = reward,
+                None => {
+                    entries.push(reward);
+                    added += 1;
+                }
+            }
+            changed = true;
+        }
+
+        if changed {
+            entries.sort_by(|a, b| a.supposed_elected_at.cmp(&b.supposed_elected_at));
+            self.save(&entries)?;
+        }
+        Ok(added)
+    }
+
+    fn save(&self, entries: &[RoundReward]) -> Result<()> {
+        let data = serde_json::to_string_pretty(entries)
+            .context("failed to serialize DePool rewards history")?;
+        write_file_atomic(&self.path, data).context("failed to save DePool rewards history")
+    }
+}
+
+/// Completed DePool round
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundReward {
+    #[serde(with = "serde_string")]
+    pub depool: ton_block::MsgAddressInt,
+    pub id: u64,
+    pub supposed_elected_at: u32,
+    pub unfreeze: u32,
+    pub completion_reason: String,
+    #[serde(with = "serde_string")]
+    pub stake: u64,
+    #[serde(with = "serde_string")]
+    pub recovered_stake: u64,
+    #[serde(with = "serde_string")]
+    pub validator_stake: u64,
+    #[serde(with = "serde_string")]
+    pub participant_reward: u64,
+    pub participant_qty: u32,
+}
+
+impl RoundReward {
+    /// Returns `None` for rounds which are not completed yet and for fake rounds
+    pub fn from_round(depool: &ton_block::MsgAddressInt, round: &Round) -> Option<Self> {
+        if matches!(
+            round.completion_reason,
+            CompletionReason::Undefined | CompletionReason::FakeRound
+        ) {
+            return None;
+        }
+
+        Some(Self {
+            depool: depool.clone(),
+            id: round.id,
+            supposed_elected_at: round.supposed_elected_at,
+            unfreeze: round.unfreeze,
+            completion_reason: format!("{:?}", round.completion_reason),
+            stake: round.stake,
+            recovered_stake: round.recovered_stake,
+            validator_stake: round.validator_stake,
+            participant_reward: round.participant_reward,
+            participant_qty: round.participant_qty,
+        })
+    }
+
+    /// Whether the stake was used for validation (rewarded or punished)
+    pub fn is_staked(&self) -> bool {
+        self.recovered_stake != self.stake
+            || matches!(
+                self.completion_reason.as_str(),
+                "RewardIsReceived" | "ValidatorIsPunished"
+            )
+    }
+
+    /// Round result received from the elector, negative for the punished rounds
+    pub fn total_reward(&self) -> i128 {
+        self.recovered_stake as i128 - self.stake as i128
+    }
+
+    /// Part of the round result which was kept or covered by the validator
+    pub fn validator_reward(&self) -> i128 {
+        self.total_reward() - self.participant_result()
+    }
+
+    /// Round result of the participants
+    ///
+    /// NOTE: losses are covered by the validator stake first
+    pub fn participant_result(&self) -> i128 {
+        match self.total_reward() {
+            total if total >= 0 => self.participant_reward as i128,
+            total => std::cmp::min(total + self.validator_stake as i128, 0),
+        }
+    }
+
+    /// Validator part of the total reward (0..1), zero for the rounds without reward
+    pub fn validator_fee_share(&self) -> f64 {
+        match self.total_reward() {
+            total if total > 0 => self.validator_reward() as f64 / total as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// Participants result relative to the round stake, negative for the losses
+    pub fn round_yield(&self) -> f64 {
+        match self.stake {
+            0 => 0.0,
+            stake => self.participant_result() as f64 / stake as f64,
+        }
+    }
+
+    /// Round yield scaled to a year by the period while the stake is frozen
+    pub fn annualized_yield(&self) -> f64 {
+        match self.unfreeze.saturating_sub(self.supposed_elected_at) {
+            0 => 0.0,
+            period => self.round_yield() * YEAR_SEC / period as f64,
+        }
+    }
+}
+
+/// Average annualized yield of the staked rounds including the losses
+pub fn average_annualized_yield<'a, I>(entries: I) -> Option<f64>
+where
+    I: IntoIterator<Item = &'a RoundReward>,
+{
+    let (sum, count) = entries
+        .into_iter()
+        .filter(|entry| entry.is_staked())
+        .fold((0.0, 0usize), |(sum, count), entry| {
+            (sum + entry.annualized_yield(), count + 1)
+        });
+    (count > 0).then(|| sum / count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    const ONE: u64 = 1_000_000_000;
+    const ROUND_PERIOD: u32 = 2 * 86400;
+
+    fn round(
+        reason: &str,
+        stake: u64,
+        recovered_stake: u64,
+        participant_reward: u64,
+    ) -> RoundReward {
+        RoundReward {
+            depool: ton_block::MsgAddressInt::from_str(&format!("0:{}", hex::encode([1; 32])))
+                .unwrap(),
+            id: 1,
+            supposed_elected_at: 0,
+            unfreeze: ROUND_PERIOD,
+            completion_reason: reason.to_owned(),
+            stake,
+            recovered_stake,
+            validator_stake: 100 * ONE,
+            participant_reward,
+            participant_qty: 10,
+        }
+    }
+
+    fn depool_round(id: u64, reason: CompletionReason, recovered_stake: u64) -> Round {
+        Round {
+            id,
+            supposed_elected_at: id as u32 * ROUND_PERIOD,
+            unfreeze: ROUND_PERIOD,
+            stake_held_for: 0,
+            vset_hash_in_election_phase: Default::default(),
+            step: crate::contracts::depool::RoundStep::Completing,
+            completion_reason: reason,
+            stake: 1000 * ONE,
+            recovered_stake,
+            unused: 0,
+            is_validator_stake_completed: false,
+            participant_reward: 0,
+            participant_qty: 10,
+            validator_stake: 100 * ONE,
+            validator_remaining_stake: 0,
+            handled_stakes_and_rewards: 0,
+        }
+    }
+
+    #[test]
+    fn history_is_updated() {
+        let path = std::env::temp_dir().join(format!(
+            "nodekeeper-rewards-{}-update.json",
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+
+        let history = RewardHistory::new(&path);
+        let depool =
+            ton_block::MsgAddressInt::from_str(&format!("0:{}", hex::encode([1; 32]))).unwrap();
+
+        let mut rounds = RoundsMap::new();
+        rounds.insert(1, depool_round(1, CompletionReason::Undefined, 0));
+        rounds.insert(2, depool_round(2, CompletionReason::FakeRound, 0));
+        assert_eq!(history.update(&depool, &rounds).unwrap(), 0);
+        assert!(!path.exists());
+
+        rounds.insert(3, depool_round(3, CompletionReason::RewardIsReceived, 0));
+        assert_eq!(history.update(&depool, &rounds).unwrap(), 1);
+        assert_eq!(history.load().unwrap()[0].recovered_stake, 0);
+
+        // Changed rounds are saved without new entries
+        rounds.insert(
+            3,
+            depool_round(3, CompletionReason::RewardIsReceived, 1010 * ONE),
+        );
+        assert_eq!(history.update(&depool, &rounds).unwrap(), 0);
+        let entries = history.load().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].recovered_stake, 1010 * ONE);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn rewarded_round() {
+        let entry = round("RewardIsReceived", 1000 * ONE, 1010 * ONE, 8 * ONE);
+        assert!(entry.is_staked());
+        assert_eq!(entry.total_reward(), 10 * ONE as i128);
+        assert_eq!(entry.participant_result(), 8 * ONE as i128);
+        assert_eq!(entry.validator_reward(), 2 * ONE as i128);
+        assert!((entry.validator_fee_share() - 0.2).abs() < 1e-9);
+        assert!((entry.round_yield() - 0.008).abs() < 1e-9);
+
+        let expected = 0.008 * YEAR_SEC / ROUND_PERIOD as f64;
+        assert!((entry.annualized_yield() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn punished_round() {
+        // Loss is covered by the validator stake
+        let entry = round("ValidatorIsPunished", 1000 * ONE, 950 * ONE, 0);
+        assert!(entry.is_staked());
+        assert_eq!(entry.total_reward(), -50 * ONE as i128);
+        assert_eq!(entry.participant_result(), 0);
+        assert_eq!(entry.validator_reward(), -50 * ONE as i128);
+        assert_eq!(entry.validator_fee_share(), 0.0);
+        assert_eq!(entry.round_yield(), 0.0);
+
+        // Loss above the validator stake is taken from the participants
+        let entry = round("ValidatorIsPunished", 1000 * ONE, 850 * ONE, 0);
+        assert_eq!(entry.participant_result(), -50 * ONE as i128);
+        assert_eq!(entry.validator_reward(), -100 * ONE as i128);
+        assert!((entry.round_yield() + 0.05).abs() < 1e-9);
+        assert!(entry.annualized_yield() < 0.0);
+    }
+
+    #[test]
+    fn not_staked_round() {
+        let entry = round("ElectionsAreLost", 1000 * ONE, 1000 * ONE, 0);
+        assert!(!entry.is_staked());
+        assert_eq!(entry.total_reward(), 0);
+        assert_eq!(entry.round_yield(), 0.0);
+
+        // Staked round without profit is still staked
+        let entry = round("RewardIsReceived", 1000 * ONE, 1000 * ONE, 0);
+        assert!(entry.is_staked());
+    }
+
+    #[test]
+    fn average_yield_includes_losses() {
+        assert_eq!(average_annualized_yield(std::iter::empty()), None);
+
+        let rewarded = round("RewardIsReceived", 1000 * ONE, 1010 * ONE, 10 * ONE);
+        let punished = round("ValidatorIsPunished", 1000 * ONE, 890 * ONE, 0);
+        let skipped = round("NoValidatorRequest", 1000 * ONE, 1000 * ONE, 0);
+
+        let entries = [rewarded.clone(), punished.clone(), skipped];
+        let expected = (rewarded.annualized_yield() + punished.annualized_yield()) / 2.0;
+        assert!((average_annualized_yield(&entries).unwrap() - expected).abs() < 1e-9);
+        // Participants lost 10 tokens and received 10 tokens
+        assert!(average_annualized_yield(&entries).unwrap().abs() < 1e-9);
+    }
+}
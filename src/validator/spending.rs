@@ -0,0 +1,415 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use broxus_util::serde_string;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use super::control::{ManagerControl, PendingSpending};
+use crate::config::AppConfigSpendingPolicy;
+use crate::contracts::InternalMessage;
+use crate::notifier::{Notification, Notifier};
+use crate::util::{write_file_atomic, Tokens};
+
+const DAY_SEC: u32 = 86400;
+
+/// Enforces the spending policy for the wallet transfers made by the manager
+pub struct SpendingGuard {
+    path: PathBuf,
+    control: Arc<ManagerControl>,
+    state: parking_lot::Mutex<SpendingState>,
+}
+
+#[derive(Default)]
+struct SpendingState {
+    policy: Option<Arc<AppConfigSpendingPolicy>>,
+    allowed: Vec<ton_block::MsgAddressInt>,
+}
+
+impl SpendingGuard {
+    pub fn new<P: AsRef<Path>>(path: P, control: Arc<ManagerControl>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            control,
+            state: Default::default(),
+        }
+    }
+
+    /// Replaces the policy and resets the known destinations
+    pub fn set_policy(&self, policy: Option<&AppConfigSpendingPolicy>) -> Result<()> {
+        let mut state = self.state.lock();
+        state.allowed = match policy {
+            Some(policy) => policy.parse_allowed_destinations()?,
+            None => Vec::new(),
+        };
+        state.policy = policy.map(|policy| Arc::new(policy.clone()));
+        Ok(())
+    }
+
+    /// Adds the destination which is known to the manager (elector, DePool, proxies)
+    pub fn allow(&self, address: &ton_block::MsgAddressInt) {
+        let mut state = self.state.lock();
+        if !state.allowed.contains(address) {
+            state.allowed.push(address.clone());
+        }
+    }
+
+    /// Returns the reason why the transfer is not allowed (without consuming approvals)
+    pub fn explain(&self, message: &InternalMessage) -> Result<Option<String>> {
+        Ok(match self.check_limits(message)? {
+            Some(reason) => Some(reason),
+            None if self.requires_approval(message) => {
+                Some("operator approval is required".to_owned())
+            }
+            None => None,
+        })
+    }
+
+    /// Checks the transfer and sends an alert if it is blocked
+    pub fn check(&self, message: &InternalMessage, notifier: &Notifier) -> Result<()> {
+        let mut reason = self.check_limits(message)?;
+        if reason.is_none() && self.requires_approval(message) {
+            let hash = transfer_hash(message);
+            let approved = self.control.take_spending_approval(PendingSpending {
+                hash,
+                destination: message.dst.clone(),
+                amount: message.amount,
+                approved_until: None,
+            });
+            if !approved {
+                reason = Some(format!(
+                    "amount is above the confirmation threshold, approve it with \
+                    `nodekeeper validator ctl approve-spending:{}`",
+                    hex::encode(hash)
+                ));
+            }
+        }
+
+        let Some(reason) = reason else {
+            return Ok(());
+        };
+
+        tracing::warn!(
+            destination = %message.dst,
+            amount = %Tokens(message.amount),
+            reason,
+            "transfer blocked by the spending policy"
+        );
+        notifier.notify(Notification::SpendingBlocked {
+            destination: message.dst.clone(),
+            amount: message.amount,
+            reason: reason.clone(),
+        });
+        anyhow::bail!("transfer blocked by the spending policy: {reason}")
+    }
+
+    /// Adds the sent transfer to the ledger
+    ///
+    /// NOTE: the transfer is already sent at this point, so errors are only reported
+    pub fn record(&self, message: &InternalMessage, notifier: &Notifier) {
+        if let Err(e) = self.try_record(message) {
+            tracing::error!(
+                destination = %message.dst,
+                amount = %Tokens(message.amount),
+                "failed to record sent transfer: {e:?}"
+            );
+            notifier.notify(Notification::Error {
+                message: format!(
+                    "Transfer {} to {} was sent but not recorded in the spending ledger: {e}",
+                    Tokens(message.amount),
+                    message.dst
+                ),
+            });
+        }
+    }
+
+    fn try_record(&self, message: &InternalMessage) -> Result<()> {
+        let now = broxus_util::now();
+
+        let mut entries = self.load()?;
+        entries.retain(|entry| entry.timestamp + DAY_SEC > now);
+        entries.push(SpendingEntry {
+            timestamp: now,
+            destination: message.dst.clone(),
+            amount: message.amount,
+        });
+
+        let data = serde_json::to_string_pretty(&entries)
+            .context("failed to serialize spending ledger")?;
+        write_file_atomic(&self.path, data).context("failed to save spending ledger")
+    }
+
+    fn check_limits(&self, message: &InternalMessage) -> Result<Option<String>> {
+        let (policy, allowed) = {
+            let state = self.state.lock();
+            match &state.policy {
+                Some(policy) => (policy.clone(), state.allowed.contains(&message.dst)),
+                None => return Ok(None),
+            }
+        };
+
+        if let Some(max) = policy.max_per_tx {
+            if message.amount > max as u128 {
+                return Ok(Some(format!(
+                    "amount exceeds the per-transaction limit {}",
+                    Tokens(max)
+                )));
+            }
+        }
+
+        if !allowed {
+            return Ok(Some("destination is not allowed".to_owned()));
+        }
+
+        if let Some(max) = policy.max_per_day {
+            let since = broxus_util::now().saturating_sub(DAY_SEC);
+            let spent = self
+                .load()?
+                .iter()
+                .filter(|entry| entry.timestamp > since)
+                .map(|entry| entry.amount)
+                .sum::<u128>();
+
+            if spent.saturating_add(message.amount) > max as u128 {
+                return Ok(Some(format!(
+                    "amount exceeds the daily limit {} ({} already spent)",
+                    Tokens(max),
+                    Tokens(spent)
+                )));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn requires_approval(&self, message: &InternalMessage) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state.policy.as_ref().and_then(|policy| policy.confirmation_threshold),
+            Some(threshold) if message.amount > threshold as u128
+        )
+    }
+
+    fn load(&self) -> Result<Vec<SpendingEntry>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(data) => serde_json::from_str(&data).context("invalid spending ledger"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(e).context("failed to read spending ledger"),
+        }
+    }
+}
+
+/// Identifies the transfer for the operator approval
+///
+/// NOTE: only the method id is used from the payload, because it contains
+/// fields which are different for each attempt (e.g. `query_id`)
+fn transfer_hash(message: &InternalMessage) -> [u8; 32] {
+    let method_id = ton_types::SliceData::load_cell(message.payload.clone())
+        .and_then(|mut payload| payload.get_next_u32())
+        .ok();
+
+    sha2::Sha256::new()
+        .chain(message.dst.to_string())
+        .chain(message.amount.to_be_bytes())
+        .chain([message.bounce as u8])
+        .chain(method_id.map(u32::to_be_bytes).unwrap_or_default())
+        .finalize()
+        .into()
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpendingEntry {
+    timestamp: u32,
+    #[serde(with = "serde_string")]
+    destination: ton_block::MsgAddressInt,
+    #[serde(with = "serde_string")]
+    amount: u128,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::contracts::{elector, ONE_EVER};
+    use crate::validator::control::ControlCommand;
+
+    fn make_guard(name: &str, policy: AppConfigSpendingPolicy) -> SpendingGuard {
+        let path = std::env::temp_dir().join(format!(
+            "nodekeeper-spending-{}-{name}.json",
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+
+        let guard = SpendingGuard::new(path, Default::default());
+        guard.set_policy(Some(&policy)).unwrap();
+        guard
+    }
+
+    fn policy() -> AppConfigSpendingPolicy {
+        AppConfigSpendingPolicy {
+            max_per_tx: None,
+            max_per_day: None,
+            confirmation_threshold: None,
+            allowed_destinations: Vec::new(),
+        }
+    }
+
+    fn make_address(byte: u8) -> ton_block::MsgAddressInt {
+        ton_block::MsgAddressInt::from_str(&format!("0:{}", hex::encode([byte; 32]))).unwrap()
+    }
+
+    fn transfer(byte: u8, amount: u128) -> InternalMessage {
+        InternalMessage::empty(make_address(byte), amount, false)
+    }
+
+    #[test]
+    fn no_policy() {
+        let guard = make_guard("no-policy", policy());
+        guard.set_policy(None).unwrap();
+
+        let message = transfer(1, 1000 * ONE_EVER);
+        assert!(guard.check(&message, &Default::default()).is_ok());
+    }
+
+    #[test]
+    fn allowed_destinations() {
+        let guard = make_guard(
+            "allowed",
+            AppConfigSpendingPolicy {
+                allowed_destinations: vec![make_address(1).to_string()],
+                ..policy()
+            },
+        );
+        let notifier = Notifier::default();
+
+        assert!(guard.check(&transfer(1, ONE_EVER), &notifier).is_ok());
+        assert!(guard.check(&transfer(2, ONE_EVER), &notifier).is_err());
+
+        guard.allow(&make_address(2));
+        assert!(guard.check(&transfer(2, ONE_EVER), &notifier).is_ok());
+
+        // Known destinations are reset with the policy
+        guard.set_policy(Some(&policy())).unwrap();
+        assert!(guard.check(&transfer(1, ONE_EVER), &notifier).is_err());
+    }
+
+    #[test]
+    fn transfer_limits() {
+        let guard = make_guard(
+            "limits",
+            AppConfigSpendingPolicy {
+                max_per_tx: Some(10 * ONE_EVER as u64),
+                max_per_day: Some(15 * ONE_EVER as u64),
+                ..policy()
+            },
+        );
+        guard.allow(&make_address(1));
+        let notifier = Notifier::default();
+
+        let message = transfer(1, 11 * ONE_EVER);
+        assert!(guard.explain(&message).unwrap().is_some());
+        assert!(guard.check(&message, &notifier).is_err());
+
+        let message = transfer(1, 10 * ONE_EVER);
+        assert!(guard.check(&message, &notifier).is_ok());
+        guard.record(&message, &notifier);
+
+        // Daily limit includes the recorded transfers
+        assert!(guard.check(&transfer(1, 6 * ONE_EVER), &notifier).is_err());
+        assert!(guard.check(&transfer(1, 5 * ONE_EVER), &notifier).is_ok());
+    }
+
+    #[test]
+    fn scoped_approval() {
+        let guard = make_guard(
+            "approval",
+            AppConfigSpendingPolicy {
+                confirmation_threshold: Some(5 * ONE_EVER as u64),
+                ..policy()
+            },
+        );
+        guard.allow(&make_address(1));
+        let notifier = Notifier::default();
+
+        assert!(guard.check(&transfer(1, 5 * ONE_EVER), &notifier).is_ok());
+
+        // Transfer above the threshold waits for the approval
+        let message = transfer(1, 10 * ONE_EVER);
+        assert!(guard.check(&message, &notifier).is_err());
+
+        let pending = guard.control.status().pending_spending.unwrap();
+        assert_eq!(pending.hash, transfer_hash(&message));
+        assert_eq!(pending.amount, message.amount);
+        assert!(pending.approved_until.is_none());
+
+        // Only the pending transfer can be approved
+        let other = ControlCommand::ApproveSpending { hash: [0; 32] };
+        assert!(guard.control.execute(other).is_err());
+
+        let approve = ControlCommand::ApproveSpending { hash: pending.hash };
+        guard.control.execute(approve.clone()).unwrap();
+
+        // Approval is consumed by the same transfer
+        assert!(guard.check(&message, &notifier).is_ok());
+        assert!(guard.control.status().pending_spending.is_none());
+        assert!(guard.check(&message, &notifier).is_err());
+
+        // Approval is not applied to a different transfer
+        guard.control.execute(approve).unwrap();
+        assert!(guard.check(&transfer(1, 11 * ONE_EVER), &notifier).is_err());
+        assert!(guard.check(&message, &notifier).is_err());
+    }
+
+    #[test]
+    fn approval_survives_new_payload() {
+        let guard = make_guard(
+            "retry",
+            AppConfigSpendingPolicy {
+                confirmation_threshold: Some(5 * ONE_EVER as u64),
+                ..policy()
+            },
+        );
+        guard.allow(&make_address(1));
+        let notifier = Notifier::default();
+
+        let stake = || InternalMessage {
+            dst: make_address(1),
+            amount: 10 * ONE_EVER,
+            payload: elector::UnsignedParticipantData {
+                election_id: 100,
+                address: [1; 32].into(),
+                max_factor: 196608,
+                public_key: [2; 32].into(),
+                adnl_addr: [3; 32].into(),
+            }
+            .sign([0; 64])
+            .unwrap(),
+            bounce: true,
+        };
+
+        let message = stake();
+        assert!(guard.check(&message, &notifier).is_err());
+        let hash = guard.control.status().pending_spending.unwrap().hash;
+        guard
+            .control
+            .execute(ControlCommand::ApproveSpending { hash })
+            .unwrap();
+
+        // Payload is rebuilt with a new `query_id` on retry
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let retry = stake();
+        assert_ne!(message.payload.repr_hash(), retry.payload.repr_hash());
+        assert!(guard.check(&retry, &notifier).is_ok());
+
+        // Transfer without the method call requires another approval
+        let transfer = InternalMessage {
+            payload: Default::default(),
+            ..stake()
+        };
+        assert!(guard.check(&transfer, &notifier).is_err());
+        let pending = guard.control.status().pending_spending.unwrap();
+        assert_ne!(pending.hash, hash);
+    }
+}
@@ -0,0 +1,330 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use sha2::Digest;
+use tokio::sync::Mutex;
+
+use super::control::ManagerControl;
+use super::hooks::{HookPoint, Hooks};
+use super::journal::{ElectionJournal, JournalEvent};
+use super::spending::SpendingGuard;
+use super::Timeline;
+use crate::config::{AppConfig, AppConfigDePoolTicktock, AppConfigSigner, AppConfigValidator};
+use crate::contracts::depool::{self, DePool};
+use crate::contracts::{Wallet, WalletSigner, ONE_EVER};
+use crate::dirs::ProjectDirs;
+use crate::network::{ConfigWithId, NodeTcpRpc, NodeUdpRpc, Subscription};
+use crate::notifier::Notifier;
+use crate::util::Tokens;
+
+const DAY_SEC: u32 = 86400;
+
+/// Sends DePool ticktocks between the elections when rounds are ready to advance
+pub struct DePoolTicker {
+    dirs: ProjectDirs,
+    journal: ElectionJournal,
+    control: Arc<ManagerControl>,
+    notifier: Notifier,
+    hooks: Hooks,
+    spending: SpendingGuard,
+    guard: Arc<Mutex<()>>,
+    subscription: Option<Arc<Subscription>>,
+    /// Hash of the node connection params which were used for the subscription
+    connection_hash: [u8; 32],
+    last_ticked: Option<(u64, depool::RoundStep)>,
+}
+
+impl DePoolTicker {
+    pub fn new(dirs: ProjectDirs, control: Arc<ManagerControl>, guard: Arc<Mutex<()>>) -> Self {
+        let journal = ElectionJournal::new(&dirs.election_journal);
+        let spending = SpendingGuard::new(&dirs.spending_ledger, control.clone());
+        Self {
+            dirs,
+            journal,
+            control,
+            notifier: Default::default(),
+            hooks: Default::default(),
+            spending,
+            guard,
+            subscription: None,
+            connection_hash: Default::default(),
+            last_ticked: None,
+        }
+    }
+
+    pub async fn run(mut self) {
+        const DISABLED_INTERVAL: u32 = 60;
+
+        loop {
+            let interval = match self.try_tick().await {
+                Ok(interval) => interval.unwrap_or(DISABLED_INTERVAL),
+                Err(e) => {
+                    tracing::error!("failed to send scheduled DePool ticktock: {e:?}");
+                    // Reconnect on the next attempt
+                    self.subscription = None;
+                    DISABLED_INTERVAL
+                }
+            };
+            tokio::time::sleep(Duration::from_secs(interval as u64)).await;
+        }
+    }
+
+    /// Returns the next check interval or `None` if ticktocks are disabled
+    async fn try_tick(&mut self) -> Result<Option<u32>> {
+        // Read config
+        let mut config = AppConfig::load(&self.dirs.app_config)?;
+        let Some(params) = config.depool_ticktock.take() else {
+            return Ok(None);
+        };
+        let Some(AppConfigValidator::DePool(validator)) = config.validator.take() else {
+            return Ok(None);
+        };
+        if let Some(AppConfigSigner::Offline { .. }) = &config.signer {
+            tracing::debug!("scheduled ticktocks are not supported with the offline signer");
+            return Ok(None);
+        }
+        if self.control.status().paused {
+            return Ok(Some(params.interval_sec));
+        }
+
+        self.notifier.set_sinks(&config.notifications);
+        self.hooks.set_config(&config.hooks);
+        self.spending.set_policy(config.spending_policy.as_ref())?;
+
+        // Recreate subscription when the node connection params are changed
+        let connection_hash: [u8; 32] =
+            sha2::Sha256::digest(&serde_json::to_vec(&(&config.control, &config.adnl))?).into();
+        if self.connection_hash != connection_hash {
+            self.subscription = None;
+            self.connection_hash = connection_hash;
+        }
+
+        // Create subscription once
+        let subscription = match &self.subscription {
+            Some(subscription) => subscription.clone(),
+            None => {
+                let node_tcp_rpc = NodeTcpRpc::new(config.control()?).await?;
+                let node_udp_rpc = NodeUdpRpc::new(config.adnl()?).await?;
+                let subscription = Subscription::new(node_tcp_rpc, node_udp_rpc);
+                self.subscription.insert(subscription).clone()
+            }
+        };
+        subscription.ensure_ready().await?;
+
+        // NOTE: rounds are updated by the validation loop during the elections
+        let ConfigWithId {
+            config: blockchain_config,
+            ..
+        } = subscription.tcp_rpc().get_config_all().await?;
+        let timings = blockchain_config
+            .elector_params()
+            .context("invalid elector params")?;
+        let current_vset = blockchain_config
+            .validator_set()
+            .context("invalid validator set")?;
+        let now = broxus_util::now();
+        if let Timeline::Elections { .. } = Timeline::compute(&timings, &current_vset, now) {
+            tracing::debug!("scheduled ticktocks are paused during the elections");
+            return Ok(Some(params.interval_sec));
+        }
+
+        // Find the round which is waiting for the ticktock
+        let depool = DePool::new(
+            validator.depool_type,
+            validator.depool.clone(),
+            subscription.clone(),
+        );
+        let depool_state = depool.get_state().await?;
+        let rounds = depool
+            .get_rounds(&depool_state)
+            .context("failed to get DePool rounds")?;
+
+        let Some(round) = find_ready_round(&rounds, now) else {
+            return Ok(Some(params.interval_sec));
+        };
+
+        // Wait until the previous ticktock advances the round
+        if self.last_ticked == Some((round.id, round.step)) {
+            tracing::debug!(
+                round_id = round.id,
+                step = ?round.step,
+                "ticktock was already sent for this step"
+            );
+            return Ok(Some(params.interval_sec));
+        }
+
+        if let Some(reason) = self.check_limits(&params, now)? {
+            tracing::warn!(
+                round_id = round.id,
+                step = ?round.step,
+                "skipping scheduled ticktock: {reason}"
+            );
+            return Ok(Some(params.interval_sec));
+        }
+
+        // Prepare wallet
+        let signer = WalletSigner::load(config.signer.as_ref(), &self.dirs)?;
+        let wallet = Wallet::with_signer(
+            validator.wallet_type,
+            validator.owner.clone(),
+            signer,
+            subscription.clone(),
+        )?;
+
+        let balance = wallet.get_balance().await?.unwrap_or_default();
+        if balance < 2 * ONE_EVER {
+            tracing::warn!(
+                balance = %Tokens(balance),
+                "wallet balance is not enough for the scheduled ticktock"
+            );
+            return Ok(Some(params.interval_sec));
+        }
+
+        // NOTE: DePool is always an allowed destination
+        self.spending.allow(depool.address());
+        let message = depool.ticktock()?;
+
+        // Prevent shutdown while sending
+        let _guard = self.guard.lock().await;
+
+        tracing::info!(
+            round_id = round.id,
+            step = ?round.step,
+            "sending scheduled ticktock"
+        );
+        let call = {
+            let _wallet_lock = self.dirs.lock_wallet(WALLET_LOCK_HOLDER).await?;
+            self.spending.check(&message, &self.notifier)?;
+            let call = wallet
+                .call(message.clone())
+                .await
+                .context("failed to send ticktock")?;
+            self.spending.record(&message, &self.notifier);
+            call
+        };
+        let tx = super::track_confirmation(call, "scheduled ticktock");
+        self.last_ticked = Some((round.id, round.step));
+
+        let event = JournalEvent::ScheduledTicktock {
+            round_id: round.id,
+            step: format!("{:?}", round.step),
+            fees: tx.data.total_fees.grams.as_u128(),
+            tx_hash: *tx.hash.as_array(),
+        };
+        let mut hook_event = serde_json::to_value(&event)?;
+        hook_event["election_id"] = round.supposed_elected_at.into();
+
+        self.journal.record(round.supposed_elected_at, event);
+        if let Err(e) = self.hooks.run(HookPoint::PostTicktock, hook_event).await {
+            // NOTE: ticktock was already sent
+            tracing::error!(round_id = round.id, "post ticktock hook failed: {e:?}");
+        }
+
+        Ok(Some(params.interval_sec))
+    }
+
+    /// Returns the reason why the ticktock can't be sent
+    fn check_limits(&self, params: &AppConfigDePoolTicktock, now: u32) -> Result<Option<String>> {
+        let since = now.saturating_sub(DAY_SEC);
+
+        let mut count = 0u32;
+        let mut fees = 0u128;
+        self.journal.with_entries(|entries| {
+            for entry in entries {
+                if entry.timestamp <= since {
+                    continue;
+                }
+                if let JournalEvent::ScheduledTicktock { fees: tx_fees, .. } = &entry.event {
+                    count += 1;
+                    fees = fees.saturating_add(*tx_fees);
+                }
+            }
+        })?;
+
+        Ok(if count >= params.max_per_day {
+            Some(format!(
+                "{count} ticktocks were already sent within 24 hours"
+            ))
+        } else if fees >= params.max_fees_per_day as u128 {
+            Some(format!(
+                "fees limit {} is reached within 24 hours",
+                Tokens(params.max_fees_per_day)
+            ))
+        } else {
+            None
+        })
+    }
+}
+
+/// Returns the round which will advance after the ticktock
+fn find_ready_round(rounds: &depool::RoundsMap, now: u32) -> Option<&depool::Round> {
+    // Give the elector some time to process the new validator set or unfreeze
+    const OFFSET: u32 = 300;
+
+    rounds.values().find(|round| match round.step {
+        depool::RoundStep::WaitingValidationStart => {
+            now >= round.supposed_elected_at.saturating_add(OFFSET)
+        }
+        depool::RoundStep::WaitingUnfreeze => {
+            round.unfreeze > 0 && now >= round.unfreeze.saturating_add(OFFSET)
+        }
+        _ => false,
+    })
+}
+
+const WALLET_LOCK_HOLDER: &str = "validator manager ticktock";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(
+        id: u64,
+        step: depool::RoundStep,
+        supposed_elected_at: u32,
+        unfreeze: u32,
+    ) -> depool::Round {
+        depool::Round {
+            id,
+            supposed_elected_at,
+            unfreeze,
+            stake_held_for: 0,
+            vset_hash_in_election_phase: Default::default(),
+            step,
+            completion_reason: depool::CompletionReason::Undefined,
+            stake: 0,
+            recovered_stake: 0,
+            unused: 0,
+            is_validator_stake_completed: false,
+            participant_reward: 0,
+            participant_qty: 0,
+            validator_stake: 0,
+            validator_remaining_stake: 0,
+            handled_stakes_and_rewards: 0,
+        }
+    }
+
+    #[test]
+    fn ready_rounds() {
+        let mut rounds = depool::RoundsMap::new();
+        rounds.insert(1, round(1, depool::RoundStep::Completed, 0, 0));
+        rounds.insert(
+            2,
+            round(2, depool::RoundStep::WaitingValidationStart, 1000, 0),
+        );
+        rounds.insert(3, round(3, depool::RoundStep::Pooling, 0, 0));
+        assert!(find_ready_round(&rounds, 1000).is_none());
+
+        // Elector needs some time after the validation start
+        let ready = find_ready_round(&rounds, 1300).unwrap();
+        assert_eq!(ready.id, 2);
+
+        rounds.insert(2, round(2, depool::RoundStep::WaitingUnfreeze, 1000, 0));
+        assert!(find_ready_round(&rounds, 5000).is_none());
+
+        rounds.insert(2, round(2, depool::RoundStep::WaitingUnfreeze, 1000, 3000));
+        assert!(find_ready_round(&rounds, 3000).is_none());
+        assert_eq!(find_ready_round(&rounds, 3300).unwrap().id, 2);
+    }
+}